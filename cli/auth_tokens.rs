@@ -0,0 +1,204 @@
+// Copyright 2018-2019 the Deno authors. All rights reserved. MIT license.
+//! Credentials for private module servers, given with the `DENO_AUTH_TOKENS`
+//! environment variable. It is a semicolon separated list of
+//! `token@host[:port]` entries, where a token of the form `username:password`
+//! is sent with basic authentication and any other token as a bearer token:
+//!
+//!   DENO_AUTH_TOKENS=a1b2c3@deno.land;user:pass@example.com:8080
+//!
+//! Credentials are only attached to https requests for their own host and
+//! port, and are never stored in `$DENO_DIR`. A host prefixed with `http://`
+//! also receives them over plain http, e.g. for a registry on localhost:
+//!
+//!   DENO_AUTH_TOKENS=a1b2c3@http://localhost:4545
+use base64;
+use http::uri::Uri;
+
+#[derive(Clone, Debug, PartialEq)]
+enum Credentials {
+  Bearer(String),
+  Basic(String, String),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+struct AuthToken {
+  /// "https", or "http" if the entry opted in to plain http.
+  scheme: String,
+  host: String,
+  /// The given port, or the default port of `scheme`.
+  port: u16,
+  credentials: Credentials,
+}
+
+fn default_port(scheme: &str) -> Option<u16> {
+  match scheme {
+    "http" => Some(80),
+    "https" => Some(443),
+    _ => None,
+  }
+}
+
+/// Splits `[http://]host[:port]` into scheme, host and port.
+fn parse_host(s: &str) -> Option<(String, String, u16)> {
+  let s = s.to_lowercase();
+  let (scheme, host) = if s.starts_with("http://") {
+    ("http", &s["http://".len()..])
+  } else if s.starts_with("https://") {
+    ("https", &s["https://".len()..])
+  } else {
+    ("https", &s[..])
+  };
+  // IPv6 addresses are written in brackets, and contain ':' themselves.
+  let (host, port) = match host.rfind(':') {
+    Some(i) if !host.ends_with(']') => {
+      (&host[..i], host[i + 1..].parse::<u16>().ok()?)
+    }
+    _ => (host, default_port(scheme)?),
+  };
+  if host.is_empty() || host.contains('/') {
+    return None;
+  }
+  Some((scheme.to_string(), host.to_string(), port))
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct AuthTokens(Vec<AuthToken>);
+
+impl AuthTokens {
+  /// Parses the value of `DENO_AUTH_TOKENS`. Malformed entries are skipped
+  /// with a warning.
+  pub fn new(s: &str) -> AuthTokens {
+    let mut tokens = Vec::new();
+    for entry in s.split(';').map(str::trim).filter(|e| !e.is_empty()) {
+      // Tokens may contain '@' themselves, but host names never do.
+      let (token, (scheme, host, port)) = match entry.rfind('@') {
+        Some(i) if i > 0 => match parse_host(&entry[i + 1..]) {
+          Some(host) => (&entry[..i], host),
+          None => {
+            warn!("Ignoring malformed DENO_AUTH_TOKENS entry");
+            continue;
+          }
+        },
+        _ => {
+          warn!("Ignoring malformed DENO_AUTH_TOKENS entry");
+          continue;
+        }
+      };
+      let credentials = match token.find(':') {
+        Some(i) => Credentials::Basic(
+          token[..i].to_string(),
+          token[i + 1..].to_string(),
+        ),
+        None => Credentials::Bearer(token.to_string()),
+      };
+      tokens.push(AuthToken {
+        scheme,
+        host,
+        port,
+        credentials,
+      });
+    }
+    AuthTokens(tokens)
+  }
+
+  /// Value of the `Authorization` header to send with a request for `url`,
+  /// if there are credentials for its scheme, host and port.
+  pub fn authorization(&self, url: &Uri) -> Option<String> {
+    let scheme = url.scheme_str()?.to_lowercase();
+    let host = url.host()?.to_lowercase();
+    let port = url.port_u16().or_else(|| default_port(&scheme))?;
+    self
+      .0
+      .iter()
+      .find(|token| {
+        token.scheme == scheme && token.host == host && token.port == port
+      })
+      .map(|token| match token.credentials {
+        Credentials::Bearer(ref token) => format!("Bearer {}", token),
+        Credentials::Basic(ref username, ref password) => format!(
+          "Basic {}",
+          base64::encode(&format!("{}:{}", username, password))
+        ),
+      })
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn uri(s: &str) -> Uri {
+    s.parse().unwrap()
+  }
+
+  #[test]
+  fn test_auth_tokens() {
+    let tokens =
+      AuthTokens::new("abc123@deno.land;user:p@ss@Example.com:8080");
+    assert_eq!(
+      tokens.authorization(&uri("https://deno.land/std/mod.ts")),
+      Some("Bearer abc123".to_string())
+    );
+    assert_eq!(
+      tokens.authorization(&uri("https://example.com:8080/mod.ts")),
+      Some("Basic dXNlcjpwQHNz".to_string())
+    );
+    // Credentials are only sent to their own host and port.
+    for url in &[
+      "https://example.com/mod.ts",
+      "https://deno.land:8080/mod.ts",
+      "https://evil.deno.land/mod.ts",
+    ] {
+      assert_eq!(tokens.authorization(&uri(url)), None);
+    }
+  }
+
+  #[test]
+  fn test_auth_tokens_default_port() {
+    let tokens = AuthTokens::new("abc@deno.land;def@example.com:443");
+    for url in &["https://deno.land/mod.ts", "https://deno.land:443/mod.ts"] {
+      assert_eq!(
+        tokens.authorization(&uri(url)),
+        Some("Bearer abc".to_string())
+      );
+    }
+    assert_eq!(
+      tokens.authorization(&uri("https://example.com/mod.ts")),
+      Some("Bearer def".to_string())
+    );
+  }
+
+  #[test]
+  fn test_auth_tokens_http() {
+    // Credentials aren't sent over plain http unless the entry opts in.
+    let tokens = AuthTokens::new("abc@deno.land;def@http://localhost:4545");
+    for url in &[
+      "http://deno.land/mod.ts",
+      "http://deno.land:443/mod.ts",
+      "https://localhost:4545/mod.ts",
+    ] {
+      assert_eq!(tokens.authorization(&uri(url)), None);
+    }
+    assert_eq!(
+      tokens.authorization(&uri("http://localhost:4545/mod.ts")),
+      Some("Bearer def".to_string())
+    );
+  }
+
+  #[test]
+  fn test_auth_tokens_malformed() {
+    let tokens = AuthTokens::new(
+      ";abc123;@deno.land;abc@;ghi@deno.land:x;jkl@ftp://deno.land;\
+       def@deno.land; ",
+    );
+    assert_eq!(
+      tokens,
+      AuthTokens(vec![AuthToken {
+        scheme: "https".to_string(),
+        host: "deno.land".to_string(),
+        port: 443,
+        credentials: Credentials::Bearer("def".to_string()),
+      }])
+    );
+  }
+}
@@ -0,0 +1,143 @@
+// Copyright 2018-2019 the Deno authors. All rights reserved. MIT license.
+//! Modules built into the deno binary, which are imported with `std:`
+//! specifiers, e.g. `import { red } from "std:colors"`. They are a subset of
+//! deno_std which is part of the runtime's snapshot (js/builtin_modules.ts), so
+//! the source of a built-in module only re-exports it from there. Import maps
+//! can map a specifier to a built-in module with a URL to fall back to, for
+//! versions of Deno which don't have it:
+//!
+//!   "imports": {
+//!     "colors": ["std:colors", "https://deno.land/std/colors/mod.ts"]
+//!   }
+use crate::errors;
+use crate::errors::ErrorKind;
+use crate::scheme_handlers::LoadFuture;
+use crate::scheme_handlers::LoadedSource;
+use crate::scheme_handlers::SchemeHandler;
+use futures::future;
+use url;
+use url::Url;
+
+pub const SCHEME: &str = "std";
+
+/// URLs of the built-in modules with the names they export. The exports must
+/// match those of the modules in js/builtin_modules.ts.
+const MODULES: &[(&str, &[&str])] = &[
+  // The empty module of the import maps specification.
+  ("std:blank", &[]),
+  (
+    "std:colors",
+    &[
+      "setEnabled",
+      "getEnabled",
+      "reset",
+      "bold",
+      "dim",
+      "italic",
+      "underline",
+      "inverse",
+      "hidden",
+      "strikethrough",
+      "black",
+      "red",
+      "green",
+      "yellow",
+      "blue",
+      "magenta",
+      "cyan",
+      "white",
+      "gray",
+      "bgBlack",
+      "bgRed",
+      "bgGreen",
+      "bgYellow",
+      "bgBlue",
+      "bgMagenta",
+      "bgCyan",
+      "bgWhite",
+    ],
+  ),
+];
+
+/// Returns true if `url` is a `std:` URL, whether or not the module exists.
+pub fn is_builtin_url(url: &str) -> bool {
+  url.starts_with("std:")
+}
+
+/// Returns true if `url` is the URL of a built-in module.
+pub fn has(url: &str) -> bool {
+  get(url).is_some()
+}
+
+/// JavaScript source of the built-in module `url`, which re-exports the
+/// module from the runtime. It doesn't need to be compiled.
+fn get(url: &str) -> Option<String> {
+  let (_, exports) = MODULES.iter().find(|(module_url, _)| *module_url == url)?;
+  if exports.is_empty() {
+    return Some(String::new());
+  }
+  Some(format!(
+    "export const {{ {} }} = Deno.builtinModules[\"{}\"];\n",
+    exports.join(", "),
+    url
+  ))
+}
+
+/// Loads built-in modules for `scheme_handlers`.
+pub struct BuiltinModules;
+
+impl SchemeHandler for BuiltinModules {
+  fn resolve(
+    &self,
+    specifier: &str,
+    _referrer: &str,
+  ) -> Result<Url, url::ParseError> {
+    // Built-in modules don't have relative imports, so resolving a relative
+    // specifier fails.
+    Url::parse(specifier)
+  }
+
+  fn load(&self, url: &Url) -> Box<LoadFuture> {
+    Box::new(future::result(match get(url.as_str()) {
+      Some(source) => Ok(LoadedSource {
+        source_code: source.as_bytes().to_vec(),
+        mime_type: Some("application/javascript".to_string()),
+      }),
+      None => Err(errors::new(
+        ErrorKind::NotFound,
+        format!("The \"{}\" built-in module is not implemented", url),
+      )),
+    }))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use futures::Future;
+
+  #[test]
+  fn test_builtin_modules() {
+    assert!(is_builtin_url("std:none"));
+    assert!(!is_builtin_url("https://deno.land/std/colors/mod.ts"));
+    assert!(has("std:blank"));
+    assert!(!has("std:none"));
+    assert!(has("std:colors"));
+
+    let handler = BuiltinModules;
+    let url = handler.resolve("std:blank", "file:///main.ts").unwrap();
+    let loaded = handler.load(&url).wait().unwrap();
+    assert!(loaded.source_code.is_empty());
+    assert_eq!(loaded.mime_type, Some("application/javascript".to_string()));
+
+    let url = handler.resolve("std:colors", "file:///main.ts").unwrap();
+    let loaded = handler.load(&url).wait().unwrap();
+    let source = String::from_utf8(loaded.source_code).unwrap();
+    assert!(source.starts_with("export const { setEnabled, getEnabled,"));
+    assert!(source.ends_with("} = Deno.builtinModules[\"std:colors\"];\n"));
+
+    let url = handler.resolve("std:none", "file:///main.ts").unwrap();
+    assert!(handler.load(&url).wait().is_err());
+    assert!(handler.resolve("./colors", "std:blank").is_err());
+  }
+}
@@ -0,0 +1,166 @@
+// Copyright 2018-2019 the Deno authors. All rights reserved. MIT license.
+//! Files written by `deno bundle` start with a header line and carry their
+//! own module loader, so they can be run as plain scripts without fetching,
+//! compiling or caching anything. An entry bundle which needs a shared chunk
+//! names it in the header:
+//!
+//!   // deno-bundle shared=shared.js
+//!
+//! The shared chunk is looked up next to the bundle and executed first.
+use crate::errors;
+use crate::errors::DenoResult;
+use crate::errors::ErrorKind;
+use std::fs;
+use std::fs::File;
+use std::io;
+use std::io::BufRead;
+use std::io::BufReader;
+use std::path::Path;
+use url::Url;
+
+/// Keep in sync with `BUNDLE_HEADER` in js/bundler.ts.
+pub const HEADER: &str = "// deno-bundle";
+
+#[derive(Debug, PartialEq)]
+pub struct Chunk {
+  pub filename: String,
+  pub source: String,
+}
+
+/// Returns the `key=value` pairs following the header if `line` is a bundle
+/// header, or `None` for any other line.
+fn parse_header(line: &str) -> Option<Vec<(&str, &str)>> {
+  let line = line.trim_end();
+  if !line.starts_with(HEADER) {
+    return None;
+  }
+  let rest = &line[HEADER.len()..];
+  if !rest.is_empty() && !rest.starts_with(' ') {
+    return None;
+  }
+  let pairs = rest
+    .split_whitespace()
+    .filter_map(|pair| {
+      let mut parts = pair.splitn(2, '=');
+      match (parts.next(), parts.next()) {
+        (Some(key), Some(value)) => Some((key, value)),
+        _ => None,
+      }
+    }).collect();
+  Some(pairs)
+}
+
+fn read_chunk(path: &Path) -> DenoResult<Chunk> {
+  let source = fs::read_to_string(path).map_err(|err| {
+    let kind = match err.kind() {
+      io::ErrorKind::NotFound => ErrorKind::NotFound,
+      _ => ErrorKind::Other,
+    };
+    errors::new(kind, format!("Cannot read {}: {}", path.display(), err))
+  })?;
+  Ok(Chunk {
+    filename: path.to_string_lossy().to_string(),
+    source,
+  })
+}
+
+/// If `url` is a local file starting with the bundle header, returns the
+/// chunks to execute in order: the shared chunk, if the header names one,
+/// followed by the bundle itself. Returns `None` for anything else, which is
+/// then loaded as a regular module.
+pub fn read(url: &Url) -> DenoResult<Option<Vec<Chunk>>> {
+  if url.scheme() != "file" {
+    return Ok(None);
+  }
+  let path = match url.to_file_path() {
+    Ok(path) => path,
+    Err(()) => return Ok(None),
+  };
+  let file = match File::open(&path) {
+    Ok(file) => file,
+    // Let the module loader report missing files as usual.
+    Err(_) => return Ok(None),
+  };
+  let mut first_line = String::new();
+  if BufReader::new(file).read_line(&mut first_line).is_err() {
+    return Ok(None);
+  }
+  let pairs = match parse_header(&first_line) {
+    Some(pairs) => pairs,
+    None => return Ok(None),
+  };
+
+  let mut chunks = Vec::new();
+  for (key, value) in pairs {
+    match key {
+      "shared" => {
+        let dir = path.parent().unwrap_or_else(|| Path::new("."));
+        chunks.push(read_chunk(&dir.join(value))?);
+      }
+      _ => {
+        return Err(errors::new(
+          ErrorKind::InvalidData,
+          format!("Unknown bundle header field \"{}\" in {}", key, url),
+        ))
+      }
+    }
+  }
+  chunks.push(read_chunk(&path)?);
+  Ok(Some(chunks))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use tempfile::TempDir;
+
+  #[test]
+  fn test_parse_header() {
+    assert_eq!(parse_header("// deno-bundle\n"), Some(vec![]));
+    assert_eq!(
+      parse_header("// deno-bundle shared=shared.js\n"),
+      Some(vec![("shared", "shared.js")])
+    );
+    assert_eq!(parse_header("// deno-bundler\n"), None);
+    assert_eq!(parse_header("console.log(1);\n"), None);
+    assert_eq!(parse_header(""), None);
+  }
+
+  #[test]
+  fn test_read() {
+    let dir = TempDir::new().expect("tempdir fail");
+    let main = dir.path().join("main.js");
+    let shared = dir.path().join("shared.js");
+    let plain = dir.path().join("plain.js");
+    fs::write(&main, "// deno-bundle shared=shared.js\nmain();\n").unwrap();
+    fs::write(&shared, "// deno-bundle\nshared();\n").unwrap();
+    fs::write(&plain, "console.log(1);\n").unwrap();
+
+    let chunks = read(&Url::from_file_path(&main).unwrap())
+      .unwrap()
+      .unwrap();
+    assert_eq!(chunks.len(), 2);
+    assert_eq!(chunks[0].source, "// deno-bundle\nshared();\n");
+    assert_eq!(chunks[1].source, "// deno-bundle shared=shared.js\nmain();\n");
+
+    let chunks = read(&Url::from_file_path(&shared).unwrap())
+      .unwrap()
+      .unwrap();
+    assert_eq!(chunks.len(), 1);
+
+    assert!(read(&Url::from_file_path(&plain).unwrap()).unwrap().is_none());
+    let missing = dir.path().join("missing.js");
+    assert!(read(&Url::from_file_path(&missing).unwrap()).unwrap().is_none());
+    let remote = Url::parse("https://example.com/main.js").unwrap();
+    assert!(read(&remote).unwrap().is_none());
+  }
+
+  #[test]
+  fn test_read_missing_shared() {
+    let dir = TempDir::new().expect("tempdir fail");
+    let main = dir.path().join("main.js");
+    fs::write(&main, "// deno-bundle shared=shared.js\nmain();\n").unwrap();
+    let err = read(&Url::from_file_path(&main).unwrap()).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::NotFound);
+  }
+}
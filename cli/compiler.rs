@@ -1,17 +1,30 @@
 // Copyright 2018-2019 the Deno authors. All rights reserved. MIT license.
 use crate::diagnostics::Diagnostic;
+use crate::import_map::ImportMap;
+use crate::js_errors::JSErrorColor;
 use crate::msg;
 use crate::resources;
+use crate::resources::ResourceId;
 use crate::startup_data;
 use crate::state::*;
 use crate::tokio_util;
 use crate::worker::Worker;
 use deno::js_check;
 use deno::Buf;
+use futures::future;
+use futures::future::Either;
+use futures::sync::oneshot;
 use futures::Future;
 use futures::Stream;
+use serde_json;
+use std::collections::HashMap;
 use std::str;
+use std::sync::atomic::AtomicUsize;
 use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::sync::Weak;
+use tokio::runtime::Runtime;
 
 // This corresponds to JS ModuleMetaData.
 // TODO Rename one or the other so they correspond.
@@ -53,9 +66,9 @@ type CompilerConfig = Option<(String, Vec<u8>)>;
 fn req(
   root_names: Vec<String>,
   compiler_config: CompilerConfig,
-  bundle: Option<String>,
-) -> Buf {
-  let j = if let Some((config_path, config_data)) = compiler_config {
+  bundle: Option<serde_json::Value>,
+) -> serde_json::Value {
+  if let Some((config_path, config_data)) = compiler_config {
     json!({
       "rootNames": root_names,
       "bundle": bundle,
@@ -67,8 +80,7 @@ fn req(
       "rootNames": root_names,
       "bundle": bundle,
     })
-  };
-  j.to_string().into_boxed_str().into_boxed_bytes()
+  }
 }
 
 /// Returns an optional tuple which represents the state of the compiler
@@ -88,63 +100,250 @@ pub fn get_compiler_config(
   }
 }
 
+lazy_static! {
+  /// Runtime of the compiler workers. It is separate from the runtimes of the
+  /// code which compiles, e.g. the one of `tokio_util::block_on()`, as
+  /// compiler workers outlive those.
+  static ref C_RUNTIME: Mutex<Runtime> =
+    Mutex::new(tokio_util::create_threadpool_runtime());
+}
+
+type PendingRequests = Arc<Mutex<HashMap<usize, oneshot::Sender<Buf>>>>;
+
+/// A compiler worker which handles the compile requests of all the workers
+/// with the same import map. It is started with the first compile request,
+/// and then keeps running until the last state using it is dropped. Requests
+/// may be sent from several threads, so each of them carries an id which the
+/// compiler copies to its response.
+pub struct CompilerWorker {
+  rid: ResourceId,
+  next_id: AtomicUsize,
+  pending: PendingRequests,
+  /// The import map with which the compiler resolves imports.
+  import_map: Option<ImportMap>,
+}
+
+impl CompilerWorker {
+  fn start(parent_state: &ThreadSafeState) -> Self {
+    // Count how many times we start the compiler worker.
+    parent_state
+      .metrics
+      .compiler_starts
+      .fetch_add(1, Ordering::SeqCst);
+
+    // The compiler marks the modules it compiles in the compiled set of
+    // `parent_state`, which it shares, like the DENO_DIR and permissions.
+    let child_state = ThreadSafeState::new_for_compiler(parent_state);
+    let rid = child_state.resource.rid;
+    let resource = child_state.resource.clone();
+
+    let mut worker = Worker::new(
+      "TS".to_string(),
+      startup_data::compiler_isolate_init(),
+      child_state,
+    );
+    js_check(worker.execute("denoMain()"));
+    js_check(worker.execute("workerMain()"));
+    js_check(worker.execute("compilerMain()"));
+
+    let pending = PendingRequests::default();
+    let pending_ = pending.clone();
+    let executor = C_RUNTIME.lock().unwrap().executor();
+    executor.spawn(worker.then(move |result| -> Result<(), ()> {
+      if let Err(err) = result {
+        // TODO(ry) Need to forward the error instead of exiting.
+        eprintln!("{}", JSErrorColor(&err).to_string());
+        std::process::exit(1);
+      }
+      // Close the resource so the stream of responses below ends. It is
+      // closed already if the worker exited because it was dropped.
+      debug!("Compiler worker exited");
+      if resources::lookup(resource.rid).is_some() {
+        resource.close();
+      }
+      Ok(())
+    }));
+    executor.spawn(
+      resources::get_message_stream_from_worker(rid)
+        .for_each(move |msg: Buf| {
+          let res_json = str::from_utf8(&msg).unwrap();
+          let res = serde_json::from_str::<serde_json::Value>(res_json)
+            .expect("Error decoding JSON string.");
+          let id = res["id"].as_u64().unwrap() as usize;
+          if let Some(sender) = pending_.lock().unwrap().remove(&id) {
+            // The receiver is gone if the compile was cancelled.
+            let _ = sender.send(msg);
+          }
+          Ok(())
+        }).map_err(|err| debug!("Compiler worker stream ended: {}", err)),
+    );
+
+    Self {
+      rid,
+      next_id: AtomicUsize::new(0),
+      pending,
+      import_map: parent_state.import_map.clone(),
+    }
+  }
+
+  /// The resource through which requests are sent to the compiler.
+  pub fn rid(&self) -> ResourceId {
+    self.rid
+  }
+
+  /// Sends a request to the compiler and returns its response.
+  fn request(
+    &self,
+    mut req: serde_json::Value,
+  ) -> impl Future<Item = Buf, Error = ()> {
+    let id = self.next_id.fetch_add(1, Ordering::SeqCst);
+    req["id"] = json!(id);
+    let req_msg = req.to_string().into_boxed_str().into_boxed_bytes();
+
+    let (sender, receiver) = oneshot::channel::<Buf>();
+    self.pending.lock().unwrap().insert(id, sender);
+    resources::post_message_to_worker(self.rid, req_msg)
+      .map_err(|_| ())
+      .and_then(move |_| {
+        debug!("Sent message to worker");
+        receiver.map_err(|_| ())
+      })
+  }
+}
+
+impl Drop for CompilerWorker {
+  fn drop(&mut self) {
+    // Closing the resource drops the channel to the compiler, which makes
+    // its workerMain() return, so the worker exits.
+    if let Some(resource) = resources::lookup(self.rid) {
+      resource.close();
+    }
+  }
+}
+
+/// The compiler workers which a state and the states created from it with
+/// `ThreadSafeState::new_child()` share, one per import map. The pool only
+/// holds weak references: each state holds its compiler, so a compiler stops
+/// when the last state using it is dropped.
+#[derive(Clone, Default)]
+pub struct CompilerPool(Arc<Mutex<Vec<Weak<CompilerWorker>>>>);
+
+impl CompilerPool {
+  /// Returns the running compiler for the import map of `state`, or starts
+  /// one.
+  fn get_or_start(&self, state: &ThreadSafeState) -> Arc<CompilerWorker> {
+    let mut compilers = self.0.lock().unwrap();
+    compilers.retain(|compiler| compiler.upgrade().is_some());
+    let running = compilers
+      .iter()
+      .filter_map(Weak::upgrade)
+      .find(|compiler| compiler.import_map == state.import_map);
+    running.unwrap_or_else(|| {
+      let compiler = Arc::new(CompilerWorker::start(state));
+      compilers.push(Arc::downgrade(&compiler));
+      compiler
+    })
+  }
+}
+
+/// Returns the compiler worker of `state`, and starts it if necessary. The
+/// compiler resolves imports with the import map of `state`, so workers with
+/// another import map use another compiler.
+fn lazy_start(state: &ThreadSafeState) -> Arc<CompilerWorker> {
+  let mut compiler = state.compiler.lock().unwrap();
+  compiler
+    .get_or_insert_with(|| state.compilers.get_or_start(state))
+    .clone()
+}
+
+/// Bundles each of `module_names` with its dependencies into the out file
+/// at the same index. With `shared_out_file`, the modules which several of
+/// them import are bundled into it instead. Resolves with the names of the
+/// bundled modules.
 pub fn bundle_async(
   state: ThreadSafeState,
-  module_name: String,
-  out_file: String,
-) -> impl Future<Item = (), Error = Diagnostic> {
+  module_names: Vec<String>,
+  out_files: Vec<String>,
+  shared_out_file: Option<String>,
+) -> impl Future<Item = Vec<String>, Error = Diagnostic> {
   debug!(
-    "Invoking the compiler to bundle. module_name: {}",
-    module_name
+    "Invoking the compiler to bundle. module_names: {:?}",
+    module_names
   );
 
-  let root_names = vec![module_name.clone()];
   let compiler_config = get_compiler_config(&state, "typescript");
-  let req_msg = req(root_names, compiler_config, Some(out_file));
-
-  // Count how many times we start the compiler worker.
-  state.metrics.compiler_starts.fetch_add(1, Ordering::SeqCst);
+  let source_map = match state.flags.bundle_source_map {
+    Some(ref source_map) => source_map.as_str(),
+    None => "inline",
+  };
+  let bundle = json!({
+    "outFiles": out_files,
+    "sharedOutFile": shared_out_file,
+    "minify": state.flags.bundle_minify,
+    "sourceMap": source_map,
+    "target": state.flags.bundle_target,
+    "deferMain": state.flags.bundle_defer_main,
+  });
+  let req_msg = req(module_names, compiler_config, Some(bundle));
+
+  let compiler = lazy_start(&state);
+
+  compiler.request(req_msg).map_err(|_| panic!("not handled")).and_then(
+    move |msg: Buf| {
+      debug!("Received message from worker");
 
-  let mut worker = Worker::new(
-    "TS".to_string(),
-    startup_data::compiler_isolate_init(),
-    // TODO(ry) Maybe we should use a separate state for the compiler.
-    // as was done previously.
-    state.clone(),
-  );
-  js_check(worker.execute("denoMain()"));
-  js_check(worker.execute("workerMain()"));
-  js_check(worker.execute("compilerMain()"));
-
-  let resource = worker.state.resource.clone();
-  let compiler_rid = resource.rid;
-  let first_msg_fut = resources::post_message_to_worker(compiler_rid, req_msg)
-    .then(move |_| worker)
-    .then(move |result| {
-      if let Err(err) = result {
-        // TODO(ry) Need to forward the error instead of exiting.
-        eprintln!("{}", err.to_string());
-        std::process::exit(1);
+      let json_str = std::str::from_utf8(&msg).unwrap();
+      debug!("Message: {}", json_str);
+      if let Some(diagnostics) = Diagnostic::from_emit_result(json_str) {
+        return Err(diagnostics);
       }
-      debug!("Sent message to worker");
-      let stream_future =
-        resources::get_message_stream_from_worker(compiler_rid).into_future();
-      stream_future.map(|(f, _rest)| f).map_err(|(f, _rest)| f)
-    });
-
-  first_msg_fut.map_err(|_| panic!("not handled")).and_then(
-    move |maybe_msg: Option<Buf>| {
+
+      Ok(source_files(json_str))
+    },
+  )
+}
+
+/// The module names of the source files in the result of a bundle or a
+/// check.
+fn source_files(json_str: &str) -> Vec<String> {
+  let res = serde_json::from_str::<serde_json::Value>(json_str)
+    .expect("Error decoding JSON string.");
+  match res["sourceFiles"].as_array() {
+    Some(source_files) => source_files
+      .iter()
+      .filter_map(|name| name.as_str().map(String::from))
+      .collect(),
+    None => vec![],
+  }
+}
+
+/// Type-checks the modules `root_names` and their dependencies without
+/// running them. Like a compile, it caches the emitted JavaScript. Resolves
+/// with the names of the checked modules.
+pub fn check_async(
+  state: ThreadSafeState,
+  root_names: Vec<String>,
+) -> impl Future<Item = Vec<String>, Error = Diagnostic> {
+  debug!("Invoking the compiler to check. root_names: {:?}", root_names);
+
+  let compiler_config = get_compiler_config(&state, "typescript");
+  let mut req_msg = req(root_names, compiler_config, None);
+  // Modules which can't be fetched are reported as diagnostics too.
+  req_msg["check"] = json!(true);
+
+  let compiler = lazy_start(&state);
+
+  compiler.request(req_msg).map_err(|_| panic!("not handled")).and_then(
+    move |msg: Buf| {
       debug!("Received message from worker");
 
-      if let Some(msg) = maybe_msg {
-        let json_str = std::str::from_utf8(&msg).unwrap();
-        debug!("Message: {}", json_str);
-        if let Some(diagnostics) = Diagnostic::from_emit_result(json_str) {
-          return Err(diagnostics);
-        }
+      let json_str = std::str::from_utf8(&msg).unwrap();
+      debug!("Message: {}", json_str);
+      if let Some(diagnostics) = Diagnostic::from_emit_result(json_str) {
+        return Err(diagnostics);
       }
 
-      Ok(())
+      Ok(source_files(json_str))
     },
   )
 }
@@ -162,76 +361,87 @@ pub fn compile_async(
 
   let root_names = vec![module_name.clone()];
   let compiler_config = get_compiler_config(&state, "typescript");
-  let req_msg = req(root_names, compiler_config, None);
-
-  // Count how many times we start the compiler worker.
-  state.metrics.compiler_starts.fetch_add(1, Ordering::SeqCst);
+  let mut req_msg = req(root_names, compiler_config, None);
+
+  // With --no-check each module is transpiled on its own, so the compiler
+  // doesn't need to load its dependencies.
+  let no_check = state.flags.no_check;
+  if no_check {
+    req_msg["noCheck"] = json!(true);
+    req_msg["sourceCode"] =
+      json!(String::from_utf8_lossy(&module_meta_data.source_code));
+  }
+  let module_meta_data_ = module_meta_data.clone();
 
-  let mut worker = Worker::new(
-    "TS".to_string(),
-    startup_data::compiler_isolate_init(),
-    // TODO(ry) Maybe we should use a separate state for the compiler.
-    // as was done previously.
-    state.clone(),
-  );
-  js_check(worker.execute("denoMain()"));
-  js_check(worker.execute("workerMain()"));
-  js_check(worker.execute("compilerMain()"));
+  let compiler = lazy_start(&state);
 
   let compiling_job = state.progress.add(format!("Compiling {}", module_name));
 
-  let resource = worker.state.resource.clone();
-  let compiler_rid = resource.rid;
-  let first_msg_fut = resources::post_message_to_worker(compiler_rid, req_msg)
-    .then(move |_| worker)
-    .then(move |result| {
-      if let Err(err) = result {
-        // TODO(ry) Need to forward the error instead of exiting.
-        eprintln!("{}", err.to_string());
-        std::process::exit(1);
-      }
-      debug!("Sent message to worker");
-      let stream_future =
-        resources::get_message_stream_from_worker(compiler_rid).into_future();
-      stream_future.map(|(f, _rest)| f).map_err(|(f, _rest)| f)
-    });
-
-  first_msg_fut
+  compiler
+    .request(req_msg)
     .map_err(|_| panic!("not handled"))
-    .and_then(move |maybe_msg: Option<Buf>| {
+    .and_then(move |msg: Buf| {
       debug!("Received message from worker");
 
-      if let Some(msg) = maybe_msg {
-        let json_str = std::str::from_utf8(&msg).unwrap();
-        debug!("Message: {}", json_str);
-        if let Some(diagnostics) = Diagnostic::from_emit_result(json_str) {
-          return Err(diagnostics);
-        }
+      let json_str = std::str::from_utf8(&msg).unwrap();
+      debug!("Message: {}", json_str);
+      if let Some(diagnostics) = Diagnostic::from_emit_result(json_str) {
+        return Err(diagnostics);
       }
 
-      Ok(())
-    }).and_then(move |_| {
-      state.dir.fetch_module_meta_data_async(
-        &module_name,
-        ".",
-        true,
-        true,
-      ).map_err(|e| {
-        // TODO(95th) Instead of panicking, We could translate this error to Diagnostic.
-        panic!("{}", e)
-      })
+      Ok(msg)
+    }).and_then(move |msg: Buf| {
+      if no_check {
+        Either::A(future::ok(cache_transpiled(&state, module_meta_data_, &msg)))
+      } else {
+        Either::B(
+          state
+            .dir
+            .fetch_module_meta_data_async(&module_name, ".", true, true)
+            .map_err(|e| {
+              // TODO(95th) Instead of panicking, We could translate this error to Diagnostic.
+              panic!("{}", e)
+            }),
+        )
+      }
     }).and_then(move |module_meta_data_after_compile| {
       // Explicit drop to keep reference alive until future completes.
       drop(compiling_job);
 
       Ok(module_meta_data_after_compile)
-    }).then(move |r| {
-      // TODO(ry) do this in worker's destructor.
-      // resource.close();
-      r
     })
 }
 
+/// Writes the JavaScript and source map of a module transpiled with
+/// --no-check to the code cache, where a type-checked compile would have put
+/// them.
+fn cache_transpiled(
+  state: &ThreadSafeState,
+  mut module_meta_data: ModuleMetaData,
+  msg: &[u8],
+) -> ModuleMetaData {
+  let res = serde_json::from_slice::<serde_json::Value>(msg)
+    .expect("Error decoding JSON string.");
+  let output_code = res["outputCode"].as_str().unwrap();
+  let source_map = res["sourceMap"].as_str().unwrap();
+  module_meta_data.maybe_output_code = Some(output_code.as_bytes().to_vec());
+  module_meta_data.maybe_source_map = Some(source_map.as_bytes().to_vec());
+
+  let (cache_path, source_map_path) = state
+    .dir
+    .cache_path(&module_meta_data.filename, &module_meta_data.source_code);
+  if let Err(err) = state.dir.code_cache(&module_meta_data) {
+    panic!("{}", err)
+  }
+  state.mark_compiled(&module_meta_data.module_name);
+
+  module_meta_data.maybe_output_code_filename =
+    Some(cache_path.to_str().unwrap().to_string());
+  module_meta_data.maybe_source_map_filename =
+    Some(source_map_path.to_str().unwrap().to_string());
+  module_meta_data
+}
+
 pub fn compile_sync(
   state: ThreadSafeState,
   module_meta_data: &ModuleMetaData,
@@ -242,6 +452,7 @@ pub fn compile_sync(
 #[cfg(test)]
 mod tests {
   use super::*;
+  use crate::ops::op_selector_std;
 
   #[test]
   fn test_compile_sync() {
@@ -280,6 +491,168 @@ mod tests {
     })
   }
 
+  #[test]
+  fn test_compile_sync_reuses_compiler() {
+    tokio_util::init(|| {
+      use crate::worker;
+      let state = ThreadSafeState::mock(vec![
+        String::from("./deno"),
+        String::from("hello.js"),
+      ]);
+      let sources: Vec<(&str, &[u8])> = vec![
+        ("./tests/002_hello.ts", include_bytes!("../tests/002_hello.ts")),
+        (
+          "./tests/subdir/print_hello.ts",
+          include_bytes!("../tests/subdir/print_hello.ts"),
+        ),
+      ];
+      for (specifier, source_code) in sources {
+        let module_name = worker::root_specifier_to_url(specifier)
+          .unwrap()
+          .to_string();
+        let module_meta_data = ModuleMetaData {
+          module_name,
+          module_redirect_source_name: None,
+          filename: specifier[1..].to_owned(),
+          media_type: msg::MediaType::TypeScript,
+          source_code: source_code.to_vec(),
+          maybe_output_code_filename: None,
+          maybe_output_code: None,
+          maybe_source_map_filename: None,
+          maybe_source_map: None,
+        };
+        let out = compile_sync(state.clone(), &module_meta_data).unwrap();
+        assert!(out.maybe_output_code.is_some());
+      }
+      // Both modules were compiled by the same compiler worker.
+      assert_eq!(state.metrics.compiler_starts.load(Ordering::SeqCst), 1);
+    })
+  }
+
+  #[test]
+  fn test_compiler_shared_by_child_states() {
+    let state = ThreadSafeState::mock(vec![String::from("./deno")]);
+    let child = ThreadSafeState::new_child(
+      &state,
+      vec![String::from("./deno")],
+      op_selector_std,
+      None,
+    );
+    let compiler = lazy_start(&state);
+    assert!(Arc::ptr_eq(&compiler, &lazy_start(&child)));
+    assert_eq!(state.metrics.compiler_starts.load(Ordering::SeqCst), 1);
+    assert_eq!(child.metrics.compiler_starts.load(Ordering::SeqCst), 0);
+
+    // The compiler stops once no state uses it anymore.
+    let rid = compiler.rid();
+    drop(compiler);
+    drop(state);
+    assert!(resources::lookup(rid).is_some());
+    drop(child);
+    assert!(resources::lookup(rid).is_none());
+  }
+
+  #[test]
+  fn test_compile_sync_no_check() {
+    tokio_util::init(|| {
+      use crate::flags::DenoFlags;
+      use crate::progress::Progress;
+      use crate::worker;
+      let specifier = "./tests/041_no_check.ts";
+      let module_name = worker::root_specifier_to_url(specifier)
+        .unwrap()
+        .to_string();
+
+      let module_meta_data = ModuleMetaData {
+        module_name,
+        module_redirect_source_name: None,
+        filename: "/tests/041_no_check.ts".to_owned(),
+        media_type: msg::MediaType::TypeScript,
+        source_code: include_bytes!("../tests/041_no_check.ts").to_vec(),
+        maybe_output_code_filename: None,
+        maybe_output_code: None,
+        maybe_source_map_filename: None,
+        maybe_source_map: None,
+      };
+
+      let state = ThreadSafeState::new(
+        DenoFlags {
+          no_check: true,
+          ..DenoFlags::default()
+        },
+        vec![String::from("./deno"), String::from("041_no_check.ts")],
+        op_selector_std,
+        Progress::new(),
+      );
+      // The module has a type error, which --no-check doesn't report.
+      let out = compile_sync(state.clone(), &module_meta_data).unwrap();
+      let output_code = String::from_utf8(out.maybe_output_code.unwrap());
+      assert!(output_code.unwrap().contains("const answer = \"forty-two\";"));
+      assert!(out.maybe_source_map.is_some());
+
+      // The output is in the code cache, where it is loaded from next time.
+      let (cache_path, source_map_path) = state
+        .dir
+        .cache_path(&module_meta_data.filename, &module_meta_data.source_code);
+      assert!(cache_path.exists());
+      assert!(source_map_path.exists());
+    })
+  }
+
+  #[test]
+  fn test_check_async() {
+    let state = ThreadSafeState::mock(vec![
+      String::from("./deno"),
+      String::from("./tests/002_hello.ts"),
+    ]);
+    let root_names = vec!["./tests/002_hello.ts", "./tests/041_no_check.ts"]
+      .into_iter()
+      .map(|specifier| {
+        crate::worker::root_specifier_to_url(specifier)
+          .unwrap()
+          .to_string()
+      }).collect();
+    let out = tokio_util::block_on(check_async(state, root_names));
+    let diagnostics = out.unwrap_err();
+    assert_eq!(diagnostics.items.len(), 1);
+    assert_eq!(diagnostics.items[0].code, 2322);
+  }
+
+  #[test]
+  fn test_check_async_missing_module() {
+    let state = ThreadSafeState::mock(vec![
+      String::from("./deno"),
+      String::from("./tests/error_004_missing_module.ts"),
+    ]);
+    let root_names = vec!["./tests/error_004_missing_module.ts"]
+      .into_iter()
+      .map(|specifier| {
+        crate::worker::root_specifier_to_url(specifier)
+          .unwrap()
+          .to_string()
+      }).collect();
+    let out = tokio_util::block_on(check_async(state, root_names));
+    let diagnostics = out.unwrap_err();
+    assert_eq!(diagnostics.items.len(), 1);
+    assert_eq!(diagnostics.items[0].code, 2307);
+    assert!(diagnostics.items[0].message.contains("bad-module.ts"));
+  }
+
+  #[test]
+  fn test_check_async_source_files() {
+    let state = ThreadSafeState::mock(vec![
+      String::from("./deno"),
+      String::from("./tests/002_hello.ts"),
+    ]);
+    let module_name =
+      crate::worker::root_specifier_to_url("./tests/002_hello.ts")
+        .unwrap()
+        .to_string();
+    let out = check_async(state, vec![module_name.clone()]);
+    let source_files = tokio_util::block_on(out).unwrap();
+    assert!(source_files.contains(&module_name));
+  }
+
   #[test]
   fn test_get_compiler_config_no_flag() {
     let compiler_type = "typescript";
@@ -304,8 +677,68 @@ mod tests {
       String::from("./tests/002_hello.ts"),
       String::from("$deno$/bundle.js"),
     ]);
-    let out =
-      bundle_async(state, module_name, String::from("$deno$/bundle.js"));
-    assert_eq!(tokio_util::block_on(out), Ok(()));
+    let out = bundle_async(
+      state,
+      vec![module_name.clone()],
+      vec![String::from("$deno$/bundle.js")],
+      None,
+    );
+    let source_files = tokio_util::block_on(out).unwrap();
+    assert!(source_files.contains(&module_name));
+  }
+
+  #[test]
+  fn test_bundle_async_shared() {
+    use crate::flags::DenoFlags;
+    use crate::progress::Progress;
+    use crate::worker;
+    use tempfile::TempDir;
+
+    let temp_dir = TempDir::new().expect("tempdir fail");
+    let out_file = |name: &str| {
+      temp_dir.path().join(name).to_str().unwrap().to_string()
+    };
+    let module_names = vec![
+      "./tests/003_relative_import.ts",
+      "./tests/subdir/mod2.ts",
+    ].into_iter()
+    .map(|specifier| {
+      worker::root_specifier_to_url(specifier)
+        .unwrap()
+        .to_string()
+    }).collect();
+
+    let state = ThreadSafeState::new(
+      DenoFlags {
+        allow_write: true,
+        bundle_minify: true,
+        bundle_source_map: Some("external".to_owned()),
+        ..DenoFlags::default()
+      },
+      vec![String::from("./deno")],
+      op_selector_std,
+      Progress::new(),
+    );
+    let out = bundle_async(
+      state,
+      module_names,
+      vec![out_file("a.js"), out_file("b.js")],
+      Some(out_file("shared.js")),
+    );
+    assert!(tokio_util::block_on(out).is_ok());
+
+    // Both entry points import print_hello.ts, so it is in the shared chunk.
+    let define = "print_hello.ts\",[\"require\",\"exports\"]";
+    let a = std::fs::read_to_string(out_file("a.js")).unwrap();
+    let shared = std::fs::read_to_string(out_file("shared.js")).unwrap();
+    assert!(a.contains("003_relative_import.ts"));
+    assert!(!a.contains(define));
+    assert!(shared.contains(define));
+    // The header, the loader, the minified code, the call which runs the
+    // entry point and the source map URL.
+    assert_eq!(a.lines().count(), 5);
+    assert!(a.starts_with("// deno-bundle shared=shared.js\n"));
+    assert!(a.ends_with("//# sourceMappingURL=a.js.map\n"));
+    assert!(std::path::Path::new(&out_file("a.js.map")).exists());
   }
 }
@@ -0,0 +1,852 @@
+// Copyright 2018-2019 the Deno authors. All rights reserved. MIT license.
+//! An RFC 6265 cookie store used by `fetch()` when a request opts in with
+//! `credentials: "include"`.
+//! https://tools.ietf.org/html/rfc6265
+use crate::fs as deno_fs;
+use hyper::header::HeaderMap;
+use hyper::header::HeaderValue;
+use hyper::header::SET_COOKIE;
+use serde_json;
+use std::fs;
+use std::net::IpAddr;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::time::SystemTime;
+use std::time::UNIX_EPOCH;
+use url::Url;
+
+/// Latest representable expiry, used for very large Max-Age values.
+/// (9999-12-31T23:59:59Z)
+const MAX_EXPIRY: u64 = 253_402_300_799;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Cookie {
+  pub name: String,
+  pub value: String,
+  /// Canonicalized (lower case) host or domain the cookie belongs to.
+  pub domain: String,
+  /// When true the cookie is only sent to exactly `domain`, and not to its
+  /// subdomains. This is the case when Set-Cookie had no Domain attribute.
+  pub host_only: bool,
+  pub path: String,
+  /// Seconds since the unix epoch. `None` means this is a session cookie and
+  /// it is never persisted.
+  pub expires: Option<u64>,
+  pub secure: bool,
+  pub http_only: bool,
+  /// Used to order cookies with equal path lengths (RFC 6265 section 5.4).
+  creation_index: u64,
+}
+
+#[derive(Clone, Default)]
+pub struct CookieJar(Arc<Mutex<Inner>>);
+
+#[derive(Default)]
+struct Inner {
+  cookies: Vec<Cookie>,
+  next_creation_index: u64,
+  /// If set, persistent cookies are written to this file whenever the jar
+  /// changes.
+  path: Option<PathBuf>,
+}
+
+impl CookieJar {
+  /// Creates an in-memory cookie jar.
+  pub fn new() -> Self {
+    CookieJar::default()
+  }
+
+  /// Creates a cookie jar backed by `path`. Persistent cookies previously
+  /// saved there are loaded; a missing or malformed file results in an empty
+  /// jar.
+  pub fn load(path: PathBuf) -> Self {
+    let mut inner = Inner::default();
+    if let Ok(s) = fs::read_to_string(&path) {
+      match serde_json::from_str::<serde_json::Value>(&s) {
+        Ok(serde_json::Value::Array(values)) => {
+          let now = now();
+          for value in values.iter() {
+            if let Some(mut cookie) = cookie_from_json(value) {
+              if cookie.expires.map_or(true, |e| e > now) {
+                cookie.creation_index = inner.next_creation_index;
+                inner.next_creation_index += 1;
+                inner.cookies.push(cookie);
+              }
+            }
+          }
+        }
+        _ => debug!("Ignoring malformed cookie jar {}", path.display()),
+      }
+    }
+    inner.path = Some(path);
+    CookieJar(Arc::new(Mutex::new(inner)))
+  }
+
+  /// Stores all `Set-Cookie` headers of a response to a request for `url`.
+  pub fn store_response_cookies(&self, url: &Url, headers: &HeaderMap) {
+    let mut changed = false;
+    {
+      let mut inner = self.0.lock().unwrap();
+      let now = now();
+      for header in headers.get_all(SET_COOKIE).iter() {
+        if let Ok(header) = header.to_str() {
+          changed |= inner.set_cookie(url, header, now);
+        }
+      }
+    }
+    if changed {
+      self.save();
+    }
+  }
+
+  /// Stores a single `Set-Cookie` header value received from `url`.
+  pub fn set_cookie(&self, url: &Url, set_cookie: &str) {
+    let changed = {
+      let mut inner = self.0.lock().unwrap();
+      inner.set_cookie(url, set_cookie, now())
+    };
+    if changed {
+      self.save();
+    }
+  }
+
+  /// Returns the value of the `Cookie` header to send with a request to
+  /// `url`, or `None` when no stored cookie matches.
+  pub fn cookie_header(&self, url: &Url) -> Option<HeaderValue> {
+    let inner = self.0.lock().unwrap();
+    let s = inner.cookie_string(url, now())?;
+    HeaderValue::from_str(&s).ok()
+  }
+
+  /// Returns all unexpired cookies in the jar.
+  pub fn cookies(&self) -> Vec<Cookie> {
+    let inner = self.0.lock().unwrap();
+    let now = now();
+    inner
+      .cookies
+      .iter()
+      .filter(|c| c.expires.map_or(true, |e| e > now))
+      .cloned()
+      .collect()
+  }
+
+  fn save(&self) {
+    let inner = self.0.lock().unwrap();
+    let path = match inner.path {
+      Some(ref path) => path,
+      None => return,
+    };
+    let now = now();
+    let values: Vec<serde_json::Value> = inner
+      .cookies
+      .iter()
+      .filter(|c| c.expires.map_or(false, |e| e > now))
+      .map(cookie_to_json)
+      .collect();
+    let s = serde_json::to_string(&values).unwrap();
+    if let Some(parent) = path.parent() {
+      let _ = fs::create_dir_all(parent);
+    }
+    // The jar may hold session tokens, so keep it private to the user.
+    if let Err(e) = deno_fs::write_file(path, s, 0o600) {
+      debug!("Failed to save cookie jar {}: {}", path.display(), e);
+    }
+  }
+}
+
+impl Inner {
+  /// Implements the storage model of RFC 6265 section 5.3. Returns true if
+  /// the jar was modified.
+  fn set_cookie(&mut self, url: &Url, set_cookie: &str, now: u64) -> bool {
+    let host = match url.host_str() {
+      Some(host) => host.to_lowercase(),
+      None => return false,
+    };
+    let parsed = match parse_set_cookie(set_cookie, now) {
+      Some(parsed) => parsed,
+      None => return false,
+    };
+
+    let (domain, host_only) = match parsed.domain {
+      // A public suffix is only accepted as the domain of the host itself,
+      // and the cookie isn't sent to other hosts under it (RFC 6265 section
+      // 5.3 step 5).
+      Some(ref domain) if is_public_suffix(domain) => {
+        if domain != &host {
+          debug!("Ignoring cookie for public suffix {}", domain);
+          return false;
+        }
+        (host, true)
+      }
+      Some(ref domain) if !domain.is_empty() => {
+        if !domain_match(&host, domain) {
+          debug!("Ignoring cookie for {} set by {}", domain, host);
+          return false;
+        }
+        // A Domain attribute can't widen the scope of a cookie set by an IP
+        // address.
+        if host.parse::<IpAddr>().is_ok() && domain != &host {
+          return false;
+        }
+        // Nor can a domain which may be a public suffix.
+        if may_be_public_suffix(domain) {
+          (host, true)
+        } else {
+          (domain.clone(), false)
+        }
+      }
+      _ => (host, true),
+    };
+
+    let path = match parsed.path {
+      Some(ref path) if path.starts_with('/') => path.clone(),
+      _ => default_path(url),
+    };
+
+    if parsed.secure && url.scheme() != "https" {
+      return false;
+    }
+
+    let existing = self.cookies.iter().position(|c| {
+      c.name == parsed.name && c.domain == domain && c.path == path
+    });
+
+    let creation_index = match existing {
+      Some(i) => self.cookies.remove(i).creation_index,
+      None => {
+        let i = self.next_creation_index;
+        self.next_creation_index += 1;
+        i
+      }
+    };
+
+    // An expiry in the past is how servers delete cookies.
+    if parsed.expires.map_or(false, |e| e <= now) {
+      return existing.is_some();
+    }
+
+    self.cookies.push(Cookie {
+      name: parsed.name,
+      value: parsed.value,
+      domain,
+      host_only,
+      path,
+      expires: parsed.expires,
+      secure: parsed.secure,
+      http_only: parsed.http_only,
+      creation_index,
+    });
+    true
+  }
+
+  /// Implements the retrieval model of RFC 6265 section 5.4.
+  fn cookie_string(&self, url: &Url, now: u64) -> Option<String> {
+    let host = url.host_str()?.to_lowercase();
+    let request_path = url.path();
+    let is_secure = url.scheme() == "https";
+
+    let mut matching: Vec<&Cookie> = self
+      .cookies
+      .iter()
+      .filter(|c| {
+        let host_ok = if c.host_only {
+          host == c.domain
+        } else {
+          domain_match(&host, &c.domain)
+        };
+        host_ok
+          && path_match(request_path, &c.path)
+          && (!c.secure || is_secure)
+          && c.expires.map_or(true, |e| e > now)
+      })
+      .collect();
+
+    if matching.is_empty() {
+      return None;
+    }
+
+    // Cookies with longer paths are listed first. Among cookies with equal
+    // path lengths, those created earlier are listed first.
+    matching.sort_by(|a, b| {
+      b.path
+        .len()
+        .cmp(&a.path.len())
+        .then(a.creation_index.cmp(&b.creation_index))
+    });
+
+    let pairs: Vec<String> = matching
+      .iter()
+      .map(|c| format!("{}={}", c.name, c.value))
+      .collect();
+    Some(pairs.join("; "))
+  }
+}
+
+#[derive(Debug, PartialEq)]
+struct ParsedSetCookie {
+  name: String,
+  value: String,
+  expires: Option<u64>,
+  domain: Option<String>,
+  path: Option<String>,
+  secure: bool,
+  http_only: bool,
+}
+
+/// Parses a Set-Cookie header value following RFC 6265 section 5.2.
+/// Returns `None` if the user agent should ignore the header entirely.
+fn parse_set_cookie(header: &str, now: u64) -> Option<ParsedSetCookie> {
+  let mut parts = header.split(';');
+  let name_value_pair = parts.next()?;
+  let eq = name_value_pair.find('=')?;
+  let name = name_value_pair[..eq].trim();
+  let value = name_value_pair[eq + 1..].trim();
+  if name.is_empty() {
+    return None;
+  }
+
+  let mut parsed = ParsedSetCookie {
+    name: name.to_string(),
+    value: value.to_string(),
+    expires: None,
+    domain: None,
+    path: None,
+    secure: false,
+    http_only: false,
+  };
+  // Max-Age takes precedence over Expires regardless of order.
+  let mut max_age_expiry = None;
+
+  for attr in parts {
+    let (attr_name, attr_value) = match attr.find('=') {
+      Some(i) => (attr[..i].trim(), attr[i + 1..].trim()),
+      None => (attr.trim(), ""),
+    };
+    match attr_name.to_lowercase().as_str() {
+      "expires" => {
+        if let Some(t) = parse_cookie_date(attr_value) {
+          parsed.expires = Some(t);
+        }
+      }
+      "max-age" => {
+        let valid = !attr_value.is_empty()
+          && attr_value
+            .chars()
+            .enumerate()
+            .all(|(i, c)| c.is_ascii_digit() || (i == 0 && c == '-'));
+        if !valid {
+          continue;
+        }
+        max_age_expiry = Some(match attr_value.parse::<i64>() {
+          Ok(delta) if delta <= 0 => 0,
+          Ok(delta) => now.saturating_add(delta as u64).min(MAX_EXPIRY),
+          // Overflows i64 so it is a very large positive value.
+          Err(_) if !attr_value.starts_with('-') => MAX_EXPIRY,
+          Err(_) => 0,
+        });
+      }
+      "domain" => {
+        let domain = attr_value.trim_start_matches('.');
+        parsed.domain = Some(domain.to_lowercase());
+      }
+      "path" => {
+        parsed.path = Some(attr_value.to_string());
+      }
+      "secure" => parsed.secure = true,
+      "httponly" => parsed.http_only = true,
+      _ => {}
+    }
+  }
+
+  if max_age_expiry.is_some() {
+    parsed.expires = max_age_expiry;
+  }
+  Some(parsed)
+}
+
+/// Domain matching as defined in RFC 6265 section 5.1.3.
+fn domain_match(host: &str, domain: &str) -> bool {
+  if host == domain {
+    return true;
+  }
+  host.ends_with(domain)
+    && host[..host.len() - domain.len()].ends_with('.')
+    && host.parse::<IpAddr>().is_err()
+}
+
+/// Whether `domain` is a public suffix, under which unrelated parties own
+/// hosts. Without the public suffix list, only single-label domains like
+/// "com" or "localhost" are treated as such.
+fn is_public_suffix(domain: &str) -> bool {
+  !domain.is_empty() && !domain.contains('.')
+}
+
+/// Whether `domain` may be a public suffix: a second-level domain under a
+/// country code, like "co.uk" or "com.au", where many registries sell
+/// domains. As in the original Netscape cookie specification, such a Domain
+/// attribute doesn't widen the scope of a cookie, which also keeps a cookie
+/// for "example.de" host-only.
+fn may_be_public_suffix(domain: &str) -> bool {
+  let labels: Vec<&str> = domain.split('.').collect();
+  labels.len() == 2
+    && labels[1].len() == 2
+    && labels[1].chars().all(|c| c.is_ascii_alphabetic())
+}
+
+/// Path matching as defined in RFC 6265 section 5.1.4.
+fn path_match(request_path: &str, cookie_path: &str) -> bool {
+  if request_path == cookie_path {
+    return true;
+  }
+  request_path.starts_with(cookie_path)
+    && (cookie_path.ends_with('/')
+      || request_path[cookie_path.len()..].starts_with('/'))
+}
+
+/// The default-path of a cookie, RFC 6265 section 5.1.4.
+fn default_path(url: &Url) -> String {
+  let path = url.path();
+  if !path.starts_with('/') {
+    return "/".to_string();
+  }
+  match path.rfind('/') {
+    Some(0) | None => "/".to_string(),
+    Some(i) => path[..i].to_string(),
+  }
+}
+
+fn is_date_delimiter(c: char) -> bool {
+  match c {
+    '\x09' | '\x20'..='\x2F' | '\x3B'..='\x40' | '\x5B'..='\x60' => true,
+    '\x7B'..='\x7E' => true,
+    _ => false,
+  }
+}
+
+/// Parses the leading 1 to `max` digits of `token`. Returns the number and
+/// the remainder of the token.
+fn leading_digits(token: &str, min: usize, max: usize) -> Option<(u32, &str)> {
+  let n = token.chars().take_while(char::is_ascii_digit).count();
+  if n < min || n > max {
+    return None;
+  }
+  Some((token[..n].parse().ok()?, &token[n..]))
+}
+
+fn parse_time(token: &str) -> Option<(u32, u32, u32)> {
+  let mut fields = token.splitn(3, ':');
+  let (h, _) = leading_digits(fields.next()?, 1, 2)?;
+  let (m, _) = leading_digits(fields.next()?, 1, 2)?;
+  let (s, rest) = leading_digits(fields.next()?, 1, 2)?;
+  // Trailing non-digits are allowed, e.g. "08:49:37GMT".
+  if rest.starts_with(|c: char| c.is_ascii_digit()) {
+    return None;
+  }
+  Some((h, m, s))
+}
+
+fn parse_month(token: &str) -> Option<u32> {
+  if token.len() < 3 {
+    return None;
+  }
+  let months = [
+    "jan", "feb", "mar", "apr", "may", "jun", "jul", "aug", "sep", "oct",
+    "nov", "dec",
+  ];
+  let prefix = token[..3].to_lowercase();
+  months
+    .iter()
+    .position(|m| *m == prefix)
+    .map(|i| i as u32 + 1)
+}
+
+/// Parses a cookie-date using the algorithm of RFC 6265 section 5.1.1.
+/// Returns seconds since the unix epoch.
+fn parse_cookie_date(s: &str) -> Option<u64> {
+  let mut time = None;
+  let mut day_of_month = None;
+  let mut month = None;
+  let mut year = None;
+
+  for token in s.split(is_date_delimiter).filter(|t| !t.is_empty()) {
+    if !token.is_ascii() {
+      continue;
+    }
+    if time.is_none() {
+      if let Some(t) = parse_time(token) {
+        time = Some(t);
+        continue;
+      }
+    }
+    if day_of_month.is_none() {
+      if let Some((d, rest)) = leading_digits(token, 1, 2) {
+        if !rest.starts_with(|c: char| c.is_ascii_digit()) {
+          day_of_month = Some(d);
+          continue;
+        }
+      }
+    }
+    if month.is_none() {
+      if let Some(m) = parse_month(token) {
+        month = Some(m);
+        continue;
+      }
+    }
+    if year.is_none() {
+      if let Some((y, rest)) = leading_digits(token, 2, 4) {
+        if !rest.starts_with(|c: char| c.is_ascii_digit()) {
+          year = Some(y);
+          continue;
+        }
+      }
+    }
+  }
+
+  let (hour, minute, second) = time?;
+  let day_of_month = day_of_month?;
+  let month = month?;
+  let mut year = year?;
+  if year >= 70 && year <= 99 {
+    year += 1900;
+  } else if year <= 69 {
+    year += 2000;
+  }
+  if day_of_month < 1
+    || day_of_month > 31
+    || year < 1601
+    || hour > 23
+    || minute > 59
+    || second > 59
+  {
+    return None;
+  }
+  if day_of_month > days_in_month(year, month) {
+    return None;
+  }
+
+  let days = days_from_civil(year as i64, month, day_of_month);
+  let secs = days * 86400
+    + i64::from(hour) * 3600
+    + i64::from(minute) * 60
+    + i64::from(second);
+  // Dates before the epoch are in the past in any case.
+  Some(if secs < 0 { 0 } else { secs as u64 })
+}
+
+fn days_in_month(year: u32, month: u32) -> u32 {
+  match month {
+    4 | 6 | 9 | 11 => 30,
+    2 if (year % 4 == 0 && year % 100 != 0) || year % 400 == 0 => 29,
+    2 => 28,
+    _ => 31,
+  }
+}
+
+/// Number of days since 1970-01-01 of a proleptic Gregorian date.
+/// http://howardhinnant.github.io/date_algorithms.html#days_from_civil
+fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+  let y = if month <= 2 { year - 1 } else { year };
+  let era = if y >= 0 { y } else { y - 399 } / 400;
+  let yoe = y - era * 400;
+  let m = i64::from(month);
+  let mp = if m > 2 { m - 3 } else { m + 9 };
+  let doy = (153 * mp + 2) / 5 + i64::from(day) - 1;
+  let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+  era * 146_097 + doe - 719_468
+}
+
+fn now() -> u64 {
+  SystemTime::now()
+    .duration_since(UNIX_EPOCH)
+    .map(|d| d.as_secs())
+    .unwrap_or(0)
+}
+
+fn cookie_to_json(cookie: &Cookie) -> serde_json::Value {
+  json!({
+    "name": cookie.name,
+    "value": cookie.value,
+    "domain": cookie.domain,
+    "host_only": cookie.host_only,
+    "path": cookie.path,
+    "expires": cookie.expires,
+    "secure": cookie.secure,
+    "http_only": cookie.http_only,
+  })
+}
+
+fn cookie_from_json(value: &serde_json::Value) -> Option<Cookie> {
+  Some(Cookie {
+    name: value["name"].as_str()?.to_string(),
+    value: value["value"].as_str()?.to_string(),
+    domain: value["domain"].as_str()?.to_string(),
+    host_only: value["host_only"].as_bool()?,
+    path: value["path"].as_str()?.to_string(),
+    expires: Some(value["expires"].as_u64()?),
+    secure: value["secure"].as_bool().unwrap_or(false),
+    http_only: value["http_only"].as_bool().unwrap_or(false),
+    creation_index: 0,
+  })
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use tempfile::TempDir;
+
+  // Sun, 06 Nov 1994 08:49:37 GMT
+  const NOV_1994: u64 = 784_111_777;
+
+  fn url(s: &str) -> Url {
+    Url::parse(s).unwrap()
+  }
+
+  fn header(inner: &Inner, s: &str, now: u64) -> Option<String> {
+    inner.cookie_string(&url(s), now)
+  }
+
+  #[test]
+  fn test_parse_cookie_date() {
+    assert_eq!(
+      parse_cookie_date("Sun, 06 Nov 1994 08:49:37 GMT"),
+      Some(NOV_1994)
+    );
+    assert_eq!(
+      parse_cookie_date("Sunday, 06-Nov-94 08:49:37 GMT"),
+      Some(NOV_1994)
+    );
+    assert_eq!(
+      parse_cookie_date("Sun Nov  6 08:49:37 1994"),
+      Some(NOV_1994)
+    );
+    assert_eq!(parse_cookie_date("Thu, 01 Jan 1970 00:00:00 GMT"), Some(0));
+    assert_eq!(
+      parse_cookie_date("Wed, 29 Feb 2012 00:00:00 GMT"),
+      Some(1_330_473_600)
+    );
+    assert_eq!(parse_cookie_date("Tue, 30 Feb 2012 00:00:00 GMT"), None);
+    assert_eq!(parse_cookie_date("Sun, 06 Nov 1994 25:49:37 GMT"), None);
+    assert_eq!(parse_cookie_date("Sun, 06 Nov 1994"), None);
+    assert_eq!(parse_cookie_date("garbage"), None);
+  }
+
+  #[test]
+  fn test_parse_set_cookie() {
+    let parsed = parse_set_cookie(
+      "SID=31d4d96e407aad42; Path=/; Domain=.Example.com; Secure; HttpOnly",
+      0,
+    )
+    .unwrap();
+    assert_eq!(
+      parsed,
+      ParsedSetCookie {
+        name: "SID".to_string(),
+        value: "31d4d96e407aad42".to_string(),
+        expires: None,
+        domain: Some("example.com".to_string()),
+        path: Some("/".to_string()),
+        secure: true,
+        http_only: true,
+      }
+    );
+    assert_eq!(parse_set_cookie("novalue", 0), None);
+    assert_eq!(parse_set_cookie("=value", 0), None);
+    let parsed = parse_set_cookie("a=", 0).unwrap();
+    assert_eq!(parsed.value, "");
+  }
+
+  #[test]
+  fn test_max_age_precedence() {
+    let parsed = parse_set_cookie(
+      "a=b; Max-Age=60; Expires=Sun, 06 Nov 1994 08:49:37 GMT",
+      1000,
+    )
+    .unwrap();
+    assert_eq!(parsed.expires, Some(1060));
+    let parsed = parse_set_cookie("a=b; Max-Age=-1", 1000).unwrap();
+    assert_eq!(parsed.expires, Some(0));
+    // Invalid Max-Age is ignored.
+    let parsed = parse_set_cookie("a=b; Max-Age=1x", 1000).unwrap();
+    assert_eq!(parsed.expires, None);
+  }
+
+  #[test]
+  fn test_domain_match() {
+    assert!(domain_match("example.com", "example.com"));
+    assert!(domain_match("www.example.com", "example.com"));
+    assert!(!domain_match("wwwexample.com", "example.com"));
+    assert!(!domain_match("example.com", "www.example.com"));
+    assert!(!domain_match("1.2.3.4", "2.3.4"));
+  }
+
+  #[test]
+  fn test_path_match() {
+    assert!(path_match("/", "/"));
+    assert!(path_match("/docs", "/docs"));
+    assert!(path_match("/docs/web", "/docs"));
+    assert!(path_match("/docs/web", "/docs/"));
+    assert!(!path_match("/docsweb", "/docs"));
+    assert!(!path_match("/", "/docs"));
+  }
+
+  #[test]
+  fn test_default_path() {
+    assert_eq!(default_path(&url("http://a.com")), "/");
+    assert_eq!(default_path(&url("http://a.com/x")), "/");
+    assert_eq!(default_path(&url("http://a.com/x/y")), "/x");
+    assert_eq!(default_path(&url("http://a.com/x/y/")), "/x/y");
+  }
+
+  #[test]
+  fn test_host_only_and_domain_cookies() {
+    let mut inner = Inner::default();
+    let origin = url("http://www.example.com/login");
+    assert!(inner.set_cookie(&origin, "host=1", 0));
+    assert!(inner.set_cookie(&origin, "dom=2; Domain=example.com", 0));
+    // A foreign domain is rejected.
+    assert!(!inner.set_cookie(&origin, "evil=3; Domain=other.com", 0));
+
+    assert_eq!(
+      header(&inner, "http://www.example.com/", 0),
+      Some("host=1; dom=2".to_string())
+    );
+    assert_eq!(
+      header(&inner, "http://api.example.com/", 0),
+      Some("dom=2".to_string())
+    );
+    assert_eq!(header(&inner, "http://other.com/", 0), None);
+  }
+
+  #[test]
+  fn test_public_suffix_domain() {
+    let mut inner = Inner::default();
+    let origin = url("http://www.example.com/");
+    assert!(!inner.set_cookie(&origin, "tld=1; Domain=com", 0));
+    assert!(!inner.set_cookie(&origin, "tld=2; Domain=.COM", 0));
+    assert_eq!(header(&inner, "http://other.com/", 0), None);
+
+    // The host itself may name its domain, but the cookie stays host-only.
+    let origin = url("http://localhost:4545/");
+    assert!(inner.set_cookie(&origin, "local=1; Domain=localhost", 0));
+    assert!(inner.cookies[0].host_only);
+    assert_eq!(
+      header(&inner, "http://localhost/", 0),
+      Some("local=1".to_string())
+    );
+    assert_eq!(header(&inner, "http://a.localhost/", 0), None);
+  }
+
+  #[test]
+  fn test_country_code_domain() {
+    let mut inner = Inner::default();
+    let origin = url("http://shop.example.co.uk/");
+    assert!(inner.set_cookie(&origin, "uk=1; Domain=co.uk", 0));
+    assert!(inner.cookies[0].host_only);
+    assert_eq!(
+      header(&inner, "http://shop.example.co.uk/", 0),
+      Some("uk=1".to_string())
+    );
+    assert_eq!(header(&inner, "http://other.co.uk/", 0), None);
+
+    // Domains at the third level are shared with their subdomains.
+    assert!(inner.set_cookie(&origin, "dom=2; Domain=example.co.uk", 0));
+    assert_eq!(
+      header(&inner, "http://www.example.co.uk/", 0),
+      Some("dom=2".to_string())
+    );
+
+    // Second-level domains under generic top-level domains are too.
+    assert!(!may_be_public_suffix("example.com"));
+    assert!(may_be_public_suffix("example.de"));
+    assert!(!may_be_public_suffix("www.example.de"));
+  }
+
+  #[test]
+  fn test_path_ordering() {
+    let mut inner = Inner::default();
+    let origin = url("http://example.com/");
+    inner.set_cookie(&origin, "a=1; Path=/", 0);
+    inner.set_cookie(&origin, "b=2; Path=/api", 0);
+    inner.set_cookie(&origin, "c=3; Path=/", 0);
+    assert_eq!(
+      header(&inner, "http://example.com/api/users", 0),
+      Some("b=2; a=1; c=3".to_string())
+    );
+    assert_eq!(
+      header(&inner, "http://example.com/", 0),
+      Some("a=1; c=3".to_string())
+    );
+  }
+
+  #[test]
+  fn test_replace_and_delete() {
+    let mut inner = Inner::default();
+    let origin = url("http://example.com/");
+    inner.set_cookie(&origin, "a=1", 0);
+    inner.set_cookie(&origin, "b=2", 0);
+    inner.set_cookie(&origin, "a=3", 0);
+    // Replacing keeps the original creation order.
+    assert_eq!(
+      header(&inner, "http://example.com/", 0),
+      Some("a=3; b=2".to_string())
+    );
+    assert!(inner.set_cookie(&origin, "a=; Max-Age=0", 10));
+    assert_eq!(
+      header(&inner, "http://example.com/", 10),
+      Some("b=2".to_string())
+    );
+  }
+
+  #[test]
+  fn test_expiry_and_secure() {
+    let mut inner = Inner::default();
+    let origin = url("https://example.com/");
+    inner.set_cookie(&origin, "a=1; Max-Age=60", 100);
+    inner.set_cookie(&origin, "s=1; Secure", 100);
+    // Secure cookies can't be set over plain http.
+    assert!(!inner.set_cookie(&url("http://example.com/"), "t=1; Secure", 100));
+    assert_eq!(
+      header(&inner, "https://example.com/", 159),
+      Some("a=1; s=1".to_string())
+    );
+    assert_eq!(
+      header(&inner, "http://example.com/", 159),
+      Some("a=1".to_string())
+    );
+    assert_eq!(header(&inner, "http://example.com/", 160), None);
+  }
+
+  #[test]
+  fn test_persistence() {
+    let temp_dir = TempDir::new().expect("tempdir fail");
+    let path = temp_dir.path().join("cookies.json");
+    let origin = url("http://example.com/");
+    {
+      let jar = CookieJar::load(path.clone());
+      jar.set_cookie(&origin, "session=1");
+      jar.set_cookie(&origin, "persistent=2; Max-Age=3600");
+    }
+    assert!(path.exists());
+    let jar = CookieJar::load(path);
+    // Session cookies are not persisted.
+    assert_eq!(
+      jar.cookie_header(&origin).unwrap(),
+      HeaderValue::from_static("persistent=2")
+    );
+  }
+
+  #[test]
+  fn test_store_response_cookies() {
+    let jar = CookieJar::new();
+    let mut headers = HeaderMap::new();
+    headers.append(SET_COOKIE, HeaderValue::from_static("a=1"));
+    headers.append(SET_COOKIE, HeaderValue::from_static("b=2; Path=/x"));
+    jar.store_response_cookies(&url("http://example.com/x/y"), &headers);
+    assert_eq!(jar.cookies().len(), 2);
+    assert_eq!(
+      jar.cookie_header(&url("http://example.com/x/z")).unwrap(),
+      HeaderValue::from_static("a=1; b=2")
+    );
+  }
+}
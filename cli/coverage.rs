@@ -0,0 +1,478 @@
+// Copyright 2018-2019 the Deno authors. All rights reserved. MIT license.
+//! Code coverage for `--coverage`. V8 counts how often each range of the
+//! compiled JavaScript runs. The ranges are mapped back to the lines and
+//! functions of the original modules with the source maps stored next to the
+//! compiled code, and written in the lcov format.
+use crate::js_errors::parse_map_string;
+use crate::js_errors::SourceMap;
+use crate::js_errors::SourceMapGetter;
+use serde_json;
+use std::cmp::Reverse;
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::Path;
+use url::Url;
+
+pub const LCOV_FILE: &str = "lcov.info";
+
+/// Coverage of a script, as returned by `Worker::take_coverage()`. The
+/// offsets are in UTF-16 code units of the source.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ScriptCoverage {
+  url: String,
+  source: String,
+  functions: Vec<FunctionCoverage>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct FunctionCoverage {
+  function_name: String,
+  /// The function itself, followed by its blocks.
+  ranges: Vec<CoverageRange>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct CoverageRange {
+  start_offset: usize,
+  end_offset: usize,
+  count: u64,
+}
+
+/// How often the lines and functions of an original module ran.
+#[derive(Debug, Default, PartialEq)]
+pub struct FileCoverage {
+  /// Counts by line number, starting at 1. Lines without code are left out.
+  pub lines: BTreeMap<u32, u64>,
+  /// Calls by line number and name. Anonymous functions have no name.
+  pub functions: BTreeMap<(u32, String), u64>,
+}
+
+impl FileCoverage {
+  fn hit_lines(&self) -> usize {
+    self.lines.values().filter(|count| **count > 0).count()
+  }
+
+  fn missed_lines(&self) -> Vec<u32> {
+    self
+      .lines
+      .iter()
+      .filter(|(_, count)| **count == 0)
+      .map(|(line, _)| *line)
+      .collect()
+  }
+}
+
+/// The coverage of the local modules, merged from one or more isolates.
+#[derive(Debug, Default)]
+pub struct Coverage {
+  /// By the URL of the original module.
+  pub files: BTreeMap<String, FileCoverage>,
+}
+
+impl Coverage {
+  /// Adds the coverage returned by `Worker::take_coverage()`. Only local
+  /// modules are kept, which are looked up in `getter` for their source map.
+  pub fn add<G: SourceMapGetter>(&mut self, json: &str, getter: &G) {
+    let scripts: Vec<ScriptCoverage> = match serde_json::from_str(json) {
+      Ok(scripts) => scripts,
+      Err(err) => {
+        debug!("invalid coverage: {}", err);
+        return;
+      }
+    };
+    for script in scripts {
+      let is_local = Url::parse(&script.url)
+        .map(|url| url.scheme() == "file")
+        .unwrap_or(false);
+      if is_local {
+        let source_map = parse_map_string(&script.url, getter);
+        self.add_script(&script, source_map.as_ref());
+      }
+    }
+  }
+
+  /// Lines run several times in one script count as often as the code on them
+  /// which ran most, while the counts of several scripts add up.
+  fn add_script(
+    &mut self,
+    script: &ScriptCoverage,
+    source_map: Option<&SourceMap>,
+  ) {
+    let source: Vec<u16> = script.source.encode_utf16().collect();
+    let counts = offset_counts(&script.functions, source.len());
+    let original = |line: u32, column: u32| match source_map {
+      None => Some((script.url.as_str(), line + 1)),
+      Some(source_map) => source_map
+        .original_location(line, column)
+        .map(|(name, line, _)| (name, line + 1)),
+    };
+
+    let mut lines: BTreeMap<(&str, u32), u64> = BTreeMap::new();
+    let (mut line, mut column) = (0, 0);
+    for (offset, unit) in source.iter().enumerate() {
+      if *unit == u16::from(b'\n') {
+        line += 1;
+        column = 0;
+        continue;
+      }
+      if !is_whitespace(*unit) {
+        if let Some(key) = original(line, column) {
+          let count = lines.entry(key).or_insert(0);
+          *count = (*count).max(counts[offset]);
+        }
+      }
+      column += 1;
+    }
+    for ((name, line), count) in lines {
+      let file = self.file(&script.url, name);
+      *file.lines.entry(line).or_insert(0) += count;
+    }
+
+    let line_starts = line_starts(&source);
+    for function in script.functions.iter() {
+      let range = match function.ranges.first() {
+        Some(range) => range,
+        None => continue,
+      };
+      // The top-level code of the script is reported as a function too.
+      if function.function_name.is_empty() && range.start_offset == 0 {
+        continue;
+      }
+      let (line, column) = position(&line_starts, range.start_offset);
+      if let Some((name, line)) = original(line, column) {
+        let file = self.file(&script.url, name);
+        *file
+          .functions
+          .entry((line, function.function_name.clone()))
+          .or_insert(0) += range.count;
+      }
+    }
+  }
+
+  /// Returns the coverage of the original module `name`, which is relative to
+  /// `script_url` if its source map has relative sources.
+  fn file(&mut self, script_url: &str, name: &str) -> &mut FileCoverage {
+    let url = Url::parse(script_url)
+      .and_then(|url| url.join(name))
+      .map(|url| url.to_string())
+      .unwrap_or_else(|_| name.to_string());
+    self.files.entry(url).or_default()
+  }
+
+  /// Returns the coverage in the lcov tracefile format.
+  pub fn lcov(&self) -> String {
+    let mut out = String::new();
+    for (url, file) in self.files.iter() {
+      out.push_str("TN:\n");
+      out.push_str(&format!("SF:{}\n", display_name(url)));
+      let mut anonymous = 0;
+      let mut functions = Vec::new();
+      for ((line, name), count) in file.functions.iter() {
+        let name = if name.is_empty() {
+          anonymous += 1;
+          format!("(anonymous_{})", anonymous - 1)
+        } else {
+          name.clone()
+        };
+        out.push_str(&format!("FN:{},{}\n", line, name));
+        functions.push((name, count));
+      }
+      for (name, count) in functions.iter() {
+        out.push_str(&format!("FNDA:{},{}\n", count, name));
+      }
+      out.push_str(&format!("FNF:{}\n", functions.len()));
+      let functions_hit = functions.iter().filter(|(_, c)| **c > 0).count();
+      out.push_str(&format!("FNH:{}\n", functions_hit));
+      for (line, count) in file.lines.iter() {
+        out.push_str(&format!("DA:{},{}\n", line, count));
+      }
+      out.push_str(&format!("LF:{}\n", file.lines.len()));
+      out.push_str(&format!("LH:{}\n", file.hit_lines()));
+      out.push_str("end_of_record\n");
+    }
+    out
+  }
+
+  /// One line with the share of lines which ran for each module, followed by
+  /// the total.
+  pub fn summary(&self) -> String {
+    let mut out = String::new();
+    let (mut hit, mut total) = (0, 0);
+    for (url, file) in self.files.iter() {
+      let file_hit = file.hit_lines();
+      hit += file_hit;
+      total += file.lines.len();
+      out.push_str(&format!(
+        "cover {} ... {} ({}/{})",
+        display_name(url),
+        percent(file_hit, file.lines.len()),
+        file_hit,
+        file.lines.len()
+      ));
+      let missed = file.missed_lines();
+      if !missed.is_empty() {
+        out.push_str(&format!(", missed lines {}", line_ranges(&missed)));
+      }
+      out.push('\n');
+    }
+    out.push_str(&format!(
+      "coverage: {} ({}/{} lines)\n",
+      percent(hit, total),
+      hit,
+      total
+    ));
+    out
+  }
+
+  /// Writes the lcov tracefile to `dir`, which is created if needed.
+  pub fn write(&self, dir: &Path) -> io::Result<()> {
+    fs::create_dir_all(dir)?;
+    fs::write(dir.join(LCOV_FILE), self.lcov())
+  }
+}
+
+/// Returns how often the code at each offset ran. The ranges are nested, and
+/// an offset has the count of the innermost range which contains it.
+fn offset_counts(functions: &[FunctionCoverage], len: usize) -> Vec<u64> {
+  let mut ranges: Vec<&CoverageRange> = functions
+    .iter()
+    .flat_map(|function| function.ranges.iter())
+    .collect();
+  // Enclosing ranges come first, so the ranges inside them overwrite them.
+  ranges.sort_by_key(|range| (range.start_offset, Reverse(range.end_offset)));
+  let mut counts = vec![0; len];
+  for range in ranges {
+    let end = range.end_offset.min(len);
+    let start = range.start_offset.min(end);
+    for count in counts[start..end].iter_mut() {
+      *count = range.count;
+    }
+  }
+  counts
+}
+
+fn is_whitespace(unit: u16) -> bool {
+  std::char::from_u32(u32::from(unit)).map_or(false, char::is_whitespace)
+}
+
+fn line_starts(source: &[u16]) -> Vec<usize> {
+  let mut starts = vec![0];
+  for (offset, unit) in source.iter().enumerate() {
+    if *unit == u16::from(b'\n') {
+      starts.push(offset + 1);
+    }
+  }
+  starts
+}
+
+/// The zero-based line and column of `offset`.
+fn position(line_starts: &[usize], offset: usize) -> (u32, u32) {
+  let line = match line_starts.binary_search(&offset) {
+    Ok(line) => line,
+    Err(next_line) => next_line - 1,
+  };
+  (line as u32, (offset - line_starts[line]) as u32)
+}
+
+/// Local modules are shown as paths.
+fn display_name(url: &str) -> String {
+  Url::parse(url)
+    .ok()
+    .and_then(|url| url.to_file_path().ok())
+    .map(|path| path.to_string_lossy().to_string())
+    .unwrap_or_else(|| url.to_string())
+}
+
+fn percent(part: usize, total: usize) -> String {
+  if total == 0 {
+    return "100.00%".to_string();
+  }
+  format!("{:.2}%", 100.0 * part as f64 / total as f64)
+}
+
+/// Formats sorted line numbers like "3-5, 9".
+fn line_ranges(lines: &[u32]) -> String {
+  let mut ranges: Vec<(u32, u32)> = Vec::new();
+  for line in lines {
+    match ranges.last_mut() {
+      Some((_, end)) if *end + 1 == *line => *end = *line,
+      _ => ranges.push((*line, *line)),
+    }
+  }
+  ranges
+    .iter()
+    .map(|(start, end)| {
+      if start == end {
+        start.to_string()
+      } else {
+        format!("{}-{}", start, end)
+      }
+    }).collect::<Vec<_>>()
+    .join(", ")
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  struct MockSourceMapGetter {}
+
+  impl SourceMapGetter for MockSourceMapGetter {
+    fn get_source_map(&self, script_name: &str) -> Option<Vec<u8>> {
+      match script_name {
+        // Generated from math.ts, which has a comment in its first line.
+        "file:///src/math.js" => Some(
+          br#"{"sources": ["math.ts"], "mappings": "AACA;AACA;AACA;AACA;AACA;AACA;AACA"}"#.to_vec(),
+        ),
+        _ => None,
+      }
+    }
+  }
+
+  // Offsets of the ranges are computed from the source, so the test data
+  // stays readable.
+  fn range(source: &str, code: &str, count: u64) -> serde_json::Value {
+    let start = source.find(code).unwrap();
+    let start_offset = source[..start].encode_utf16().count();
+    let end_offset = start_offset + code.encode_utf16().count();
+    json!({
+      "startOffset": start_offset,
+      "endOffset": end_offset,
+      "count": count,
+    })
+  }
+
+  fn math_coverage(add_count: u64) -> String {
+    let source = "function add(a, b) {\n  return a + b;\n}\n\
+                  function sub(a, b) {\n  return a - b;\n}\n\
+                  add(1, 2);\n";
+    let add = "function add(a, b) {\n  return a + b;\n}";
+    let sub = "function sub(a, b) {\n  return a - b;\n}";
+    json!([
+      {
+        "url": "file:///src/math.js",
+        "source": source,
+        "functions": [
+          {
+            "functionName": "",
+            "ranges": [range(source, source, 1)],
+          },
+          {
+            "functionName": "add",
+            "ranges": [range(source, add, add_count)],
+          },
+          {
+            "functionName": "sub",
+            "ranges": [range(source, sub, 0)],
+          },
+        ],
+      },
+      {
+        "url": "gen/cli/bundle/main.js",
+        "source": "denoMain();",
+        "functions": [],
+      },
+    ]).to_string()
+  }
+
+  #[test]
+  fn test_offset_counts() {
+    let source = "if (a) { b(); } else { c(); }";
+    let functions: Vec<FunctionCoverage> = serde_json::from_value(json!([
+      {
+        "functionName": "",
+        "ranges": [
+          range(source, source, 1),
+          range(source, "{ c(); }", 0),
+          range(source, "c()", 3),
+        ],
+      },
+    ])).unwrap();
+    let counts = offset_counts(&functions, source.len());
+    assert_eq!(counts[0], 1);
+    assert_eq!(counts[source.find("b()").unwrap()], 1);
+    assert_eq!(counts[source.find("{ c").unwrap()], 0);
+    assert_eq!(counts[source.find("c()").unwrap()], 3);
+    assert_eq!(counts[source.len() - 1], 0);
+  }
+
+  #[test]
+  fn test_coverage_add() {
+    let getter = MockSourceMapGetter {};
+    let mut coverage = Coverage::default();
+    coverage.add(&math_coverage(1), &getter);
+    assert_eq!(
+      coverage.files.keys().collect::<Vec<_>>(),
+      vec!["file:///src/math.ts"]
+    );
+    let file = &coverage.files["file:///src/math.ts"];
+    let lines: Vec<(u32, u64)> = file
+      .lines
+      .iter()
+      .map(|(line, count)| (*line, *count))
+      .collect();
+    assert_eq!(
+      lines,
+      vec![(2, 1), (3, 1), (4, 1), (5, 0), (6, 0), (7, 0), (8, 1)]
+    );
+    assert_eq!(file.functions[&(2, "add".to_string())], 1);
+    assert_eq!(file.functions[&(5, "sub".to_string())], 0);
+
+    // The counts of several isolates add up.
+    coverage.add(&math_coverage(2), &getter);
+    let file = &coverage.files["file:///src/math.ts"];
+    assert_eq!(file.lines[&3], 3);
+    assert_eq!(file.lines[&6], 0);
+    assert_eq!(file.functions[&(2, "add".to_string())], 3);
+  }
+
+  #[test]
+  fn test_coverage_without_source_map() {
+    let source = "const a = 1;\n\n  console.log(a);\n";
+    let json = json!([{
+      "url": "file:///src/a.js",
+      "source": source,
+      "functions": [{
+        "functionName": "",
+        "ranges": [range(source, source, 1)],
+      }],
+    }]).to_string();
+    let mut coverage = Coverage::default();
+    coverage.add(&json, &MockSourceMapGetter {});
+    let file = &coverage.files["file:///src/a.js"];
+    assert_eq!(file.lines.keys().collect::<Vec<_>>(), vec![&1, &3]);
+    assert!(file.functions.is_empty());
+  }
+
+  #[test]
+  fn test_lcov() {
+    let mut coverage = Coverage::default();
+    coverage.add(&math_coverage(1), &MockSourceMapGetter {});
+    let lcov = coverage.lcov();
+    assert!(lcov.starts_with("TN:\nSF:"));
+    assert!(lcov.contains("math.ts\nFN:2,add\nFN:5,sub\n"));
+    assert!(lcov.contains("FNDA:1,add\nFNDA:0,sub\nFNF:2\nFNH:1\n"));
+    assert!(lcov.contains("DA:4,1\nDA:5,0\n"));
+    assert!(lcov.ends_with("LF:7\nLH:4\nend_of_record\n"));
+  }
+
+  #[test]
+  fn test_summary() {
+    let mut coverage = Coverage::default();
+    coverage.add(&math_coverage(1), &MockSourceMapGetter {});
+    let summary = coverage.summary();
+    assert!(summary.starts_with("cover "));
+    assert!(summary.contains("math.ts ... 57.14% (4/7), missed lines 5-7\n"));
+    assert!(summary.ends_with("coverage: 57.14% (4/7 lines)\n"));
+  }
+
+  #[test]
+  fn test_line_ranges() {
+    assert_eq!(line_ranges(&[]), "");
+    assert_eq!(line_ranges(&[3, 4, 5, 9, 11, 12]), "3-5, 9, 11-12");
+  }
+}
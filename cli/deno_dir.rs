@@ -7,10 +7,15 @@ use crate::errors::ErrorKind;
 use crate::fs as deno_fs;
 use crate::http_util;
 use crate::js_errors::SourceMapGetter;
+use crate::lockfile::sha256_hex;
+use crate::lockfile::Lockfile;
 use crate::msg;
 use crate::progress::Progress;
+use crate::scheme_handlers;
+use crate::scheme_handlers::SchemeHandler;
 use crate::tokio_util;
 use crate::version;
+use base64;
 use dirs;
 use futures::future::{loop_fn, Either, Loop};
 use futures::Future;
@@ -18,13 +23,22 @@ use http;
 use ring;
 use serde_json;
 use std;
+use std::collections::HashMap;
+use std::collections::HashSet;
 use std::fmt::Write;
 use std::fs;
 use std::path::Path;
 use std::path::PathBuf;
 use std::result::Result;
 use std::str;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::time::Duration;
+use std::time::SystemTime;
+use std::time::UNIX_EPOCH;
+use tokio_threadpool;
 use url;
+use url::percent_encoding::percent_decode;
 use url::Url;
 
 #[derive(Clone)]
@@ -42,9 +56,20 @@ pub struct DenoDir {
   // This splits to http and https deps
   pub deps_http: PathBuf,
   pub deps_https: PathBuf,
+  /// Decoded `data:` URL modules, named by the hash of their URL.
+  pub deps_data: PathBuf,
   /// The active configuration file contents (or empty array) which applies to
   /// source code cached by `DenoDir`.
   pub config: Vec<u8>,
+  /// The lock file given with `--lock`, which remote source code is checked
+  /// against.
+  pub lockfile: Option<Arc<Lockfile>>,
+  /// URL prefixes given with `--reload=<prefixes>`. Matching remote modules
+  /// are downloaded and compiled again, once per run.
+  pub reload_whitelist: Vec<String>,
+  /// With `--reload=revalidate` cached remote modules are revalidated with a
+  /// conditional request, once per run, instead of being used as they are.
+  pub revalidate: bool,
 
   pub progress: Progress,
 }
@@ -55,6 +80,9 @@ impl DenoDir {
   pub fn new(
     custom_root: Option<PathBuf>,
     state_config: &Option<Vec<u8>>,
+    lockfile: Option<Arc<Lockfile>>,
+    reload_whitelist: Vec<String>,
+    revalidate: bool,
     progress: Progress,
   ) -> std::io::Result<Self> {
     // Only setup once.
@@ -72,6 +100,7 @@ impl DenoDir {
     let deps = root.as_path().join("deps");
     let deps_http = deps.join("http");
     let deps_https = deps.join("https");
+    let deps_data = deps.join("data");
 
     // Internally within DenoDir, we use the config as part of the hash to
     // determine if a file has been transpiled with the same configuration, but
@@ -88,7 +117,11 @@ impl DenoDir {
       deps,
       deps_http,
       deps_https,
+      deps_data,
       config,
+      lockfile,
+      reload_whitelist,
+      revalidate,
       progress,
     };
 
@@ -97,12 +130,14 @@ impl DenoDir {
     deno_fs::mkdir(deno_dir.deps.as_ref(), 0o755, true)?;
     deno_fs::mkdir(deno_dir.deps_http.as_ref(), 0o755, true)?;
     deno_fs::mkdir(deno_dir.deps_https.as_ref(), 0o755, true)?;
+    deno_fs::mkdir(deno_dir.deps_data.as_ref(), 0o755, true)?;
 
     debug!("root {}", deno_dir.root.display());
     debug!("gen {}", deno_dir.gen.display());
     debug!("deps {}", deno_dir.deps.display());
     debug!("deps_http {}", deno_dir.deps_http.display());
     debug!("deps_https {}", deno_dir.deps_https.display());
+    debug!("deps_data {}", deno_dir.deps_data.display());
 
     Ok(deno_dir)
   }
@@ -127,25 +162,49 @@ impl DenoDir {
   ) -> std::io::Result<()> {
     let (cache_path, source_map_path) = self
       .cache_path(&module_meta_data.filename, &module_meta_data.source_code);
-    // TODO(ry) This is a race condition w.r.t to exists() -- probably should
-    // create the file in exclusive mode. A worry is what might happen is there
-    // are two processes and one reads the cache file while the other is in the
-    // midst of writing it.
+    // Records the source of the compiled code for gc().
+    let meta_path = cache_meta_path(&cache_path);
+    if !meta_path.exists() {
+      let meta = json!({
+        "filename": module_meta_data.filename,
+        "source_hash": sha256_hex(&module_meta_data.source_code),
+      });
+      deno_fs::write_file_atomic(&meta_path, meta.to_string(), 0o666)?;
+    }
+    // Another process may be writing the same files concurrently. That's fine
+    // because they are replaced atomically, and both write the same contents.
     if cache_path.exists() && source_map_path.exists() {
       Ok(())
     } else {
-      match &module_meta_data.maybe_output_code {
-        Some(output_code) => fs::write(cache_path, output_code),
+      match &module_meta_data.maybe_source_map {
+        Some(source_map) => {
+          deno_fs::write_file_atomic(&source_map_path, source_map, 0o666)
+        }
         _ => Ok(()),
       }?;
-      match &module_meta_data.maybe_source_map {
-        Some(source_map) => fs::write(source_map_path, source_map),
+      // Written last, as it is loaded together with the source map.
+      match &module_meta_data.maybe_output_code {
+        Some(output_code) => {
+          deno_fs::write_file_atomic(&cache_path, output_code, 0o666)
+        }
         _ => Ok(()),
       }?;
       Ok(())
     }
   }
 
+  /// Returns true if the URL of a remote module starts with one of the
+  /// prefixes given with `--reload=<prefixes>`, and the module wasn't
+  /// reloaded yet during this run.
+  fn needs_reload(self: &Self, module_name: &str, filename: &str) -> bool {
+    is_remote(module_name)
+      && self
+        .reload_whitelist
+        .iter()
+        .any(|prefix| module_name.starts_with(prefix.as_str()))
+      && REFRESHED.lock().unwrap().insert(filename.to_string())
+  }
+
   pub fn fetch_module_meta_data_async(
     self: &Self,
     specifier: &str,
@@ -163,9 +222,13 @@ impl DenoDir {
 
     let result = self.resolve_module(&specifier, &referrer);
     if let Err(err) = result {
-      return Either::A(futures::future::err(DenoError::from(err)));
+      return Either::A(futures::future::err(err));
     }
     let (module_name, filename) = result.unwrap();
+    // Modules matching --reload=<prefixes> bypass the cache, unless they
+    // can't be fetched anyway.
+    let use_cache =
+      use_cache && (no_fetch || !self.needs_reload(&module_name, &filename));
 
     let gen = self.gen.clone();
 
@@ -275,8 +338,9 @@ impl DenoDir {
         let prefix = "http://".to_string();
         (rest, prefix)
       } else {
-        // TODO(kevinkassimo): change this to support other protocols than http
-        unimplemented!()
+        // Modules of other schemes, like data: URLs, are named by a hash of
+        // their URL, which can't be recovered from the filename.
+        return String::from(filename);
       };
       // Windows doesn't support ":" in filenames, so we represent port using a
       // special string.
@@ -303,6 +367,9 @@ impl DenoDir {
       specifier, referrer
     );
 
+    if let Some(result) = scheme_handlers::resolve(&specifier, &referrer) {
+      return result;
+    }
     resolve_file_url(specifier, referrer)
   }
 
@@ -311,7 +378,7 @@ impl DenoDir {
     self: &Self,
     specifier: &str,
     referrer: &str,
-  ) -> Result<(String, String), url::ParseError> {
+  ) -> DenoResult<(String, String)> {
     let j = self.resolve_module_url(specifier, referrer)?;
 
     let module_name = j.to_string();
@@ -330,13 +397,257 @@ impl DenoDir {
           get_cache_filename(self.deps_http.as_path(), &j).as_ref(),
         )
       }
-      // TODO(kevinkassimo): change this to support other protocols than http.
-      _ => unimplemented!(),
+      "data" => {
+        // Cached under the hash of the URL, which has no path of its own.
+        filename = deno_fs::normalize_path(
+          self.deps_data.join(sha256_hex(module_name.as_bytes())).as_ref(),
+        )
+      }
+      scheme if scheme_handlers::get(&module_name).is_some() => {
+        // Keep the extension, which may determine the media type.
+        let extension = Path::new(j.path())
+          .extension()
+          .and_then(|ext| ext.to_str())
+          .map(|ext| format!(".{}", ext))
+          .unwrap_or_default();
+        let name = sha256_hex(module_name.as_bytes()) + &extension;
+        filename =
+          deno_fs::normalize_path(self.deps.join(scheme).join(name).as_ref())
+      }
+      scheme => {
+        return Err(errors::new(
+          ErrorKind::InvalidUri,
+          format!("No handler for the \"{}\" scheme: {}", scheme, j),
+        ))
+      }
     }
 
     debug!("module_name: {}, filename: {}", module_name, filename);
     Ok((module_name, filename))
   }
+
+  /// Lists all remote modules in the `deps` directory, sorted by URL.
+  pub fn cached_modules(self: &Self) -> std::io::Result<Vec<CachedModule>> {
+    let mut modules = Vec::new();
+    let deps = [("http", &self.deps_http), ("https", &self.deps_https)];
+    for (scheme, dir) in deps.iter() {
+      for filename in walk_files(dir)? {
+        let filename_str = deno_fs::normalize_path(&filename);
+        if filename_str.ends_with(".headers.json") || is_hidden(&filename) {
+          continue;
+        }
+        let url = match cache_filename_to_url(scheme, dir, &filename) {
+          Some(url) => url,
+          None => continue,
+        };
+        let metadata = fs::metadata(&filename)?;
+        let headers = get_source_code_headers(&filename_str);
+        let downloaded = match headers.fetched_at {
+          Some(secs) => UNIX_EPOCH + Duration::from_secs(secs),
+          None => metadata.modified()?,
+        };
+        modules.push(CachedModule {
+          url,
+          size: metadata.len(),
+          downloaded,
+          mime_type: headers.mime_type,
+          filename,
+        });
+      }
+    }
+    modules.sort_by(|a, b| a.url.cmp(&b.url));
+    Ok(modules)
+  }
+
+  /// Removes cached remote modules whose URL starts with `url_prefix` and
+  /// which were downloaded at least `older_than` ago. Returns the URLs of the
+  /// removed modules.
+  pub fn clean(
+    self: &Self,
+    older_than: Option<Duration>,
+    url_prefix: Option<&str>,
+  ) -> std::io::Result<Vec<String>> {
+    let now = SystemTime::now();
+    let mut removed = Vec::new();
+    for module in self.cached_modules()? {
+      if let Some(prefix) = url_prefix {
+        if !module.url.starts_with(prefix) {
+          continue;
+        }
+      }
+      if let Some(older_than) = older_than {
+        // Times in the future count as just downloaded.
+        let age = now
+          .duration_since(module.downloaded)
+          .unwrap_or_else(|_| Duration::from_secs(0));
+        if age < older_than {
+          continue;
+        }
+      }
+      fs::remove_file(&module.filename)?;
+      let headers_filename = source_code_headers_filename(
+        &deno_fs::normalize_path(&module.filename),
+      );
+      // May not exist.
+      let _ = fs::remove_file(&headers_filename);
+      // Remove directories left empty, remove_dir() fails on the others.
+      for dir in module.filename.ancestors().skip(1) {
+        if dir == self.deps_http || dir == self.deps_https {
+          break;
+        }
+        if fs::remove_dir(dir).is_err() {
+          break;
+        }
+      }
+      removed.push(module.url);
+    }
+    Ok(removed)
+  }
+
+  /// Removes compiled code from `gen` whose source file was removed or
+  /// changed since it was compiled, be it a local or a cached remote module.
+  /// Code compiled with another compiler configuration is kept while its
+  /// source is unchanged, as that configuration may still be used. Returns
+  /// the number of removed files.
+  pub fn gc(self: &Self) -> std::io::Result<usize> {
+    let mut live = HashMap::new();
+    let mut removed = 0;
+    for entry in fs::read_dir(&self.gen)? {
+      let path = entry?.path();
+      if is_hidden(&path) {
+        continue;
+      }
+      let name = match path.file_name().and_then(|n| n.to_str()) {
+        Some(name) => name.to_string(),
+        None => continue,
+      };
+      let extension = [".js.map", ".js", ".meta.json"]
+        .iter()
+        .find(|extension| name.ends_with(*extension));
+      let cache_key = match extension {
+        Some(extension) => name[..name.len() - extension.len()].to_string(),
+        None => continue,
+      };
+      let is_live = *live
+        .entry(cache_key.clone())
+        .or_insert_with(|| is_live_cache_entry(&self.gen, &cache_key));
+      if !is_live {
+        fs::remove_file(&path)?;
+        // The metadata isn't compiled code.
+        if !name.ends_with(".meta.json") {
+          removed += 1;
+        }
+      }
+    }
+    Ok(removed)
+  }
+}
+
+/// The file next to the compiled code `cache_path` which records the file and
+/// the hash of the source it was compiled from.
+fn cache_meta_path(cache_path: &Path) -> PathBuf {
+  cache_path.with_extension("meta.json")
+}
+
+/// Returns true if the source of the compiled code `cache_key` in `gen` is
+/// unchanged. Code cached without metadata by older versions is dead.
+fn is_live_cache_entry(gen: &Path, cache_key: &str) -> bool {
+  let meta_path = cache_meta_path(&gen.join(format!("{}.js", cache_key)));
+  let meta = match fs::read_to_string(meta_path)
+    .ok()
+    .and_then(|s| serde_json::from_str::<serde_json::Value>(&s).ok())
+  {
+    Some(meta) => meta,
+    None => return false,
+  };
+  let (filename, hash) =
+    match (meta["filename"].as_str(), meta["source_hash"].as_str()) {
+      (Some(filename), Some(hash)) => (filename, hash),
+      _ => return false,
+    };
+  match fs::read(filename) {
+    Ok(mut source_code) => {
+      // Same as fetch_module_meta_data_async(), which computes the cache key.
+      if source_code.starts_with(b"#!") {
+        source_code = filter_shebang(source_code);
+      }
+      sha256_hex(&source_code) == hash
+    }
+    Err(_) => false,
+  }
+}
+
+/// A remote module in the `deps` directory of `DenoDir`.
+#[derive(Debug)]
+pub struct CachedModule {
+  pub url: String,
+  pub filename: PathBuf,
+  pub size: u64,
+  /// When the module was downloaded, as recorded in `.headers.json`. Modules
+  /// downloaded by older versions use the modification time of the file.
+  pub downloaded: SystemTime,
+  /// The MIME type recorded in `.headers.json`, if any.
+  pub mime_type: Option<String>,
+}
+
+/// Lock and temporary files in the cache are hidden, see
+/// `download_lock_filename()` and `deno_fs::write_file_atomic()`.
+fn is_hidden(filename: &Path) -> bool {
+  filename
+    .file_name()
+    .and_then(|name| name.to_str())
+    .map_or(false, |name| name.starts_with('.'))
+}
+
+/// The file locked while `filename` is downloaded. It is removed afterwards.
+fn download_lock_filename(filename: &str) -> PathBuf {
+  let p = Path::new(filename);
+  let name = p.file_name().and_then(|name| name.to_str()).unwrap_or("");
+  p.with_file_name(format!(".{}.lock", name))
+}
+
+/// Recursively lists the files below `dir`. A missing `dir` is empty.
+fn walk_files(dir: &Path) -> std::io::Result<Vec<PathBuf>> {
+  let mut files = Vec::new();
+  let entries = match fs::read_dir(dir) {
+    Ok(entries) => entries,
+    Err(ref e) if e.kind() == std::io::ErrorKind::NotFound => {
+      return Ok(files);
+    }
+    Err(e) => return Err(e),
+  };
+  for entry in entries {
+    let entry = entry?;
+    if entry.file_type()?.is_dir() {
+      files.extend(walk_files(&entry.path())?);
+    } else {
+      files.push(entry.path());
+    }
+  }
+  Ok(files)
+}
+
+/// The inverse of `get_cache_filename()`.
+fn cache_filename_to_url(
+  scheme: &str,
+  basedir: &Path,
+  filename: &Path,
+) -> Option<String> {
+  let relative = filename.strip_prefix(basedir).ok()?;
+  let mut components = relative.components();
+  let host_port = components.next()?.as_os_str().to_str()?;
+  let host = match host_port.rfind("_PORT") {
+    Some(i) => format!("{}:{}", &host_port[..i], &host_port[i + 5..]),
+    None => host_port.to_string(),
+  };
+  let mut path = String::new();
+  for component in components {
+    path.push('/');
+    path.push_str(component.as_os_str().to_str()?);
+  }
+  Url::parse(&format!("{}://{}{}", scheme, host, path))
+    .ok()
+    .map(|url| url.to_string())
 }
 
 impl SourceMapGetter for DenoDir {
@@ -373,12 +684,36 @@ fn get_source_code_async(
 ) -> impl Future<Item = ModuleMetaData, Error = DenoError> {
   let filename = filename.to_string();
   let module_name = module_name.to_string();
+  if is_data_url(&module_name) {
+    return Either::A(futures::future::result(fetch_data_url_source(
+      &module_name,
+      &filename,
+    )));
+  }
+  // Modules of custom schemes are loaded again unless fetching is disabled,
+  // their handler can cache them if it wants to.
+  if !no_fetch {
+    if let Some(handler) = scheme_handlers::get(&module_name) {
+      return Either::B(Either::B(fetch_scheme_source_async(
+        handler,
+        &module_name,
+        &filename,
+      )));
+    }
+  }
   let is_module_remote = is_remote(&module_name);
+  // With --reload=revalidate a cached remote module is only used after the
+  // server confirmed that it didn't change.
+  let revalidate = is_module_remote
+    && use_cache
+    && !no_fetch
+    && deno_dir.revalidate
+    && needs_revalidation(&filename);
   // We try fetch local. Three cases:
   // 1. Remote downloads are not allowed, we're only allowed to use cache.
   // 2. This is a remote module and we're allowed to use cached downloads.
   // 3. This is a local module.
-  if !is_module_remote || use_cache || no_fetch {
+  if !is_module_remote || (use_cache && !revalidate) || no_fetch {
     debug!(
       "fetch local or reload {} is_module_remote {}",
       module_name, is_module_remote
@@ -387,7 +722,10 @@ fn get_source_code_async(
     match fetch_local_source(deno_dir, &module_name, &filename, None) {
       Ok(Some(output)) => {
         debug!("found local source ");
-        return Either::A(futures::future::ok(output));
+        return Either::A(futures::future::result(check_lockfile(
+          deno_dir.lockfile.as_ref(),
+          output,
+        )));
       }
       Ok(None) => {
         debug!("fetch_local_source returned None");
@@ -423,17 +761,83 @@ fn get_source_code_async(
   debug!("is remote but didn't find module");
 
   // not cached/local, try remote.
-  Either::B(
-    fetch_remote_source_async(deno_dir, &module_name, &filename).and_then(
-      move |maybe_remote_source| match maybe_remote_source {
+  Either::B(Either::A(
+    fetch_remote_source_async(deno_dir, &module_name, &filename, revalidate)
+      .and_then(move |maybe_remote_source| match maybe_remote_source {
         Some(output) => Ok(output),
         None => Err(DenoError::from(std::io::Error::new(
           std::io::ErrorKind::NotFound,
           format!("cannot find remote file '{}'", &filename),
         ))),
-      },
-    ),
-  )
+      }),
+  ))
+}
+
+/// Loads a module of a custom scheme with its `SchemeHandler`, and caches it
+/// at `filename` so that it can be read back like any other cached module.
+fn fetch_scheme_source_async(
+  handler: Arc<dyn SchemeHandler>,
+  module_name: &str,
+  filename: &str,
+) -> impl Future<Item = ModuleMetaData, Error = DenoError> {
+  let module_name = module_name.to_string();
+  let filename = filename.to_string();
+  // The module name was serialized from a Url by resolve_module().
+  let url = Url::parse(&module_name).unwrap();
+  handler.load(&url).and_then(move |loaded| {
+    let p = PathBuf::from(&filename);
+    if let Some(parent) = p.parent() {
+      fs::create_dir_all(parent)?;
+    }
+    deno_fs::write_file_atomic(&p, &loaded.source_code, 0o666)?;
+    save_source_code_headers(&filename, loaded.mime_type.clone(), None, None);
+    Ok(ModuleMetaData {
+      module_name,
+      module_redirect_source_name: None,
+      media_type: map_content_type(
+        &p,
+        loaded.mime_type.as_ref().map(String::as_str),
+      ),
+      filename,
+      source_code: loaded.source_code,
+      maybe_output_code_filename: None,
+      maybe_output_code: None,
+      maybe_source_map_filename: None,
+      maybe_source_map: None,
+    })
+  })
+}
+
+lazy_static! {
+  /// Cached files which were already reloaded or revalidated. This happens
+  /// once per process, even though each worker has its own `DenoDir`.
+  static ref REFRESHED: Mutex<HashSet<String>> = Mutex::new(HashSet::new());
+}
+
+/// Returns true the first time it is called for a cached remote module during
+/// this run, unless the server said that the module never changes.
+fn needs_revalidation(filename: &str) -> bool {
+  if get_source_code_headers(filename).cache_headers.is_immutable() {
+    return false;
+  }
+  REFRESHED.lock().unwrap().insert(filename.to_string())
+}
+
+/// Verifies the source code of a remote module against the lock file, or
+/// records its hash with `--lock-write`. Local modules are passed through.
+fn check_lockfile(
+  maybe_lockfile: Option<&Arc<Lockfile>>,
+  module_meta_data: ModuleMetaData,
+) -> DenoResult<ModuleMetaData> {
+  if let Some(lockfile) = maybe_lockfile {
+    if is_remote(&module_meta_data.module_name) {
+      lockfile.check_or_insert(
+        &module_meta_data.module_name,
+        &module_meta_data.source_code,
+      )?;
+    }
+  }
+  Ok(module_meta_data)
 }
 
 #[cfg(test)]
@@ -455,7 +859,7 @@ fn get_source_code(
   ))
 }
 
-fn get_cache_filename(basedir: &Path, url: &Url) -> PathBuf {
+pub fn get_cache_filename(basedir: &Path, url: &Url) -> PathBuf {
   let host = url.host_str().unwrap();
   let host_port = match url.port() {
     // Windows doesn't support ":" in filenames, so we represent port using a
@@ -508,12 +912,76 @@ fn source_code_hash(
   out
 }
 
-fn is_remote(module_name: &str) -> bool {
+pub fn is_remote(module_name: &str) -> bool {
   module_name.starts_with("http://") || module_name.starts_with("https://")
 }
 
+/// Modules can be inlined in their specifier with a `data:` URL, e.g.
+/// `data:application/typescript;base64,ZXhwb3J0IHt9Ow==`.
+pub fn is_data_url(module_name: &str) -> bool {
+  module_name.starts_with("data:")
+}
+
+/// Splits a `data:` URL into its MIME type, if any, and decoded contents.
+fn decode_data_url(url: &Url) -> DenoResult<(Option<String>, Vec<u8>)> {
+  let invalid = || {
+    errors::new(
+      ErrorKind::InvalidData,
+      format!("Invalid data URL \"{}\"", url),
+    )
+  };
+  let path = url.path();
+  let comma = path.find(',').ok_or_else(invalid)?;
+  let (mut mime_type, data) = (&path[..comma], &path[comma + 1..]);
+  let data: Vec<u8> = percent_decode(data.as_bytes()).collect();
+  let is_base64 = mime_type.to_lowercase().ends_with(";base64");
+  if is_base64 {
+    mime_type = &mime_type[..mime_type.len() - ";base64".len()];
+  }
+  // Drop parameters like ";charset=utf-8", the media type alone decides how
+  // the module is compiled.
+  if let Some(semicolon) = mime_type.find(';') {
+    mime_type = &mime_type[..semicolon];
+  }
+  let mime_type = match mime_type.trim() {
+    "" => None,
+    mime_type => Some(mime_type.to_lowercase()),
+  };
+  let data = if is_base64 {
+    base64::decode(&data).map_err(|_| invalid())?
+  } else {
+    data
+  };
+  Ok((mime_type, data))
+}
+
+/// Decodes the `data:` URL `module_name` and caches its source code at
+/// `filename`, so it can be read back like any other cached module.
+fn fetch_data_url_source(
+  module_name: &str,
+  filename: &str,
+) -> DenoResult<ModuleMetaData> {
+  let (mime_type, source_code) = decode_data_url(&Url::parse(module_name)?)?;
+  let p = Path::new(filename);
+  if !p.exists() {
+    deno_fs::write_file_atomic(p, &source_code, 0o666)?;
+    save_source_code_headers(filename, mime_type.clone(), None, None);
+  }
+  Ok(ModuleMetaData {
+    module_name: module_name.to_string(),
+    module_redirect_source_name: None,
+    filename: filename.to_string(),
+    media_type: map_content_type(p, mime_type.as_ref().map(String::as_str)),
+    source_code,
+    maybe_output_code_filename: None,
+    maybe_output_code: None,
+    maybe_source_map_filename: None,
+    maybe_source_map: None,
+  })
+}
+
 fn parse_local_or_remote(p: &str) -> Result<url::Url, url::ParseError> {
-  if is_remote(p) || p.starts_with("file:") {
+  if is_remote(p) || is_data_url(p) || p.starts_with("file:") {
     Url::parse(p)
   } else {
     Url::from_file_path(p).map_err(|_err| url::ParseError::IdnaError)
@@ -577,32 +1045,116 @@ fn filter_shebang(bytes: Vec<u8>) -> Vec<u8> {
 
 /// Asynchronously fetch remote source file specified by the URL `module_name`
 /// and write it to disk at `filename`.
+/// If `conditional` is true and the module is already cached, the request is
+/// conditional and the cached file is kept if the server answers with 304.
+/// The source is checked against the lock file, and a download which fails
+/// the check isn't written.
 fn fetch_remote_source_async(
   deno_dir: &DenoDir,
   module_name: &str,
   filename: &str,
+  conditional: bool,
 ) -> impl Future<Item = Option<ModuleMetaData>, Error = DenoError> {
-  use crate::http_util::FetchOnceResult;
-
-  let download_job = deno_dir
-    .progress
-    .add(format!("Downloading {}", module_name));
-
+  let download_job = deno_dir.progress.add(format!(
+    "{} {}",
+    if conditional { "Checking" } else { "Downloading" },
+    module_name
+  ));
+
+  // Other processes sharing this DENO_DIR may be downloading the same module.
+  // Wait for them, so the module isn't written by several at the same time.
+  let lock_filename = download_lock_filename(filename);
+  let cache_modified = cache_modified_times(filename);
+  let dir = deno_dir.clone();
   let filename = filename.to_owned();
   let module_name = module_name.to_owned();
 
+  acquire_download_lock(lock_filename)
+    .and_then(move |download_lock| {
+      // Use the module another process downloaded while we were waiting.
+      if cache_modified_times(&filename) != cache_modified {
+        match fetch_local_source(&dir, &module_name, &filename, None) {
+          Ok(Some(output)) => {
+            return Either::A(futures::future::result(
+              check_lockfile(dir.lockfile.as_ref(), output).map(Some),
+            ))
+          }
+          Ok(None) => {}
+          Err(err) => return Either::A(futures::future::err(err)),
+        }
+      }
+      Either::B(
+        fetch_remote_source_locked(dir, module_name, filename, conditional)
+          .then(move |r| {
+            drop(download_lock);
+            r
+          }),
+      )
+    }).then(move |r| {
+      // Explicit drop to keep reference alive until future completes.
+      drop(download_job);
+      r
+    })
+}
+
+/// Returns when the cached copy of a module and its headers were last
+/// modified, if they exist.
+fn cache_modified_times(
+  filename: &str,
+) -> (Option<SystemTime>, Option<SystemTime>) {
+  let modified = |filename: &str| {
+    fs::metadata(filename)
+      .and_then(|metadata| metadata.modified())
+      .ok()
+  };
+  (
+    modified(filename),
+    modified(&source_code_headers_filename(filename)),
+  )
+}
+
+/// Acquires the lock on `lock_filename`. Another process may hold it for a
+/// whole download, so it is waited for outside of the executor. Returns
+/// `None` if the file can't be locked at all.
+fn acquire_download_lock(
+  lock_filename: PathBuf,
+) -> impl Future<Item = Option<deno_fs::FileLock>, Error = DenoError> {
+  if let Some(parent) = lock_filename.parent() {
+    let _ = fs::create_dir_all(parent);
+  }
+  let acquire = move || match deno_fs::FileLock::acquire(&lock_filename) {
+    Ok(lock) => Some(lock),
+    Err(e) => {
+      debug!("Unable to lock {}: {}", lock_filename.display(), e);
+      None
+    }
+  };
+  futures::future::poll_fn(move || {
+    match tokio_threadpool::blocking(|| acquire()) {
+      Ok(async_lock) => Ok(async_lock),
+      // Not running on a thread pool, so block the thread.
+      Err(_) => Ok(futures::Async::Ready(acquire())),
+    }
+  })
+}
+
+/// Downloads the module like `fetch_remote_source_async()`, once its download
+/// lock is held.
+fn fetch_remote_source_locked(
+  deno_dir: DenoDir,
+  module_name: String,
+  filename: String,
+  conditional: bool,
+) -> impl Future<Item = Option<ModuleMetaData>, Error = DenoError> {
+  use crate::http_util::CacheHeaders;
+  use crate::http_util::FetchOnceResult;
+
   // We write a special ".headers.json" file into the `.deno/deps` directory along side the
   // cached file, containing just the media type and possible redirect target (both are http headers).
   // If redirect target is present, the file itself if not cached.
   // In future resolutions, we would instead follow this redirect target ("redirect_to").
   loop_fn(
-    (
-      deno_dir.clone(),
-      None,
-      None,
-      module_name.clone(),
-      filename.clone(),
-    ),
+    (deno_dir, None, None, module_name, filename),
     |(
       dir,
       mut maybe_initial_module_name,
@@ -611,14 +1163,18 @@ fn fetch_remote_source_async(
       filename,
     )| {
       let url = module_name.parse::<http::uri::Uri>().unwrap();
+      // Only send validators if there is a cached file they apply to.
+      let validators = if conditional && Path::new(&filename).is_file() {
+        get_source_code_headers(&filename).cache_headers
+      } else {
+        CacheHeaders::default()
+      };
       // Single pass fetch, either yields code or yields redirect.
-      http_util::fetch_string_once(url).and_then(move |fetch_once_result| {
-        match fetch_once_result {
+      http_util::fetch_string_once(url, &validators).and_then(
+        move |fetch_once_result| match fetch_once_result {
           FetchOnceResult::Redirect(url) => {
             // If redirects, update module_name and filename for next looped call.
-            let resolve_result = dir
-              .resolve_module(&(url.to_string()), ".")
-              .map_err(DenoError::from);
+            let resolve_result = dir.resolve_module(&(url.to_string()), ".");
             match resolve_result {
               Ok((new_module_name, new_filename)) => {
                 if maybe_initial_module_name.is_none() {
@@ -637,21 +1193,27 @@ fn fetch_remote_source_async(
               Err(e) => Err(e),
             }
           }
-          FetchOnceResult::Code(source, maybe_content_type) => {
-            // We land on the code.
+          FetchOnceResult::Code(source, maybe_content_type, cache_headers) => {
+            // We land on the code. Check it against the lock file before it
+            // is cached, so that later runs don't load it from the cache.
+            if let Some(ref lockfile) = dir.lockfile {
+              lockfile.check_or_insert(&module_name, source.as_bytes())?;
+            }
             let p = PathBuf::from(filename.clone());
             match p.parent() {
               Some(ref parent) => fs::create_dir_all(parent),
               None => Ok(()),
             }?;
             // Write file and create .headers.json for the file.
-            deno_fs::write_file(&p, &source, 0o666)?;
+            deno_fs::write_file_atomic(&p, &source, 0o666)?;
             {
               save_source_code_headers(
                 &filename,
                 maybe_content_type.clone(),
                 None,
+                Some(cache_headers),
               );
+              save_fetch_time(&filename);
             }
             // Check if this file is downloaded due to some old redirect request.
             if maybe_initial_filename.is_some() {
@@ -667,6 +1229,7 @@ fn fetch_remote_source_async(
                   &maybe_initial_filename.clone().unwrap(),
                   maybe_content_type.clone(),
                   Some(module_name.clone()),
+                  None,
                 );
               }
             }
@@ -685,14 +1248,43 @@ fn fetch_remote_source_async(
               maybe_source_map: None,
             })))
           }
-        }
-      })
+          FetchOnceResult::NotModified(cache_headers) => {
+            // The cached file is up to date. Keep it and the headers the
+            // server sent along with the 304.
+            let mut headers = get_source_code_headers(&filename);
+            headers.cache_headers.update(cache_headers);
+            save_source_code_headers(
+              &filename,
+              headers.mime_type.clone(),
+              None,
+              Some(headers.cache_headers),
+            );
+            if let Some(ref initial_filename) = maybe_initial_filename {
+              save_source_code_headers(
+                initial_filename,
+                headers.mime_type,
+                Some(module_name.clone()),
+                None,
+              );
+            }
+            let maybe_output = fetch_local_source(
+              &dir,
+              &module_name,
+              &filename,
+              maybe_initial_module_name,
+            )?;
+            match maybe_output {
+              Some(output) => Ok(Loop::Break(Some(check_lockfile(
+                dir.lockfile.as_ref(),
+                output,
+              )?))),
+              None => Ok(Loop::Break(None)),
+            }
+          }
+        },
+      )
     },
-  ).then(move |r| {
-    // Explicit drop to keep reference alive until future completes.
-    drop(download_job);
-    r
-  })
+  )
 }
 
 /// Fetch remote source code.
@@ -706,6 +1298,7 @@ fn fetch_remote_source(
     deno_dir,
     module_name,
     filename,
+    false,
   ))
 }
 
@@ -791,10 +1384,19 @@ pub struct SourceCodeHeaders {
   /// Where should we actually look for source code.
   /// This should be an absolute path!
   pub redirect_to: Option<String>,
+  /// ETag, Last-Modified and Cache-Control of the response, used to
+  /// revalidate the cached file.
+  pub cache_headers: http_util::CacheHeaders,
+  /// When the source code was downloaded, in seconds since the unix epoch.
+  pub fetched_at: Option<u64>,
 }
 
 static MIME_TYPE: &'static str = "mime_type";
 static REDIRECT_TO: &'static str = "redirect_to";
+static ETAG: &'static str = "etag";
+static LAST_MODIFIED: &'static str = "last_modified";
+static CACHE_CONTROL: &'static str = "cache_control";
+static FETCHED_AT: &'static str = "fetched_at";
 
 fn source_code_headers_filename(filename: &str) -> String {
   [&filename, ".headers.json"].concat()
@@ -804,7 +1406,7 @@ fn source_code_headers_filename(filename: &str) -> String {
 /// NOTICE: chances are that the source code itself is not downloaded due to redirects.
 /// In this case, the headers file provides info about where we should go and get
 /// the source code that redirect eventually points to (which should be cached).
-fn get_source_code_headers(filename: &str) -> SourceCodeHeaders {
+pub fn get_source_code_headers(filename: &str) -> SourceCodeHeaders {
   let headers_filename = source_code_headers_filename(filename);
   let hd = Path::new(&headers_filename);
   // .headers.json file might not exists.
@@ -818,12 +1420,20 @@ fn get_source_code_headers(filename: &str) -> SourceCodeHeaders {
       return SourceCodeHeaders {
         mime_type: headers[MIME_TYPE].as_str().map(String::from),
         redirect_to: headers[REDIRECT_TO].as_str().map(String::from),
+        cache_headers: http_util::CacheHeaders {
+          etag: headers[ETAG].as_str().map(String::from),
+          last_modified: headers[LAST_MODIFIED].as_str().map(String::from),
+          cache_control: headers[CACHE_CONTROL].as_str().map(String::from),
+        },
+        fetched_at: headers[FETCHED_AT].as_u64(),
       };
     }
   }
   SourceCodeHeaders {
     mime_type: None,
     redirect_to: None,
+    cache_headers: Default::default(),
+    fetched_at: None,
   }
 }
 
@@ -832,15 +1442,13 @@ fn get_source_code_headers(filename: &str) -> SourceCodeHeaders {
 /// For example, if the extension ".js" already mean JS file and we have
 /// content type of "text/javascript", then we would not save the mime type.
 /// If nothing needs to be saved, the headers file is not created.
-fn save_source_code_headers(
+pub fn save_source_code_headers(
   filename: &str,
   mime_type: Option<String>,
   redirect_to: Option<String>,
+  cache_headers: Option<http_util::CacheHeaders>,
 ) {
   let headers_filename = source_code_headers_filename(filename);
-  // Remove possibly existing stale .headers.json file.
-  // May not exist. DON'T unwrap.
-  let _ = std::fs::remove_file(&headers_filename);
   let p = PathBuf::from(filename);
   // TODO(kevinkassimo): consider introduce serde::Deserialize to make things simpler.
   // This is super ugly at this moment...
@@ -861,19 +1469,64 @@ fn save_source_code_headers(
   if redirect_to.is_some() {
     value_map.insert(REDIRECT_TO.to_string(), json!(redirect_to.unwrap()));
   }
+  if let Some(cache_headers) = cache_headers {
+    if let Some(etag) = cache_headers.etag {
+      value_map.insert(ETAG.to_string(), json!(etag));
+    }
+    if let Some(last_modified) = cache_headers.last_modified {
+      value_map.insert(LAST_MODIFIED.to_string(), json!(last_modified));
+    }
+    if let Some(cache_control) = cache_headers.cache_control {
+      value_map.insert(CACHE_CONTROL.to_string(), json!(cache_control));
+    }
+  }
+  // The download time is kept until the file is downloaded again.
+  if let Some(fetched_at) = get_source_code_headers(filename).fetched_at {
+    value_map.insert(FETCHED_AT.to_string(), json!(fetched_at));
+  }
+  write_source_code_headers(&headers_filename, &value_map);
+}
+
+/// Records in the `.headers.json` file of `filename` that it was downloaded
+/// just now.
+fn save_fetch_time(filename: &str) {
+  let headers_filename = source_code_headers_filename(filename);
+  let mut value_map = match fs::read_to_string(&headers_filename)
+    .ok()
+    .and_then(|s| serde_json::from_str::<serde_json::Value>(&s).ok())
+  {
+    Some(serde_json::Value::Object(value_map)) => value_map,
+    _ => serde_json::map::Map::new(),
+  };
+  let now = SystemTime::now()
+    .duration_since(UNIX_EPOCH)
+    .map(|d| d.as_secs())
+    .unwrap_or(0);
+  value_map.insert(FETCHED_AT.to_string(), json!(now));
+  write_source_code_headers(&headers_filename, &value_map);
+}
+
+fn write_source_code_headers(
+  headers_filename: &str,
+  value_map: &serde_json::map::Map<String, serde_json::Value>,
+) {
   // Only save to file when there is actually data.
   if !value_map.is_empty() {
-    let _ = serde_json::to_string(&value_map).map(|s| {
+    let _ = serde_json::to_string(value_map).map(|s| {
       // It is possible that we need to create file
       // with parent folders not yet created.
       // (Due to .headers.json feature for redirection)
-      let hd = PathBuf::from(&headers_filename);
+      let hd = PathBuf::from(headers_filename);
       let _ = match hd.parent() {
         Some(ref parent) => fs::create_dir_all(parent),
         None => Ok(()),
       };
-      let _ = deno_fs::write_file(&(hd.as_path()), s, 0o666);
+      let _ = deno_fs::write_file_atomic(&(hd.as_path()), s, 0o666);
     });
+  } else {
+    // Remove possibly existing stale .headers.json file.
+    // May not exist. DON'T unwrap.
+    let _ = std::fs::remove_file(headers_filename);
   }
 }
 
@@ -888,6 +1541,7 @@ pub fn resolve_file_url(
   }
 
   let j = if is_remote(&specifier)
+    || is_data_url(&specifier)
     || (Path::new(&specifier).is_absolute() && !is_remote(&referrer))
   {
     parse_local_or_remote(&specifier)?
@@ -925,6 +1579,9 @@ mod tests {
     let deno_dir = DenoDir::new(
       Some(temp_dir.path().to_path_buf()),
       &config,
+      None,
+      vec![],
+      false,
       Progress::new(),
     ).expect("setup fail");
     (temp_dir, deno_dir)
@@ -1074,6 +1731,7 @@ mod tests {
       &filename,
       Some("text/typescript".to_owned()),
       Some("http://deno.land/a.js".to_owned()),
+      None,
     );
     let headers2 = get_source_code_headers(&filename);
     assert_eq!(headers2.mime_type.clone().unwrap(), "text/typescript");
@@ -1081,6 +1739,23 @@ mod tests {
       headers2.redirect_to.clone().unwrap(),
       "http://deno.land/a.js"
     );
+    assert_eq!(headers2.cache_headers, http_util::CacheHeaders::default());
+
+    let cache_headers = http_util::CacheHeaders {
+      etag: Some("\"abc\"".to_owned()),
+      last_modified: Some("Wed, 21 Oct 2015 07:28:00 GMT".to_owned()),
+      cache_control: Some("max-age=3600".to_owned()),
+    };
+    save_source_code_headers(
+      &filename,
+      Some("text/javascript".to_owned()),
+      None,
+      Some(cache_headers.clone()),
+    );
+    let headers3 = get_source_code_headers(&filename);
+    // Matches the extension, so the mime type isn't saved.
+    assert!(headers3.mime_type.is_none());
+    assert_eq!(headers3.cache_headers, cache_headers);
   }
 
   #[test]
@@ -1106,8 +1781,8 @@ mod tests {
         "export { printHello } from \"./print_hello.ts\";\n".as_bytes()
       );
       assert_eq!(&(r.media_type), &msg::MediaType::TypeScript);
-      // Should not create .headers.json file due to matching ext
-      assert!(fs::read_to_string(&headers_file_name).is_err());
+      // Should not record the mime type due to matching ext
+      assert!(get_source_code_headers(&filename).mime_type.is_none());
 
       // Modify .headers.json, write using fs write and read using save_source_code_headers
       let _ =
@@ -1133,6 +1808,7 @@ mod tests {
         &filename,
         Some("application/json".to_owned()),
         None,
+        None,
       );
       let result3 =
         get_source_code(&deno_dir, module_name, &filename, true, false);
@@ -1159,9 +1835,9 @@ mod tests {
       let expected4 =
         "export { printHello } from \"./print_hello.ts\";\n".as_bytes();
       assert_eq!(r4.source_code, expected4);
-      // Now the old .headers.json file should have gone! Resolved back to TypeScript
+      // Now the old mime type should have gone! Resolved back to TypeScript
       assert_eq!(&(r4.media_type), &msg::MediaType::TypeScript);
-      assert!(fs::read_to_string(&headers_file_name).is_err());
+      assert!(get_source_code_headers(&filename).mime_type.is_none());
     });
   }
 
@@ -1197,6 +1873,7 @@ mod tests {
         &filename,
         Some("text/typescript".to_owned()),
         None,
+        None,
       );
       let result2 =
         get_source_code(&deno_dir, module_name, &filename, true, false);
@@ -1207,7 +1884,12 @@ mod tests {
       // If get_source_code does not call remote, this should be TypeScript
       // as we modified before! (we do not overwrite .headers.json due to no http fetch)
       assert_eq!(&(r2.media_type), &msg::MediaType::TypeScript);
-      assert!(fs::read_to_string(&headers_file_name).is_err());
+      // Only the download time is left in .headers.json.
+      assert!(
+        !fs::read_to_string(&headers_file_name)
+          .unwrap()
+          .contains("mime_type")
+      );
 
       // Don't use_cache
       let result3 =
@@ -1394,25 +2076,26 @@ mod tests {
           .join("127.0.0.1_PORT4545/tests/subdir/mt_video_mp2t.t3.ts")
           .as_ref(),
       );
-      let headers_file_name = source_code_headers_filename(&filename);
 
       let result = tokio_util::block_on(fetch_remote_source_async(
         &deno_dir,
         &module_name,
         &filename,
+        false,
       ));
       assert!(result.is_ok());
       let r = result.unwrap().unwrap();
       assert_eq!(r.source_code, b"export const loaded = true;\n");
       assert_eq!(&(r.media_type), &msg::MediaType::TypeScript);
-      // matching ext, no .headers.json file created
-      assert!(fs::read_to_string(&headers_file_name).is_err());
+      // matching ext, no mime type recorded
+      assert!(get_source_code_headers(&filename).mime_type.is_none());
 
       // Modify .headers.json, make sure read from local
       save_source_code_headers(
         &filename,
         Some("text/javascript".to_owned()),
         None,
+        None,
       );
       let result2 =
         fetch_local_source(&deno_dir, &module_name, &filename, None);
@@ -1424,6 +2107,102 @@ mod tests {
     });
   }
 
+  #[test]
+  fn test_fetch_source_async_lockfile() {
+    use crate::tokio_util;
+    tokio_util::init(|| {
+      let (temp_dir, mut deno_dir) = test_setup();
+      let module_name =
+        "http://127.0.0.1:4545/tests/subdir/mt_video_mp2t.t3.ts".to_string();
+      let filename = deno_fs::normalize_path(
+        deno_dir
+          .deps_http
+          .join("127.0.0.1_PORT4545/tests/subdir/mt_video_mp2t.t3.ts")
+          .as_ref(),
+      );
+      let lock_filename = temp_dir.path().join("lock.json");
+      fs::write(&lock_filename, format!("{{\"{}\": \"bad\"}}", module_name))
+        .unwrap();
+      let lockfile = Lockfile::new(lock_filename, false).unwrap();
+      deno_dir.lockfile = Some(Arc::new(lockfile));
+
+      let result = tokio_util::block_on(fetch_remote_source_async(
+        &deno_dir,
+        &module_name,
+        &filename,
+        false,
+      ));
+      assert_eq!(result.err().unwrap().kind(), ErrorKind::InvalidData);
+      // The download which failed the check isn't cached.
+      assert!(!Path::new(&filename).exists());
+    });
+  }
+
+  #[test]
+  fn test_get_source_code_revalidate() {
+    // Relies on the etag_script.ts route of tools/http_server.py.
+    tokio_util::init(|| {
+      let (_temp_dir, mut deno_dir) = test_setup();
+      deno_dir.revalidate = true;
+      let module_name = "http://localhost:4545/tests/etag_script.ts";
+      let filename = deno_fs::normalize_path(
+        deno_dir
+          .deps_http
+          .join("localhost_PORT4545/tests/etag_script.ts")
+          .as_ref(),
+      );
+      let expected = b"console.log(\"etag_script.ts\");\n";
+
+      let r = get_source_code(&deno_dir, module_name, &filename, true, false)
+        .unwrap();
+      assert_eq!(r.source_code, &expected[..]);
+      let headers = get_source_code_headers(&filename);
+      assert_eq!(
+        headers.cache_headers.etag,
+        Some("\"33a64df551425fcc55e\"".to_owned())
+      );
+      assert_eq!(
+        headers.cache_headers.cache_control,
+        Some("max-age=3600".to_owned())
+      );
+
+      // Modules are only revalidated once per run.
+      assert!(!needs_revalidation(&filename));
+
+      // A 304 keeps the cached file, so changing it shows that it wasn't
+      // downloaded again.
+      fs::write(&filename, "// cached").unwrap();
+      let result = tokio_util::block_on(fetch_remote_source_async(
+        &deno_dir,
+        module_name,
+        &filename,
+        true,
+      ));
+      let r = result.unwrap().unwrap();
+      assert_eq!(r.source_code, b"// cached");
+      assert_eq!(&(r.media_type), &msg::MediaType::TypeScript);
+
+      // With a different ETag the module is downloaded again.
+      save_source_code_headers(
+        &filename,
+        None,
+        None,
+        Some(http_util::CacheHeaders {
+          etag: Some("\"other\"".to_owned()),
+          ..Default::default()
+        }),
+      );
+      let result = tokio_util::block_on(fetch_remote_source_async(
+        &deno_dir,
+        module_name,
+        &filename,
+        true,
+      ));
+      let r = result.unwrap().unwrap();
+      assert_eq!(r.source_code, &expected[..]);
+    });
+  }
+
   #[test]
   fn test_fetch_source_1() {
     use crate::tokio_util;
@@ -1438,21 +2217,21 @@ mod tests {
           .join("localhost_PORT4545/tests/subdir/mt_video_mp2t.t3.ts")
           .as_ref(),
       );
-      let headers_file_name = source_code_headers_filename(&filename);
 
       let result = fetch_remote_source(&deno_dir, module_name, &filename);
       assert!(result.is_ok());
       let r = result.unwrap().unwrap();
       assert_eq!(r.source_code, "export const loaded = true;\n".as_bytes());
       assert_eq!(&(r.media_type), &msg::MediaType::TypeScript);
-      // matching ext, no .headers.json file created
-      assert!(fs::read_to_string(&headers_file_name).is_err());
+      // matching ext, no mime type recorded
+      assert!(get_source_code_headers(&filename).mime_type.is_none());
 
       // Modify .headers.json, make sure read from local
       save_source_code_headers(
         &filename,
         Some("text/javascript".to_owned()),
         None,
+        None,
       );
       let result2 = fetch_local_source(&deno_dir, module_name, &filename, None);
       assert!(result2.is_ok());
@@ -1463,6 +2242,37 @@ mod tests {
     });
   }
 
+  #[test]
+  fn test_fetch_remote_source_downloaded_while_waiting() {
+    let (_temp_dir, deno_dir) = test_setup();
+    // Nothing listens on this port, so the module can't be downloaded.
+    let module_name = "http://localhost:4599/locked.ts";
+    let filename = deno_fs::normalize_path(
+      deno_dir
+        .deps_http
+        .join("localhost_PORT4599/locked.ts")
+        .as_ref(),
+    );
+    let lock_filename = download_lock_filename(&filename);
+    fs::create_dir_all(lock_filename.parent().unwrap()).unwrap();
+    let lock = deno_fs::FileLock::acquire(&lock_filename).unwrap();
+
+    let deno_dir_ = deno_dir.clone();
+    let filename_ = filename.clone();
+    let fetch = std::thread::spawn(move || {
+      fetch_remote_source(&deno_dir_, module_name, &filename_)
+    });
+    // Another process downloads the module while the fetch waits.
+    std::thread::sleep(Duration::from_millis(200));
+    let source = "export const locked = true;\n";
+    deno_fs::write_file_atomic(Path::new(&filename), source, 0o666).unwrap();
+    drop(lock);
+
+    let r = fetch.join().unwrap().unwrap().unwrap();
+    assert_eq!(r.source_code, source.as_bytes());
+    assert!(!lock_filename.exists());
+  }
+
   #[test]
   fn test_fetch_source_2() {
     use crate::tokio_util;
@@ -1590,6 +2400,178 @@ mod tests {
     })
   }
 
+  #[test]
+  fn test_fetch_module_meta_data_reload_whitelist() {
+    let (_temp_dir, mut deno_dir) = test_setup();
+    deno_dir.reload_whitelist =
+      vec!["http://localhost:4545/tests/subdir/".to_string()];
+    let cached = deno_dir.deps_http.join("localhost_PORT4545/tests");
+    fs::create_dir_all(cached.join("subdir")).unwrap();
+    fs::write(cached.join("subdir/mod2.ts"), "// stale").unwrap();
+    fs::write(cached.join("cached.ts"), "// cached").unwrap();
+
+    tokio_util::init(|| {
+      // Matches the prefix, so it is downloaded again...
+      let specifier = "http://localhost:4545/tests/subdir/mod2.ts";
+      let r = deno_dir.fetch_module_meta_data(specifier, ".", true, false);
+      assert_eq!(
+        r.unwrap().source_code,
+        "export { printHello } from \"./print_hello.ts\";\n".as_bytes()
+      );
+      // ...but only once per run.
+      fs::write(cached.join("subdir/mod2.ts"), "// stale").unwrap();
+      let r = deno_dir.fetch_module_meta_data(specifier, ".", true, false);
+      assert_eq!(r.unwrap().source_code, b"// stale");
+
+      // Other modules come from the cache.
+      let specifier = "http://localhost:4545/tests/cached.ts";
+      let r = deno_dir.fetch_module_meta_data(specifier, ".", true, false);
+      assert_eq!(r.unwrap().source_code, b"// cached");
+    })
+  }
+
+  #[test]
+  fn test_decode_data_url() {
+    let decode = |s: &str| decode_data_url(&Url::parse(s).unwrap());
+    assert_eq!(
+      decode("data:application/typescript;base64,ZXhwb3J0IHt9Ow==").unwrap(),
+      (
+        Some("application/typescript".to_string()),
+        b"export {};".to_vec()
+      )
+    );
+    assert_eq!(
+      decode("data:text/javascript,console.log(%22hi%22)").unwrap(),
+      (
+        Some("text/javascript".to_string()),
+        b"console.log(\"hi\")".to_vec()
+      )
+    );
+    assert_eq!(decode("data:,1").unwrap(), (None, b"1".to_vec()));
+    // Parameters of the media type are dropped.
+    assert_eq!(
+      decode("data:Text/JavaScript ;charset=utf-8;base64,MQ==").unwrap(),
+      (Some("text/javascript".to_string()), b"1".to_vec())
+    );
+    assert_eq!(
+      decode("data:application/typescript;charset=utf-8,1").unwrap(),
+      (Some("application/typescript".to_string()), b"1".to_vec())
+    );
+    assert_eq!(decode("data:;charset=utf-8,1").unwrap(), (None, b"1".to_vec()));
+    assert!(decode("data:text/javascript").is_err());
+    assert!(decode("data:text/javascript;base64,%%%").is_err());
+  }
+
+  #[test]
+  fn test_fetch_module_meta_data_data_url() {
+    let (_temp_dir, deno_dir) = test_setup();
+    let specifier = "data:application/typescript;base64,ZXhwb3J0IHt9Ow==";
+
+    let (module_name, filename) =
+      deno_dir.resolve_module(specifier, ".").unwrap();
+    assert_eq!(module_name, specifier);
+    assert!(Path::new(&filename).starts_with(&deno_dir.deps_data));
+
+    tokio_util::init(|| {
+      let r = deno_dir.fetch_module_meta_data(specifier, ".", true, false);
+      let r = r.unwrap();
+      assert_eq!(r.module_name, specifier);
+      assert_eq!(r.source_code, b"export {};");
+      assert_eq!(&(r.media_type), &msg::MediaType::TypeScript);
+
+      // The decoded source is cached, and can be read without fetching.
+      assert_eq!(fs::read(&filename).unwrap(), b"export {};");
+      let r = deno_dir.fetch_module_meta_data(specifier, ".", true, true);
+      assert_eq!(&(r.unwrap().media_type), &msg::MediaType::TypeScript);
+    });
+
+    // Data modules can only import absolute URLs.
+    assert!(deno_dir.resolve_module("./foo.ts", specifier).is_err());
+    let (module_name, _) = deno_dir
+      .resolve_module("http://localhost:4545/tests/subdir/mod2.ts", &filename)
+      .unwrap();
+    assert_eq!(module_name, "http://localhost:4545/tests/subdir/mod2.ts");
+
+    // The charset doesn't change the media type.
+    let specifier = "data:application/typescript;charset=utf-8,export%20{};";
+    let (_, filename) = deno_dir.resolve_module(specifier, ".").unwrap();
+    let r = fetch_data_url_source(specifier, &filename).unwrap();
+    assert_eq!(&(r.media_type), &msg::MediaType::TypeScript);
+  }
+
+  #[test]
+  fn test_fetch_module_meta_data_scheme_handler() {
+    use crate::scheme_handlers::LoadFuture;
+    use crate::scheme_handlers::LoadedSource;
+    use std::collections::HashMap;
+
+    /// A file system which only exists in memory.
+    struct MemoryHandler(HashMap<&'static str, &'static str>);
+
+    impl SchemeHandler for MemoryHandler {
+      fn resolve(
+        &self,
+        specifier: &str,
+        referrer: &str,
+      ) -> Result<Url, url::ParseError> {
+        Url::parse(referrer)
+          .and_then(|base| base.join(specifier))
+          .or_else(|_| Url::parse(specifier))
+      }
+
+      fn load(&self, url: &Url) -> Box<LoadFuture> {
+        Box::new(futures::future::result(match self.0.get(url.path()) {
+          Some(source) => Ok(LoadedSource {
+            source_code: source.as_bytes().to_vec(),
+            mime_type: None,
+          }),
+          None => Err(errors::new(ErrorKind::NotFound, url.to_string())),
+        }))
+      }
+    }
+
+    let mut files = HashMap::new();
+    files.insert("/main.ts", "import { a } from \"./a.js\";");
+    files.insert("/a.js", "export const a = 1;");
+    scheme_handlers::register("memory-test", Arc::new(MemoryHandler(files)));
+
+    let (_temp_dir, deno_dir) = test_setup();
+    let (module_name, filename) =
+      deno_dir.resolve_module("memory-test:/main.ts", ".").unwrap();
+    assert_eq!(module_name, "memory-test:/main.ts");
+    assert!(
+      Path::new(&filename).starts_with(deno_dir.deps.join("memory-test"))
+    );
+    let (module_name, _) =
+      deno_dir.resolve_module("./a.js", "memory-test:/main.ts").unwrap();
+    assert_eq!(module_name, "memory-test:/a.js");
+
+    let fetch = |specifier, referrer, no_fetch| {
+      deno_dir.fetch_module_meta_data(specifier, referrer, true, no_fetch)
+    };
+    tokio_util::init(|| {
+      let r = fetch("./a.js", "memory-test:/main.ts", false).unwrap();
+      assert_eq!(r.module_name, "memory-test:/a.js");
+      assert_eq!(r.source_code, b"export const a = 1;");
+      assert_eq!(&(r.media_type), &msg::MediaType::JavaScript);
+
+      // The cached copy is used when fetching is disabled.
+      let r = fetch("memory-test:/a.js", ".", true).unwrap();
+      assert_eq!(&(r.media_type), &msg::MediaType::JavaScript);
+
+      let err = fetch("memory-test:/b.js", ".", false).unwrap_err();
+      assert_eq!(err.kind(), ErrorKind::NotFound);
+    });
+  }
+
+  #[test]
+  fn test_resolve_module_no_scheme_handler() {
+    let (_temp_dir, deno_dir) = test_setup();
+    let err = deno_dir.resolve_module("unknown-test:/a.ts", ".").unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::InvalidUri);
+    assert!(err.to_string().contains("\"unknown-test\" scheme"));
+  }
+
   #[test]
   fn test_src_file_to_url_1() {
     let (_temp_dir, deno_dir) = test_setup();
@@ -1996,4 +2978,137 @@ mod tests {
       .to_owned();
     assert_eq!(filter_shebang(code), "\nconsole.log('hello');\n".as_bytes());
   }
+
+  #[test]
+  fn test_cached_modules_and_clean() {
+    let (_temp_dir, deno_dir) = test_setup();
+    let (_, a) = deno_dir
+      .resolve_module("http://localhost:4545/tests/a.ts", ".")
+      .unwrap();
+    let (_, b) = deno_dir
+      .resolve_module("https://deno.land/x/b", ".")
+      .unwrap();
+    fs::create_dir_all(Path::new(&a).parent().unwrap()).unwrap();
+    fs::create_dir_all(Path::new(&b).parent().unwrap()).unwrap();
+    fs::write(&a, "export {};").unwrap();
+    fs::write(&b, "export const b = 1;").unwrap();
+    save_source_code_headers(
+      &b,
+      Some("application/typescript".into()),
+      None,
+      None,
+    );
+
+    let modules = deno_dir.cached_modules().unwrap();
+    assert_eq!(modules.len(), 2);
+    assert_eq!(modules[0].url, "http://localhost:4545/tests/a.ts");
+    assert_eq!(modules[0].size, 10);
+    assert_eq!(modules[0].mime_type, None);
+    assert_eq!(modules[1].url, "https://deno.land/x/b");
+    assert_eq!(
+      modules[1].mime_type,
+      Some("application/typescript".to_string())
+    );
+
+    // Nothing was downloaded a day ago.
+    let day = Duration::from_secs(24 * 60 * 60);
+    assert!(deno_dir.clean(Some(day), None).unwrap().is_empty());
+
+    // The download time in .headers.json takes precedence over the
+    // modification time, and outlives other changes of the headers.
+    fs::write(
+      source_code_headers_filename(&a),
+      "{ \"fetched_at\": 784111777 }",
+    ).unwrap();
+    save_source_code_headers(&a, None, None, None);
+    assert_eq!(get_source_code_headers(&a).fetched_at, Some(784_111_777));
+    let removed = deno_dir.clean(Some(day), None).unwrap();
+    assert_eq!(removed, vec!["http://localhost:4545/tests/a.ts".to_string()]);
+    fs::create_dir_all(Path::new(&a).parent().unwrap()).unwrap();
+    fs::write(&a, "export {};").unwrap();
+
+    let removed = deno_dir.clean(None, Some("https://deno.land/")).unwrap();
+    assert_eq!(removed, vec!["https://deno.land/x/b".to_string()]);
+    assert!(!Path::new(&b).exists());
+    assert!(!Path::new(&source_code_headers_filename(&b)).exists());
+    assert!(!deno_dir.deps_https.join("deno.land").exists());
+    assert!(deno_dir.deps_https.exists());
+    assert_eq!(deno_dir.cached_modules().unwrap().len(), 1);
+
+    let removed = deno_dir.clean(None, None).unwrap();
+    assert_eq!(removed, vec!["http://localhost:4545/tests/a.ts".to_string()]);
+    assert!(deno_dir.cached_modules().unwrap().is_empty());
+  }
+
+  #[test]
+  fn test_gc() {
+    let (_temp_dir, deno_dir) = test_setup();
+    let (_, filename) = deno_dir
+      .resolve_module("http://localhost:4545/tests/a.ts", ".")
+      .unwrap();
+    fs::create_dir_all(Path::new(&filename).parent().unwrap()).unwrap();
+    fs::write(&filename, "#!/usr/bin/env deno\nexport {};").unwrap();
+
+    let cached = ModuleMetaData {
+      filename: filename.clone(),
+      source_code: b"\nexport {};".to_vec(),
+      module_name: "http://localhost:4545/tests/a.ts".to_owned(),
+      module_redirect_source_name: None,
+      media_type: msg::MediaType::TypeScript,
+      maybe_output_code: Some(b"export {};".to_vec()),
+      maybe_output_code_filename: None,
+      maybe_source_map: Some(b"{}".to_vec()),
+      maybe_source_map_filename: None,
+    };
+    let stale = ModuleMetaData {
+      filename: "/stale.ts".to_owned(),
+      module_name: "file:///stale.ts".to_owned(),
+      ..cached.clone()
+    };
+    deno_dir.code_cache(&cached).unwrap();
+    deno_dir.code_cache(&stale).unwrap();
+    let (cached_js, cached_map) =
+      deno_dir.cache_path(&cached.filename, &cached.source_code);
+    let (stale_js, stale_map) =
+      deno_dir.cache_path(&stale.filename, &stale.source_code);
+    assert!(stale_js.exists() && stale_map.exists());
+
+    // A local module, compiled with two configurations.
+    let local_filename = deno_dir.root.join("local.ts");
+    fs::write(&local_filename, "export {};").unwrap();
+    let local = ModuleMetaData {
+      filename: deno_fs::normalize_path(&local_filename),
+      source_code: b"export {};".to_vec(),
+      module_name: "file:///local.ts".to_owned(),
+      ..cached.clone()
+    };
+    let other_config_dir = DenoDir::new(
+      Some(deno_dir.root.clone()),
+      &Some(b"{ \"compilerOptions\": {} }".to_vec()),
+      None,
+      vec![],
+      false,
+      Progress::new(),
+    ).unwrap();
+    deno_dir.code_cache(&local).unwrap();
+    other_config_dir.code_cache(&local).unwrap();
+    let (local_js, _) =
+      deno_dir.cache_path(&local.filename, &local.source_code);
+    let (other_config_js, _) =
+      other_config_dir.cache_path(&local.filename, &local.source_code);
+    assert_ne!(local_js, other_config_js);
+
+    assert_eq!(deno_dir.gc().unwrap(), 2);
+    assert!(cached_js.exists() && cached_map.exists());
+    assert!(!stale_js.exists() && !stale_map.exists());
+    assert!(!cache_meta_path(&stale_js).exists());
+    assert!(local_js.exists() && other_config_js.exists());
+    assert_eq!(deno_dir.gc().unwrap(), 0);
+
+    // Both configurations lose their code once the source changes.
+    fs::write(&local_filename, "export const a = 1;").unwrap();
+    assert_eq!(deno_dir.gc().unwrap(), 4);
+    assert!(!local_js.exists() && !other_config_js.exists());
+    assert!(cached_js.exists());
+  }
 }
@@ -16,7 +16,8 @@ pub trait DisplayFormatter {
   fn format_source_name(&self, level: usize) -> String;
 }
 
-#[derive(Debug, PartialEq, Clone)]
+#[derive(Debug, PartialEq, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
 pub struct Diagnostic {
   pub items: Vec<DiagnosticItem>,
 }
@@ -73,7 +74,8 @@ impl fmt::Display for Diagnostic {
   }
 }
 
-#[derive(Debug, PartialEq, Clone)]
+#[derive(Debug, PartialEq, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
 pub struct DiagnosticItem {
   /// The top level message relating to the diagnostic item.
   pub message: String,
@@ -328,7 +330,8 @@ impl fmt::Display for DiagnosticItem {
   }
 }
 
-#[derive(Debug, PartialEq, Clone)]
+#[derive(Debug, PartialEq, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
 pub struct DiagnosticMessageChain {
   pub message: String,
   pub code: i64,
@@ -367,7 +370,8 @@ impl DiagnosticMessageChain {
   }
 }
 
-#[derive(Debug, PartialEq, Clone)]
+#[derive(Debug, PartialEq, Clone, Serialize)]
+#[serde(rename_all = "lowercase")]
 pub enum DiagnosticCategory {
   Log,        // 0
   Debug,      // 1
@@ -665,4 +669,53 @@ mod tests {
     let expected = "deno/tests/complex_diagnostics.ts:19:3 - error TS2322: Example 1\n\n19   values: o => [\n     ~~~~~~\n\n/foo/bar.ts:129:3 - error TS2000: Example 2\n\n129   values: undefined,\n      ~~~~~~\n\n\nFound 2 errors.\n";
     assert_eq!(expected, strip_ansi_codes(&d.to_string()));
   }
+
+  #[test]
+  fn diagnostic_to_json() {
+    let d = Diagnostic {
+      items: vec![DiagnosticItem {
+        message: "Example".to_string(),
+        message_chain: Some(Box::new(DiagnosticMessageChain {
+          message: "Example chain".to_string(),
+          code: 2322,
+          category: DiagnosticCategory::Error,
+          next: None,
+        })),
+        related_information: None,
+        source_line: Some("  values: undefined,".to_string()),
+        line_number: Some(128),
+        script_resource_name: Some("/foo/bar.ts".to_string()),
+        start_position: Some(1500),
+        end_position: Some(1508),
+        category: DiagnosticCategory::Warning,
+        code: 2322,
+        start_column: Some(2),
+        end_column: Some(10),
+      }],
+    };
+    let expected = json!({
+      "items": [
+        {
+          "message": "Example",
+          "messageChain": {
+            "message": "Example chain",
+            "code": 2322,
+            "category": "error",
+            "next": null
+          },
+          "relatedInformation": null,
+          "sourceLine": "  values: undefined,",
+          "lineNumber": 128,
+          "scriptResourceName": "/foo/bar.ts",
+          "startPosition": 1500,
+          "endPosition": 1508,
+          "category": "warning",
+          "code": 2322,
+          "startColumn": 2,
+          "endColumn": 10
+        }
+      ]
+    });
+    assert_eq!(serde_json::to_value(&d).unwrap(), expected);
+  }
 }
@@ -0,0 +1,185 @@
+// Copyright 2018-2019 the Deno authors. All rights reserved. MIT license.
+//! Watches files for `--watch` by polling their modification time and size,
+//! which works the same on every platform and file system.
+use crate::tokio_util::panic_on_error;
+use deno::Modules;
+use futures::Future;
+use futures::Stream;
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::PathBuf;
+use std::time::Duration;
+use std::time::Instant;
+use std::time::SystemTime;
+use tokio::timer::Interval;
+use url::Url;
+
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Modification time and size of a file, or `None` if it doesn't exist.
+type Stamp = Option<(SystemTime, u64)>;
+
+fn stamp(path: &PathBuf) -> Stamp {
+  let metadata = fs::metadata(path).ok()?;
+  Some((metadata.modified().ok()?, metadata.len()))
+}
+
+pub struct FileWatcher {
+  /// When the program started loading the files.
+  since: SystemTime,
+  files: Vec<(PathBuf, Stamp)>,
+  /// Files which were already modified or removed when they were added.
+  changed_when_added: Vec<PathBuf>,
+}
+
+impl FileWatcher {
+  /// Watches `paths`, which were loaded after `since`.
+  pub fn new(since: SystemTime, paths: Vec<PathBuf>) -> Self {
+    let mut watcher = Self {
+      since,
+      files: Vec::new(),
+      changed_when_added: Vec::new(),
+    };
+    watcher.add(paths);
+    watcher
+  }
+
+  /// Starts watching those of `paths` which aren't watched yet. They may have
+  /// been loaded a while ago, so a file which was modified after `since` or
+  /// doesn't exist anymore counts as changed.
+  pub fn add(&mut self, paths: Vec<PathBuf>) {
+    for path in paths {
+      if !self.files.iter().any(|(watched, _)| *watched == path) {
+        let stamp = stamp(&path);
+        match stamp {
+          Some((modified, _)) if modified < self.since => {}
+          _ => self.changed_when_added.push(path.clone()),
+        }
+        self.files.push((path, stamp));
+      }
+    }
+  }
+
+  /// Returns the files which changed, were created or were removed since
+  /// the last call.
+  pub fn changed(&mut self) -> Vec<PathBuf> {
+    let mut changed: Vec<PathBuf> = self.changed_when_added.drain(..).collect();
+    for (path, old_stamp) in self.files.iter_mut() {
+      let new_stamp = stamp(path);
+      if new_stamp != *old_stamp {
+        *old_stamp = new_stamp;
+        if !changed.contains(path) {
+          changed.push(path.clone());
+        }
+      }
+    }
+    changed
+  }
+}
+
+/// Resolves with the changed files once any of the files returned by
+/// `list_files` changes. It is called again before each check, as the
+/// program may still be loading modules. `since` is when the program started
+/// loading them.
+pub fn watch<F>(
+  since: SystemTime,
+  mut list_files: F,
+) -> impl Future<Item = Vec<PathBuf>, Error = ()>
+where
+  F: FnMut() -> Vec<PathBuf>,
+{
+  let mut watcher = FileWatcher::new(since, list_files());
+  panic_on_error(
+    Interval::new(Instant::now() + POLL_INTERVAL, POLL_INTERVAL)
+      .map(move |_| {
+        let changed = watcher.changed();
+        watcher.add(list_files());
+        changed
+      }).filter(|changed| !changed.is_empty())
+      .into_future()
+      .map(|(changed, _)| changed.unwrap())
+      .map_err(|(err, _)| err),
+  )
+}
+
+/// Returns the local files of the module graph of `main_module`.
+pub fn module_graph_files(
+  modules: &Modules,
+  main_module: &str,
+) -> Vec<PathBuf> {
+  let mut module_names = Vec::new();
+  let mut pending = match modules.deps(main_module) {
+    Some(deps) => vec![deps],
+    None => vec![],
+  };
+  while let Some(deps) = pending.pop() {
+    module_names.push(deps.name);
+    pending.extend(deps.deps.unwrap_or_default());
+  }
+  local_files(&module_names)
+}
+
+/// Returns the local files of the modules `module_names`.
+pub fn local_files(module_names: &[String]) -> Vec<PathBuf> {
+  let mut files = BTreeSet::new();
+  for module_name in module_names {
+    if let Ok(url) = Url::parse(module_name) {
+      if url.scheme() == "file" {
+        if let Ok(path) = url.to_file_path() {
+          files.insert(path);
+        }
+      }
+    }
+  }
+  files.into_iter().collect()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use tempfile::TempDir;
+
+  #[test]
+  fn test_file_watcher() {
+    let dir = TempDir::new().expect("tempdir fail");
+    let a = dir.path().join("a.ts");
+    let b = dir.path().join("b.ts");
+    fs::write(&a, "export const a = 1;").unwrap();
+    let since =
+      fs::metadata(&a).unwrap().modified().unwrap() + Duration::from_nanos(1);
+    let mut watcher = FileWatcher::new(since, vec![a.clone()]);
+    assert!(watcher.changed().is_empty());
+
+    // The size changes, so this is noticed even if the modification time
+    // stays the same.
+    fs::write(&a, "export const a = 12;").unwrap();
+    assert_eq!(watcher.changed(), vec![a.clone()]);
+    assert!(watcher.changed().is_empty());
+
+    // A file which doesn't exist when it is added was removed after it was
+    // loaded.
+    watcher.add(vec![b.clone()]);
+    assert_eq!(watcher.changed(), vec![b.clone()]);
+    fs::write(&b, "").unwrap();
+    assert_eq!(watcher.changed(), vec![b.clone()]);
+    fs::remove_file(&a).unwrap();
+    assert_eq!(watcher.changed(), vec![a.clone()]);
+    assert!(watcher.changed().is_empty());
+  }
+
+  #[test]
+  fn test_file_watcher_modified_before_added() {
+    let dir = TempDir::new().expect("tempdir fail");
+    let c = dir.path().join("c.ts");
+    fs::write(&c, "export const c = 3;").unwrap();
+    // The program loaded c.ts, which was modified again before it was
+    // watched.
+    let since = fs::metadata(&c).unwrap().modified().unwrap();
+    let mut watcher = FileWatcher::new(since, vec![]);
+    watcher.add(vec![c.clone()]);
+    assert_eq!(watcher.changed(), vec![c.clone()]);
+    assert!(watcher.changed().is_empty());
+    fs::write(&c, "export const c = 34;").unwrap();
+    assert_eq!(watcher.changed(), vec![c]);
+  }
+}
@@ -1,6 +1,7 @@
 // Copyright 2018-2019 the Deno authors. All rights reserved. MIT license.
 use clap::{App, AppSettings, Arg, ArgMatches, SubCommand};
 use crate::deno_dir;
+use std::time::Duration;
 
 // Creates vector of strings, Vec<String>
 macro_rules! svec {
@@ -12,12 +13,29 @@ pub struct DenoFlags {
   pub log_debug: bool,
   pub version: bool,
   pub reload: bool,
+  /// When set (`--reload=revalidate`) cached remote modules are revalidated
+  /// with conditional requests instead of being downloaded again.
+  pub revalidate: bool,
+  /// URL prefixes given with `--reload=<prefixes>`. Only matching remote
+  /// modules are downloaded and compiled again.
+  pub reload_whitelist: Vec<String>,
   /// When the `--config`/`-c` flag is used to pass the name, this will be set
   /// the path passed on the command line, otherwise `None`.
   pub config_path: Option<String>,
   /// When the `--importmap` flag is used to pass the name, this will be set
   /// the path passed on the command line, otherwise `None`.
   pub import_map_path: Option<String>,
+  /// PEM file with additional CA certificates to trust for HTTPS, passed
+  /// with `--cert`.
+  pub ca_file: Option<String>,
+  /// PEM files of the client certificate and private key presented to HTTPS
+  /// servers which request client authentication.
+  pub client_cert_file: Option<String>,
+  pub client_key_file: Option<String>,
+  /// Lock file with hashes of remote modules, passed with `--lock`.
+  pub lock: Option<String>,
+  /// When set (`--lock-write`) the lock file is updated instead of checked.
+  pub lock_write: bool,
   pub allow_read: bool,
   pub read_whitelist: Vec<String>,
   pub allow_write: bool,
@@ -29,15 +47,72 @@ pub struct DenoFlags {
   pub allow_hrtime: bool,
   pub no_prompts: bool,
   pub no_fetch: bool,
+  /// When set (`--no-check`) TypeScript modules are transpiled one by one
+  /// without type-checking.
+  pub no_check: bool,
+  /// When set (`deno check --format=json`) diagnostics are printed as JSON.
+  pub json_diagnostics: bool,
+  /// Directory to write a bundle for each entry point to, passed to
+  /// `deno bundle` with `--out-dir`.
+  pub bundle_out_dir: Option<String>,
+  /// When set (`deno bundle --minify`) comments and whitespace are removed
+  /// from bundles.
+  pub bundle_minify: bool,
+  /// How `deno bundle` writes source maps: "inline", "external" or "none".
+  pub bundle_source_map: Option<String>,
+  /// ES version of bundles, passed with `--target`.
+  pub bundle_target: Option<String>,
+  /// When set (`deno snapshot`) bundles only run the dependencies of their
+  /// entry point, which runs once the program starts from the snapshot.
+  pub bundle_defer_main: bool,
+  /// File written by `deno compile` or `deno snapshot`, or the report of
+  /// `deno test`, passed with `--output`.
+  pub output: Option<String>,
+  /// Snapshot to start from, passed to `deno run` with `--snapshot`.
+  pub snapshot_path: Option<String>,
+  /// When set (`--watch`) `deno run`, `deno bundle` and `deno test` run again
+  /// whenever a local module, the config file or the import map changes.
+  pub watch: bool,
+  /// `deno test` only runs the tests whose name contains this, or matches it
+  /// if it is a regex wrapped in slashes.
+  pub test_filter: Option<String>,
+  /// When set (`deno test --fail-fast`) the run stops at the first failure.
+  pub test_fail_fast: bool,
+  /// Format of the results of `deno test`: "pretty", "tap" or "junit".
+  pub test_reporter: Option<String>,
+  /// Directory which `deno run` and `deno test` write the code coverage of
+  /// local modules to, passed with `--coverage`.
+  pub coverage_dir: Option<String>,
+  /// Cookies stored by `fetch()` requests made with
+  /// `credentials: "include"` are kept in `$DENO_DIR/cookies.json` between
+  /// runs.
+  pub persist_cookies: bool,
   pub v8_flags: Option<Vec<String>>,
   pub xeval_replvar: Option<String>,
   pub xeval_delim: Option<String>,
+  /// `deno cache clean --older-than` only removes modules downloaded at
+  /// least this long ago.
+  pub cache_older_than: Option<Duration>,
 }
 
 static ENV_VARIABLES_HELP: &str = "ENVIRONMENT VARIABLES:
     DENO_DIR        Set deno's base directory
+    DENO_CERT       Load certificate authority from PEM encoded file
+    DENO_AUTH_TOKENS
+                    Credentials for private module servers
+    DENO_SCHEME_HANDLERS
+                    Commands which load modules of other URL schemes
     NO_COLOR        Set to disable color";
 
+fn coverage_arg<'a, 'b>() -> Arg<'a, 'b> {
+  Arg::with_name("coverage")
+    .long("coverage")
+    .value_name("DIR")
+    .help("Write the code coverage of local modules to DIR/lcov.info")
+    .takes_value(true)
+    .require_equals(true)
+}
+
 fn add_run_args<'a, 'b>(app: App<'a, 'b>) -> App<'a, 'b> {
   app
     .arg(
@@ -85,6 +160,24 @@ fn add_run_args<'a, 'b>(app: App<'a, 'b>) -> App<'a, 'b> {
       Arg::with_name("no-prompt")
         .long("no-prompt")
         .help("Do not use prompts"),
+    ).arg(
+      Arg::with_name("no-check")
+        .long("no-check")
+        .help("Skip type-checking of TypeScript modules")
+        .long_help(
+          "Skip type-checking of TypeScript modules. Each module is
+transpiled to JavaScript on its own, which is much faster. Type errors
+are not reported, so type-check separately with 'deno check'.",
+        ),
+    ).arg(
+      Arg::with_name("persist-cookies")
+        .long("persist-cookies")
+        .help("Persist fetch() cookies in DENO_DIR")
+        .long_help(
+          "Persist cookies received by fetch() requests made with
+credentials: \"include\" in $DENO_DIR/cookies.json, so later runs
+send them again. Session cookies are never persisted.",
+        ),
     ).arg(
       Arg::with_name("importmap")
         .long("importmap")
@@ -135,8 +228,21 @@ To get help on the another subcommands (run in this case):
       Arg::with_name("reload")
         .short("r")
         .long("reload")
+        .min_values(0)
+        .takes_value(true)
+        .use_delimiter(true)
+        .require_equals(true)
         .help("Reload source code cache (recompile TypeScript)")
-        .global(true),
+        .long_help(
+          "Reload source code cache (recompile TypeScript).
+
+--reload downloads every remote module again.
+--reload=https://deno.land/std,https://example.com/ only reloads
+remote modules whose URL starts with one of the given prefixes.
+--reload=revalidate instead asks the server whether cached modules
+changed, using their ETag and Last-Modified headers. Unchanged modules
+are not downloaded.",
+        ).global(true),
     ).arg(
       Arg::with_name("config")
         .short("c")
@@ -145,6 +251,46 @@ To get help on the another subcommands (run in this case):
         .help("Load compiler configuration file")
         .takes_value(true)
         .global(true),
+    ).arg(
+      Arg::with_name("cert")
+        .long("cert")
+        .value_name("FILE")
+        .help("Load certificate authority from PEM encoded file")
+        .takes_value(true)
+        .global(true),
+    ).arg(
+      Arg::with_name("client-cert")
+        .long("client-cert")
+        .value_name("FILE")
+        .help("Load TLS client certificate from PEM encoded file")
+        .takes_value(true)
+        .requires("client-key")
+        .global(true),
+    ).arg(
+      Arg::with_name("client-key")
+        .long("client-key")
+        .value_name("FILE")
+        .help("Load TLS client private key from PEM encoded file")
+        .takes_value(true)
+        .requires("client-cert")
+        .global(true),
+    ).arg(
+      Arg::with_name("lock")
+        .long("lock")
+        .value_name("FILE")
+        .help("Check the specified lock file")
+        .long_help(
+          "Check remote modules against the hashes in the specified lock
+file. Loading a module whose hash is missing or different is an error.",
+        ).takes_value(true)
+        .require_equals(true)
+        .global(true),
+    ).arg(
+      Arg::with_name("lock-write")
+        .long("lock-write")
+        .requires("lock")
+        .help("Write lock file. Use with --lock.")
+        .global(true),
     ).arg(
       Arg::with_name("v8-options")
         .long("v8-options")
@@ -173,10 +319,187 @@ compiler.",
         .about("Bundle module and dependnecies into single file")
         .long_about(
           "Fetch, compile, and output to a single file a module and its dependencies.
+
+  deno bundle main.ts bundle.js
+
+With --out-dir, several entry points are bundled at once. Each is written
+to a file named after it, and the modules which several of them import
+to shared.js, which is loaded together with them:
+
+  deno bundle --out-dir=dist --minify --target=es2017 main.ts worker.ts
+
+With --watch, the bundles are written again whenever a local module changes.
 "
-        )
-          .arg(Arg::with_name("source_file").takes_value(true).required(true))
-          .arg(Arg::with_name("out_file").takes_value(true).required(true)),
+        ).arg(
+          Arg::with_name("watch")
+            .long("watch")
+            .help("Bundle again when local modules change"),
+        ).arg(
+          Arg::with_name("out-dir")
+            .long("out-dir")
+            .value_name("DIR")
+            .help("Bundle each entry point to a file in DIR")
+            .takes_value(true)
+            .require_equals(true),
+        ).arg(
+          Arg::with_name("minify")
+            .long("minify")
+            .help("Remove comments and whitespace"),
+        ).arg(
+          Arg::with_name("source-map")
+            .long("source-map")
+            .value_name("KIND")
+            .help("Source maps: inline (default), external or none")
+            .takes_value(true)
+            .require_equals(true)
+            .possible_values(&["inline", "external", "none"]),
+        ).arg(
+          Arg::with_name("target")
+            .long("target")
+            .value_name("VERSION")
+            .help("ES version of the bundles, e.g. es5, es2017 or esnext")
+            .takes_value(true)
+            .require_equals(true),
+        ).arg(
+          Arg::with_name("files")
+            .value_name("FILES")
+            .help("Entry point and out file, or entry points with --out-dir")
+            .takes_value(true)
+            .multiple(true)
+            .required(true),
+        ),
+    ).subcommand(
+      SubCommand::with_name("check")
+        .setting(AppSettings::DisableVersion)
+        .about("Type-check modules without running them")
+        .long_about(
+          "Type-check modules and their dependencies without running them.
+Exits with status 1 if there are any diagnostics.
+
+  deno check main.ts worker.ts
+
+  # Print the diagnostics as JSON to stdout, for editors and CI
+  deno check --format=json main.ts",
+        ).arg(
+          Arg::with_name("format")
+            .long("format")
+            .value_name("FORMAT")
+            .help("Output format of the diagnostics")
+            .takes_value(true)
+            .require_equals(true)
+            .possible_values(&["pretty", "json"])
+            .default_value("pretty"),
+        ).arg(
+          Arg::with_name("files")
+            .takes_value(true)
+            .multiple(true)
+            .required(true),
+        ),
+    ).subcommand(
+      add_run_args(SubCommand::with_name("test"))
+        .setting(AppSettings::DisableVersion)
+        .about("Run tests")
+        .long_about(
+          "Run the tests registered with Deno.test() by the test modules.
+
+Directories are searched for files ending with _test.ts, the current one if
+no files are given. Each module runs in a worker of its own, with the
+permissions given here.
+
+  deno test
+  deno test --allow-read src/ 'lib/**/*_test.ts'
+
+  # Only run the tests whose name contains 'parse'
+  deno test --filter=parse
+
+  # Write a JUnit report for CI
+  deno test --reporter=junit -o report.xml
+
+  # Write the code coverage of the modules the tests load to cov/lcov.info
+  deno test --coverage=cov
+
+  # Run the tests again whenever a local module changes
+  deno test --watch",
+        ).arg(coverage_arg())
+        .arg(
+          Arg::with_name("watch")
+            .long("watch")
+            .help("Run the tests again when local modules change"),
+        ).arg(
+          Arg::with_name("filter")
+            .long("filter")
+            .value_name("PATTERN")
+            .help("Only run the tests whose name contains PATTERN")
+            .takes_value(true)
+            .require_equals(true),
+        ).arg(
+          Arg::with_name("fail-fast")
+            .long("fail-fast")
+            .help("Stop at the first failure"),
+        ).arg(
+          Arg::with_name("reporter")
+            .long("reporter")
+            .value_name("KIND")
+            .help("Report the results as text (pretty), TAP or JUnit XML")
+            .takes_value(true)
+            .require_equals(true)
+            .possible_values(&["pretty", "tap", "junit"]),
+        ).arg(
+          Arg::with_name("output")
+            .short("o")
+            .long("output")
+            .value_name("FILE")
+            .help("Write the TAP or JUnit report to FILE")
+            .takes_value(true),
+        ).arg(
+          Arg::with_name("files")
+            .value_name("FILES")
+            .help("Test modules, directories or globs")
+            .takes_value(true)
+            .multiple(true),
+        ),
+    ).subcommand(
+      add_run_args(SubCommand::with_name("compile"))
+        .setting(AppSettings::DisableVersion)
+        .about("Compile a program into a single executable")
+        .long_about(
+          "Bundle a program and write it together with the deno runtime to a
+single executable, which runs it with the permissions given here.
+
+  deno compile --allow-net main.ts -o mytool",
+        ).arg(
+          Arg::with_name("output")
+            .short("o")
+            .long("output")
+            .value_name("FILE")
+            .help("Executable to write")
+            .takes_value(true)
+            .required(true),
+        ).arg(Arg::with_name("file").takes_value(true).required(true)),
+    ).subcommand(
+      add_run_args(SubCommand::with_name("snapshot"))
+        .setting(AppSettings::DisableVersion)
+        .about("Snapshot a program for faster startup")
+        .long_about(
+          "Run the top-level code of the dependencies of a program and write
+a V8 snapshot of the result, which 'deno run --snapshot' starts from.
+The program's own module runs when it is started.
+
+While the snapshot is made, the dependencies run with the given permissions
+and can use synchronous APIs like Deno.readFileSync(). Asynchronous ones,
+like timers or fetch(), don't complete before the snapshot is written.
+
+  deno snapshot main.ts -o app.snap
+  deno run --snapshot=app.snap arg1 arg2",
+        ).arg(
+          Arg::with_name("output")
+            .short("o")
+            .long("output")
+            .value_name("FILE")
+            .help("Snapshot file to write")
+            .takes_value(true)
+            .required(true),
+        ).arg(Arg::with_name("file").takes_value(true).required(true)),
     ).subcommand(
       SubCommand::with_name("fetch")
         .setting(AppSettings::DisableVersion)
@@ -194,6 +517,25 @@ would be made unless --reload is specified.
   # Once cached, static imports no longer send network requests
   deno run -A https://deno.land/std/http/file_server.ts",
         ).arg(Arg::with_name("file").takes_value(true).required(true)),
+    ).subcommand(
+      SubCommand::with_name("vendor")
+        .setting(AppSettings::DisableVersion)
+        .about("Copy remote dependencies into a directory")
+        .long_about(
+          "Copy remote dependencies into a directory, ./vendor by default.
+
+Fetches all statically imported modules like 'deno fetch', then copies
+every remote module into the output directory and writes an import map
+to import_map.json in it which points their URLs at the copies. The
+program then runs without network access:
+
+  deno vendor main.ts
+  deno run --importmap=vendor/import_map.json main.ts
+
+  deno vendor main.ts third_party
+  deno run --importmap=third_party/import_map.json main.ts",
+        ).arg(Arg::with_name("file").takes_value(true).required(true))
+        .arg(Arg::with_name("out_dir").takes_value(true).required(false)),
     ).subcommand(
       SubCommand::with_name("types")
         .setting(AppSettings::DisableVersion)
@@ -258,7 +600,6 @@ Automatically downloads Prettier dependencies on first run.
           AppSettings::AllowExternalSubcommands,
           AppSettings::DisableHelpSubcommand,
           AppSettings::DisableVersion,
-          AppSettings::SubcommandRequired,
         ]).about("Run a program given a filename or url to the source code")
         .long_about(
           "Run a program given a filename or url to the source code.
@@ -275,7 +616,28 @@ ability to spawn subprocesses.
   deno run --allow-net --allow-read=$(pwd) https://deno.land/std/http/file_server.ts
 
   # run program with all permissions
-  deno run -A https://deno.land/std/http/file_server.ts",
+  deno run -A https://deno.land/std/http/file_server.ts
+
+  # start from a snapshot written by 'deno snapshot'
+  deno run --snapshot=app.snap arg1 arg2
+
+  # restart the program whenever a local module changes
+  deno run --watch --allow-net server.ts
+
+  # write the code coverage of local modules to cov/lcov.info
+  deno run --coverage=cov main.ts",
+        ).arg(
+          Arg::with_name("watch")
+            .long("watch")
+            .help("Restart the program when local modules change"),
+        ).arg(coverage_arg().conflicts_with_all(&["watch", "snapshot"]))
+        .arg(
+          Arg::with_name("snapshot")
+            .long("snapshot")
+            .value_name("FILE")
+            .help("Start from a snapshot written by 'deno snapshot'")
+            .takes_value(true)
+            .require_equals(true),
         ).subcommand(
           // this is a fake subcommand - it's used in conjunction with
           // AppSettings:AllowExternalSubcommand to treat it as an
@@ -321,6 +683,97 @@ Demonstrates breaking the input up by space delimiter instead of by lines:
             .help("Set delimiter, defaults to newline")
             .takes_value(true),
         ).arg(Arg::with_name("code").takes_value(true).required(true)),
+    ).subcommand(
+      SubCommand::with_name("cache")
+        .setting(AppSettings::DisableVersion)
+        .setting(AppSettings::SubcommandRequiredElseHelp)
+        .about("Manage the cache in DENO_DIR")
+        .long_about(
+          "Manage the cache of remote modules and compiled code in DENO_DIR.
+
+  # List cached remote modules
+  deno cache ls
+
+  # Remove modules downloaded more than 30 days ago
+  deno cache clean --older-than 30d
+
+  # Remove all modules from deno.land
+  deno cache clean https://deno.land/
+
+  # Remove compiled code of modules which were removed or changed
+  deno cache gc",
+        ).subcommand(
+          SubCommand::with_name("ls")
+            .setting(AppSettings::DisableVersion)
+            .about("List cached remote modules"),
+        ).subcommand(
+          SubCommand::with_name("clean")
+            .setting(AppSettings::DisableVersion)
+            .about("Remove cached remote modules")
+            .arg(
+              Arg::with_name("older-than")
+                .long("older-than")
+                .value_name("AGE")
+                .help("Only remove modules downloaded at least AGE ago")
+                .long_help(
+                  "Only remove modules downloaded at least AGE ago. AGE is a
+number followed by one of s, m, h, d or w, e.g. 30d.",
+                ).takes_value(true)
+                .validator(|age| match parse_duration(&age) {
+                  Some(_) => Ok(()),
+                  None => Err(format!("Invalid age '{}'", age)),
+                }),
+            ).arg(
+              Arg::with_name("url-prefix")
+                .help("Only remove modules whose URL starts with this")
+                .takes_value(true),
+            ),
+        ).subcommand(
+          SubCommand::with_name("gc")
+            .setting(AppSettings::DisableVersion)
+            .about("Remove compiled code of removed or changed modules"),
+        ),
+    ).subcommand(
+      SubCommand::with_name("importmap")
+        .setting(AppSettings::DisableVersion)
+        .setting(AppSettings::SubcommandRequiredElseHelp)
+        .about("Check import maps and debug their resolution")
+        .long_about(
+          "Check import maps and debug their resolution.
+
+  # Report ignored entries, overlapping scopes and missing targets
+  deno importmap check import_map.json
+
+  # Show which scope and key a specifier is resolved with
+  deno importmap resolve moment --referrer ./src/main.ts \\
+    --importmap import_map.json",
+        ).subcommand(
+          SubCommand::with_name("check")
+            .setting(AppSettings::DisableVersion)
+            .about("Report problems with the entries of an import map")
+            .arg(Arg::with_name("file").takes_value(true).required(true)),
+        ).subcommand(
+          SubCommand::with_name("resolve")
+            .setting(AppSettings::DisableVersion)
+            .about("Resolve a specifier with an import map")
+            .arg(
+              Arg::with_name("importmap")
+                .long("importmap")
+                .value_name("FILE")
+                .help("Import map file")
+                .takes_value(true)
+                .required(true),
+            ).arg(
+              Arg::with_name("referrer")
+                .long("referrer")
+                .value_name("URL")
+                .help("Module which imports the specifier")
+                .long_help(
+                  "URL or path of the module which imports the specifier.
+Defaults to the current directory.",
+                ).takes_value(true),
+            ).arg(Arg::with_name("specifier").takes_value(true).required(true)),
+        ),
     ).subcommand(
       // this is a fake subcommand - it's used in conjunction with
       // AppSettings:AllowExternalSubcommand to treat it as an
@@ -329,6 +782,22 @@ Demonstrates breaking the input up by space delimiter instead of by lines:
     )
 }
 
+/// Parses durations like "90s", "15m", "12h", "30d" or "2w".
+fn parse_duration(s: &str) -> Option<Duration> {
+  let (unit_start, _) = s.char_indices().last()?;
+  let (number, unit) = s.split_at(unit_start);
+  let seconds_per_unit = match unit {
+    "s" => 1,
+    "m" => 60,
+    "h" => 60 * 60,
+    "d" => 24 * 60 * 60,
+    "w" => 7 * 24 * 60 * 60,
+    _ => return None,
+  };
+  let number: u64 = number.parse().ok()?;
+  number.checked_mul(seconds_per_unit).map(Duration::from_secs)
+}
+
 /// Convert paths supplied into full path.
 /// If a path is invalid, we print out a warning
 /// and ignore this path in the output.
@@ -362,9 +831,28 @@ pub fn parse_flags(matches: &ArgMatches) -> DenoFlags {
     flags.version = true;
   }
   if matches.is_present("reload") {
-    flags.reload = true;
+    match matches.values_of("reload") {
+      Some(values) => {
+        for value in values {
+          if value == "revalidate" {
+            flags.revalidate = true;
+          } else {
+            flags.reload_whitelist.push(value.to_string());
+          }
+        }
+      }
+      None => flags.reload = true,
+    }
   }
   flags.config_path = matches.value_of("config").map(ToOwned::to_owned);
+  flags.ca_file = matches.value_of("cert").map(ToOwned::to_owned);
+  flags.client_cert_file =
+    matches.value_of("client-cert").map(ToOwned::to_owned);
+  flags.client_key_file = matches.value_of("client-key").map(ToOwned::to_owned);
+  flags.lock = matches.value_of("lock").map(ToOwned::to_owned);
+  if matches.is_present("lock-write") {
+    flags.lock_write = true;
+  }
   if matches.is_present("v8-options") {
     let v8_flags = svec!["deno", "--help"];
     flags.v8_flags = Some(v8_flags);
@@ -445,6 +933,12 @@ fn parse_run_args(mut flags: DenoFlags, matches: &ArgMatches) -> DenoFlags {
   if matches.is_present("no-prompt") {
     flags.no_prompts = true;
   }
+  if matches.is_present("no-check") {
+    flags.no_check = true;
+  }
+  if matches.is_present("persist-cookies") {
+    flags.persist_cookies = true;
+  }
   flags.import_map_path = matches.value_of("importmap").map(ToOwned::to_owned);
 
   flags
@@ -458,12 +952,19 @@ const PRETTIER_URL: &str = "https://deno.land/std@v0.7.0/prettier/main.ts";
 #[derive(Debug, PartialEq)]
 pub enum DenoSubcommand {
   Bundle,
+  Cache,
+  Check,
+  Compile,
   Eval,
   Fetch,
+  ImportMap,
   Info,
   Repl,
   Run,
+  Snapshot,
+  Test,
   Types,
+  Vendor,
   Version,
   Xeval,
 }
@@ -479,11 +980,85 @@ pub fn flags_from_vec(
   let subcommand = match matches.subcommand() {
     ("bundle", Some(bundle_match)) => {
       flags.allow_write = true;
-      let source_file: &str = bundle_match.value_of("source_file").unwrap();
-      let out_file: &str = bundle_match.value_of("out_file").unwrap();
-      argv.extend(vec![source_file.to_string(), out_file.to_string()]);
+      let files: Vec<String> = bundle_match
+        .values_of("files")
+        .unwrap()
+        .map(String::from)
+        .collect();
+      flags.bundle_out_dir =
+        bundle_match.value_of("out-dir").map(ToOwned::to_owned);
+      if flags.bundle_out_dir.is_none() && files.len() != 2 {
+        clap::Error::with_description(
+          "Pass an entry point and an out file, or use --out-dir",
+          clap::ErrorKind::WrongNumberOfValues,
+        ).exit();
+      }
+      flags.bundle_minify = bundle_match.is_present("minify");
+      flags.bundle_source_map =
+        bundle_match.value_of("source-map").map(ToOwned::to_owned);
+      flags.bundle_target =
+        bundle_match.value_of("target").map(ToOwned::to_owned);
+      flags.watch = bundle_match.is_present("watch");
+      argv.extend(files);
       DenoSubcommand::Bundle
     }
+    ("cache", Some(cache_match)) => {
+      match cache_match.subcommand() {
+        ("clean", Some(clean_match)) => {
+          flags.cache_older_than =
+            clean_match.value_of("older-than").and_then(parse_duration);
+          argv.push("clean".to_string());
+          if let Some(url_prefix) = clean_match.value_of("url-prefix") {
+            argv.push(url_prefix.to_string());
+          }
+        }
+        (action, _) => argv.push(action.to_string()),
+      }
+      DenoSubcommand::Cache
+    }
+    ("check", Some(check_match)) => {
+      flags.json_diagnostics = check_match.value_of("format") == Some("json");
+      let files: Vec<String> = check_match
+        .values_of("files")
+        .unwrap()
+        .map(String::from)
+        .collect();
+      argv.extend(files);
+      DenoSubcommand::Check
+    }
+    ("compile", Some(compile_match)) => {
+      flags = parse_run_args(flags, compile_match);
+      flags.output = compile_match.value_of("output").map(ToOwned::to_owned);
+      let file: &str = compile_match.value_of("file").unwrap();
+      argv.push(file.to_string());
+      DenoSubcommand::Compile
+    }
+    ("snapshot", Some(snapshot_match)) => {
+      flags = parse_run_args(flags, snapshot_match);
+      flags.output = snapshot_match.value_of("output").map(ToOwned::to_owned);
+      let file: &str = snapshot_match.value_of("file").unwrap();
+      argv.push(file.to_string());
+      DenoSubcommand::Snapshot
+    }
+    ("importmap", Some(importmap_match)) => {
+      match importmap_match.subcommand() {
+        ("check", Some(check_match)) => {
+          let file: &str = check_match.value_of("file").unwrap();
+          argv.extend(vec!["check".to_string(), file.to_string()]);
+        }
+        ("resolve", Some(resolve_match)) => {
+          flags.import_map_path =
+            resolve_match.value_of("importmap").map(ToOwned::to_owned);
+          let specifier: &str = resolve_match.value_of("specifier").unwrap();
+          argv.extend(vec!["resolve".to_string(), specifier.to_string()]);
+          if let Some(referrer) = resolve_match.value_of("referrer") {
+            argv.push(referrer.to_string());
+          }
+        }
+        _ => unreachable!(),
+      }
+      DenoSubcommand::ImportMap
+    }
     ("eval", Some(eval_match)) => {
       flags.allow_net = true;
       flags.allow_env = true;
@@ -524,8 +1099,35 @@ pub fn flags_from_vec(
       argv.extend(vec![file.to_string()]);
       DenoSubcommand::Info
     }
+    ("test", Some(test_match)) => {
+      flags = parse_run_args(flags, test_match);
+      flags.test_filter = test_match.value_of("filter").map(ToOwned::to_owned);
+      flags.test_fail_fast = test_match.is_present("fail-fast");
+      flags.watch = test_match.is_present("watch");
+      flags.test_reporter =
+        test_match.value_of("reporter").map(ToOwned::to_owned);
+      flags.output = test_match.value_of("output").map(ToOwned::to_owned);
+      flags.coverage_dir =
+        test_match.value_of("coverage").map(ToOwned::to_owned);
+      if let Some(files) = test_match.values_of("files") {
+        argv.extend(files.map(String::from));
+      }
+      DenoSubcommand::Test
+    }
     ("types", Some(_)) => DenoSubcommand::Types,
+    ("vendor", Some(vendor_match)) => {
+      let file: &str = vendor_match.value_of("file").unwrap();
+      let out_dir: &str = vendor_match.value_of("out_dir").unwrap_or("vendor");
+      argv.extend(vec![file.to_string(), out_dir.to_string()]);
+      DenoSubcommand::Vendor
+    }
     ("run", Some(run_match)) => {
+      // A snapshot contains the program, so all arguments are passed to it.
+      flags.snapshot_path =
+        run_match.value_of("snapshot").map(ToOwned::to_owned);
+      flags.watch = run_match.is_present("watch");
+      flags.coverage_dir =
+        run_match.value_of("coverage").map(ToOwned::to_owned);
       match run_match.subcommand() {
         (script, Some(script_match)) => {
           argv.extend(vec![script.to_string()]);
@@ -541,7 +1143,15 @@ pub fn flags_from_vec(
           }
           DenoSubcommand::Run
         }
-        _ => unreachable!(),
+        _ => {
+          if flags.snapshot_path.is_none() {
+            clap::Error::with_description(
+              "Pass the script to run, or a snapshot with --snapshot",
+              clap::ErrorKind::MissingRequiredArgument,
+            ).exit();
+          }
+          DenoSubcommand::Run
+        }
       }
     }
     ("xeval", Some(eval_match)) => {
@@ -1112,4 +1722,460 @@ mod tests {
     assert_eq!(subcommand, DenoSubcommand::Run);
     assert_eq!(argv, svec!["deno", "script.ts"]);
   }
+
+  #[test]
+  fn test_flags_from_vec_28() {
+    let (flags, subcommand, argv) =
+      flags_from_vec(svec!["deno", "run", "--persist-cookies", "script.ts"]);
+    assert_eq!(
+      flags,
+      DenoFlags {
+        persist_cookies: true,
+        ..DenoFlags::default()
+      }
+    );
+    assert_eq!(subcommand, DenoSubcommand::Run);
+    assert_eq!(argv, svec!["deno", "script.ts"]);
+  }
+
+  #[test]
+  fn test_flags_from_vec_29() {
+    let (flags, subcommand, argv) = flags_from_vec(svec![
+      "deno",
+      "run",
+      "--cert=ca.pem",
+      "--client-cert=client.crt",
+      "--client-key=client.key",
+      "script.ts"
+    ]);
+    assert_eq!(
+      flags,
+      DenoFlags {
+        ca_file: Some("ca.pem".to_owned()),
+        client_cert_file: Some("client.crt".to_owned()),
+        client_key_file: Some("client.key".to_owned()),
+        ..DenoFlags::default()
+      }
+    );
+    assert_eq!(subcommand, DenoSubcommand::Run);
+    assert_eq!(argv, svec!["deno", "script.ts"]);
+  }
+
+  #[test]
+  fn test_flags_from_vec_30() {
+    // A client certificate is useless without its key.
+    let matches = create_cli_app().get_matches_from_safe(svec![
+      "deno",
+      "--client-cert=client.crt",
+      "script.ts"
+    ]);
+    assert!(matches.is_err());
+  }
+
+  #[test]
+  fn test_flags_from_vec_31() {
+    let (flags, subcommand, argv) = flags_from_vec(svec![
+      "deno",
+      "fetch",
+      "--lock=lock.json",
+      "--lock-write",
+      "script.ts"
+    ]);
+    assert_eq!(
+      flags,
+      DenoFlags {
+        lock: Some("lock.json".to_owned()),
+        lock_write: true,
+        ..DenoFlags::default()
+      }
+    );
+    assert_eq!(subcommand, DenoSubcommand::Fetch);
+    assert_eq!(argv, svec!["deno", "script.ts"]);
+  }
+
+  #[test]
+  fn test_flags_from_vec_32() {
+    let (flags, subcommand, argv) =
+      flags_from_vec(svec!["deno", "vendor", "script.ts"]);
+    assert_eq!(flags, DenoFlags::default());
+    assert_eq!(subcommand, DenoSubcommand::Vendor);
+    assert_eq!(argv, svec!["deno", "script.ts", "vendor"]);
+
+    let (flags, subcommand, argv) =
+      flags_from_vec(svec!["deno", "vendor", "script.ts", "third_party"]);
+    assert_eq!(flags, DenoFlags::default());
+    assert_eq!(subcommand, DenoSubcommand::Vendor);
+    assert_eq!(argv, svec!["deno", "script.ts", "third_party"]);
+  }
+
+  #[test]
+  fn test_flags_from_vec_33() {
+    let (flags, subcommand, argv) =
+      flags_from_vec(svec!["deno", "cache", "ls"]);
+    assert_eq!(flags, DenoFlags::default());
+    assert_eq!(subcommand, DenoSubcommand::Cache);
+    assert_eq!(argv, svec!["deno", "ls"]);
+
+    let (flags, subcommand, argv) = flags_from_vec(svec![
+      "deno",
+      "cache",
+      "clean",
+      "--older-than",
+      "30d",
+      "https://deno.land/"
+    ]);
+    assert_eq!(
+      flags,
+      DenoFlags {
+        cache_older_than: Some(Duration::from_secs(30 * 24 * 60 * 60)),
+        ..DenoFlags::default()
+      }
+    );
+    assert_eq!(subcommand, DenoSubcommand::Cache);
+    assert_eq!(argv, svec!["deno", "clean", "https://deno.land/"]);
+  }
+
+  #[test]
+  fn test_flags_from_vec_34() {
+    let (flags, subcommand, argv) = flags_from_vec(svec![
+      "deno",
+      "run",
+      "--reload=revalidate",
+      "script.ts"
+    ]);
+    assert_eq!(
+      flags,
+      DenoFlags {
+        revalidate: true,
+        ..DenoFlags::default()
+      }
+    );
+    assert_eq!(subcommand, DenoSubcommand::Run);
+    assert_eq!(argv, svec!["deno", "script.ts"]);
+
+    let (flags, _, argv) =
+      flags_from_vec(svec!["deno", "run", "-r", "script.ts"]);
+    assert_eq!(
+      flags,
+      DenoFlags {
+        reload: true,
+        ..DenoFlags::default()
+      }
+    );
+    assert_eq!(argv, svec!["deno", "script.ts"]);
+  }
+
+  #[test]
+  fn test_flags_from_vec_35() {
+    let (flags, subcommand, argv) = flags_from_vec(svec![
+      "deno",
+      "run",
+      "--reload=https://deno.land/std,https://example.com/",
+      "script.ts"
+    ]);
+    assert_eq!(
+      flags,
+      DenoFlags {
+        reload_whitelist: svec![
+          "https://deno.land/std",
+          "https://example.com/"
+        ],
+        ..DenoFlags::default()
+      }
+    );
+    assert_eq!(subcommand, DenoSubcommand::Run);
+    assert_eq!(argv, svec!["deno", "script.ts"]);
+  }
+
+  #[test]
+  fn test_flags_from_vec_36() {
+    let (flags, subcommand, argv) =
+      flags_from_vec(svec!["deno", "importmap", "check", "import_map.json"]);
+    assert_eq!(flags, DenoFlags::default());
+    assert_eq!(subcommand, DenoSubcommand::ImportMap);
+    assert_eq!(argv, svec!["deno", "check", "import_map.json"]);
+
+    let (flags, subcommand, argv) = flags_from_vec(svec![
+      "deno",
+      "importmap",
+      "resolve",
+      "moment",
+      "--referrer",
+      "./src/main.ts",
+      "--importmap",
+      "import_map.json"
+    ]);
+    assert_eq!(
+      flags,
+      DenoFlags {
+        import_map_path: Some("import_map.json".to_owned()),
+        ..DenoFlags::default()
+      }
+    );
+    assert_eq!(subcommand, DenoSubcommand::ImportMap);
+    assert_eq!(argv, svec!["deno", "resolve", "moment", "./src/main.ts"]);
+  }
+
+  #[test]
+  fn test_flags_from_vec_37() {
+    let (flags, subcommand, argv) =
+      flags_from_vec(svec!["deno", "run", "--no-check", "script.ts"]);
+    assert_eq!(
+      flags,
+      DenoFlags {
+        no_check: true,
+        ..DenoFlags::default()
+      }
+    );
+    assert_eq!(subcommand, DenoSubcommand::Run);
+    assert_eq!(argv, svec!["deno", "script.ts"]);
+  }
+
+  #[test]
+  fn test_flags_from_vec_38() {
+    let (flags, subcommand, argv) =
+      flags_from_vec(svec!["deno", "check", "main.ts", "worker.ts"]);
+    assert_eq!(flags, DenoFlags::default());
+    assert_eq!(subcommand, DenoSubcommand::Check);
+    assert_eq!(argv, svec!["deno", "main.ts", "worker.ts"]);
+
+    let (flags, subcommand, argv) =
+      flags_from_vec(svec!["deno", "check", "--format=json", "main.ts"]);
+    assert_eq!(
+      flags,
+      DenoFlags {
+        json_diagnostics: true,
+        ..DenoFlags::default()
+      }
+    );
+    assert_eq!(subcommand, DenoSubcommand::Check);
+    assert_eq!(argv, svec!["deno", "main.ts"]);
+  }
+
+  #[test]
+  fn test_flags_from_vec_39() {
+    let (flags, subcommand, argv) = flags_from_vec(svec![
+      "deno",
+      "bundle",
+      "--out-dir=dist",
+      "--minify",
+      "--source-map=external",
+      "--target=es2017",
+      "main.ts",
+      "worker.ts"
+    ]);
+    assert_eq!(
+      flags,
+      DenoFlags {
+        allow_write: true,
+        bundle_out_dir: Some("dist".to_owned()),
+        bundle_minify: true,
+        bundle_source_map: Some("external".to_owned()),
+        bundle_target: Some("es2017".to_owned()),
+        ..DenoFlags::default()
+      }
+    );
+    assert_eq!(subcommand, DenoSubcommand::Bundle);
+    assert_eq!(argv, svec!["deno", "main.ts", "worker.ts"]);
+  }
+
+  #[test]
+  fn test_flags_from_vec_40() {
+    let (flags, subcommand, argv) = flags_from_vec(svec![
+      "deno",
+      "compile",
+      "--allow-net",
+      "--allow-read",
+      "main.ts",
+      "-o",
+      "mytool"
+    ]);
+    assert_eq!(
+      flags,
+      DenoFlags {
+        allow_net: true,
+        allow_read: true,
+        output: Some("mytool".to_owned()),
+        ..DenoFlags::default()
+      }
+    );
+    assert_eq!(subcommand, DenoSubcommand::Compile);
+    assert_eq!(argv, svec!["deno", "main.ts"]);
+  }
+
+  #[test]
+  fn test_flags_from_vec_41() {
+    let (flags, subcommand, argv) = flags_from_vec(svec![
+      "deno",
+      "snapshot",
+      "--allow-read",
+      "main.ts",
+      "-o",
+      "app.snap"
+    ]);
+    assert_eq!(
+      flags,
+      DenoFlags {
+        allow_read: true,
+        output: Some("app.snap".to_owned()),
+        ..DenoFlags::default()
+      }
+    );
+    assert_eq!(subcommand, DenoSubcommand::Snapshot);
+    assert_eq!(argv, svec!["deno", "main.ts"]);
+
+    let (flags, subcommand, argv) = flags_from_vec(svec![
+      "deno",
+      "run",
+      "--snapshot=app.snap",
+      "--allow-net",
+      "arg1",
+      "--arg2"
+    ]);
+    assert_eq!(
+      flags,
+      DenoFlags {
+        allow_net: true,
+        snapshot_path: Some("app.snap".to_owned()),
+        ..DenoFlags::default()
+      }
+    );
+    assert_eq!(subcommand, DenoSubcommand::Run);
+    assert_eq!(argv, svec!["deno", "arg1", "--arg2"]);
+
+    let (flags, subcommand, argv) =
+      flags_from_vec(svec!["deno", "run", "--snapshot=app.snap"]);
+    assert_eq!(flags.snapshot_path, Some("app.snap".to_owned()));
+    assert_eq!(subcommand, DenoSubcommand::Run);
+    assert_eq!(argv, svec!["deno"]);
+  }
+
+  #[test]
+  fn test_flags_from_vec_42() {
+    let (flags, subcommand, argv) = flags_from_vec(svec![
+      "deno",
+      "run",
+      "--watch",
+      "--allow-net",
+      "server.ts",
+      "--port=8000"
+    ]);
+    assert_eq!(
+      flags,
+      DenoFlags {
+        allow_net: true,
+        watch: true,
+        ..DenoFlags::default()
+      }
+    );
+    assert_eq!(subcommand, DenoSubcommand::Run);
+    assert_eq!(argv, svec!["deno", "server.ts", "--port=8000"]);
+
+    let (flags, subcommand, argv) = flags_from_vec(svec![
+      "deno",
+      "bundle",
+      "--watch",
+      "main.ts",
+      "bundle.js"
+    ]);
+    assert_eq!(
+      flags,
+      DenoFlags {
+        allow_write: true,
+        watch: true,
+        ..DenoFlags::default()
+      }
+    );
+    assert_eq!(subcommand, DenoSubcommand::Bundle);
+    assert_eq!(argv, svec!["deno", "main.ts", "bundle.js"]);
+
+    let (flags, subcommand, argv) =
+      flags_from_vec(svec!["deno", "test", "--watch", "src/"]);
+    assert_eq!(
+      flags,
+      DenoFlags {
+        watch: true,
+        ..DenoFlags::default()
+      }
+    );
+    assert_eq!(subcommand, DenoSubcommand::Test);
+    assert_eq!(argv, svec!["deno", "src/"]);
+  }
+
+  #[test]
+  fn test_flags_from_vec_43() {
+    let (flags, subcommand, argv) = flags_from_vec(svec![
+      "deno",
+      "test",
+      "--allow-read",
+      "--filter=parse",
+      "--fail-fast",
+      "--reporter=junit",
+      "-o",
+      "report.xml",
+      "src/",
+      "lib/**/*_test.ts"
+    ]);
+    assert_eq!(
+      flags,
+      DenoFlags {
+        allow_read: true,
+        test_filter: Some("parse".to_owned()),
+        test_fail_fast: true,
+        test_reporter: Some("junit".to_owned()),
+        output: Some("report.xml".to_owned()),
+        ..DenoFlags::default()
+      }
+    );
+    assert_eq!(subcommand, DenoSubcommand::Test);
+    assert_eq!(argv, svec!["deno", "src/", "lib/**/*_test.ts"]);
+
+    let (flags, subcommand, argv) = flags_from_vec(svec!["deno", "test"]);
+    assert_eq!(flags, DenoFlags::default());
+    assert_eq!(subcommand, DenoSubcommand::Test);
+    assert_eq!(argv, svec!["deno"]);
+  }
+
+  #[test]
+  fn test_flags_from_vec_44() {
+    let (flags, subcommand, argv) = flags_from_vec(svec![
+      "deno",
+      "run",
+      "--coverage=cov",
+      "script.ts",
+      "arg"
+    ]);
+    assert_eq!(
+      flags,
+      DenoFlags {
+        coverage_dir: Some("cov".to_owned()),
+        ..DenoFlags::default()
+      }
+    );
+    assert_eq!(subcommand, DenoSubcommand::Run);
+    assert_eq!(argv, svec!["deno", "script.ts", "arg"]);
+
+    let (flags, subcommand, argv) =
+      flags_from_vec(svec!["deno", "test", "--coverage=cov", "src/"]);
+    assert_eq!(
+      flags,
+      DenoFlags {
+        coverage_dir: Some("cov".to_owned()),
+        ..DenoFlags::default()
+      }
+    );
+    assert_eq!(subcommand, DenoSubcommand::Test);
+    assert_eq!(argv, svec!["deno", "src/"]);
+  }
+
+  #[test]
+  fn test_parse_duration() {
+    assert_eq!(parse_duration("90s"), Some(Duration::from_secs(90)));
+    assert_eq!(parse_duration("2h"), Some(Duration::from_secs(7200)));
+    assert_eq!(parse_duration("1w"), Some(Duration::from_secs(604_800)));
+    assert_eq!(parse_duration("30"), None);
+    assert_eq!(parse_duration("d"), None);
+    assert_eq!(parse_duration("-1d"), None);
+    assert_eq!(parse_duration(""), None);
+  }
 }
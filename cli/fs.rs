@@ -8,8 +8,16 @@ use std::path::{Path, PathBuf};
 use rand;
 use rand::Rng;
 
+#[cfg(unix)]
+use nix::fcntl::{flock, FlockArg};
 #[cfg(unix)]
 use nix::unistd::{chown as unix_chown, Gid, Uid};
+#[cfg(unix)]
+use std::os::unix::fs::MetadataExt;
+#[cfg(unix)]
+use std::os::unix::io::AsRawFd;
+#[cfg(windows)]
+use std::os::windows::fs::OpenOptionsExt;
 #[cfg(any(unix))]
 use std::os::unix::fs::DirBuilderExt;
 #[cfg(any(unix))]
@@ -48,6 +56,108 @@ pub fn write_file_2<T: AsRef<[u8]>>(
   file.write_all(data.as_ref())
 }
 
+/// Like `write_file()`, but the data is first written to a temporary file in
+/// the same directory which then replaces `filename`. Other processes thus
+/// either see the old or the new contents, never a partially written file.
+pub fn write_file_atomic<T: AsRef<[u8]>>(
+  filename: &Path,
+  data: T,
+  perm: u32,
+) -> std::io::Result<()> {
+  let file_name = filename
+    .file_name()
+    .and_then(|name| name.to_str())
+    .unwrap_or("");
+  let unique = rand::thread_rng().gen::<u32>();
+  let temp_filename =
+    filename.with_file_name(format!(".{}.{:08x}.tmp", file_name, unique));
+  let result = write_file(&temp_filename, data, perm)
+    .and_then(|()| std::fs::rename(&temp_filename, filename));
+  if result.is_err() {
+    let _ = std::fs::remove_file(&temp_filename);
+  }
+  result
+}
+
+/// An exclusive advisory lock on a file, held until the value is dropped.
+/// Only cooperating processes which also lock the file are excluded. The file
+/// is removed when the lock is released.
+pub struct FileLock {
+  _file: File,
+  #[cfg(unix)]
+  path: PathBuf,
+}
+
+impl FileLock {
+  /// Blocks until the lock on `filename` is acquired. The file is created if
+  /// it doesn't exist.
+  #[cfg(unix)]
+  pub fn acquire(filename: &Path) -> std::io::Result<FileLock> {
+    loop {
+      let file = OpenOptions::new()
+        .write(true)
+        .create(true)
+        .open(filename)?;
+      match flock(file.as_raw_fd(), FlockArg::LockExclusive) {
+        Ok(()) => {}
+        Err(nix::Error::Sys(nix::errno::Errno::EINTR)) => continue,
+        Err(e) => {
+          return Err(std::io::Error::new(ErrorKind::Other, e.to_string()))
+        }
+      }
+      // The holder before us removed the file when it released the lock, so
+      // it may be locked through a new file by now. Try again if so.
+      let locked = file.metadata()?;
+      match std::fs::metadata(filename) {
+        Ok(ref current)
+          if current.dev() == locked.dev() && current.ino() == locked.ino() =>
+        {
+          return Ok(FileLock {
+            _file: file,
+            path: filename.to_path_buf(),
+          })
+        }
+        Ok(_) => continue,
+        Err(ref e) if e.kind() == ErrorKind::NotFound => continue,
+        Err(e) => return Err(e),
+      }
+    }
+  }
+
+  /// Blocks until the lock on `filename` is acquired. The file is created if
+  /// it doesn't exist.
+  #[cfg(windows)]
+  pub fn acquire(filename: &Path) -> std::io::Result<FileLock> {
+    // Opening the file without sharing fails while another process has it
+    // open, which serves as the lock. It is deleted once it is closed.
+    const ERROR_SHARING_VIOLATION: i32 = 32;
+    const FILE_FLAG_DELETE_ON_CLOSE: u32 = 0x0400_0000;
+    loop {
+      let result = OpenOptions::new()
+        .write(true)
+        .create(true)
+        .share_mode(0)
+        .custom_flags(FILE_FLAG_DELETE_ON_CLOSE)
+        .open(filename);
+      match result {
+        Ok(file) => return Ok(FileLock { _file: file }),
+        Err(ref e) if e.raw_os_error() == Some(ERROR_SHARING_VIOLATION) => {
+          std::thread::sleep(std::time::Duration::from_millis(50));
+        }
+        Err(e) => return Err(e),
+      }
+    }
+  }
+}
+
+#[cfg(unix)]
+impl Drop for FileLock {
+  fn drop(&mut self) {
+    // Removed while still locked, so nobody acquires the lock on it anymore.
+    let _ = std::fs::remove_file(&self.path);
+  }
+}
+
 #[cfg(any(unix))]
 fn set_permissions(file: &mut File, perm: u32) -> std::io::Result<()> {
   debug!("set file perm to {}", perm);
@@ -129,3 +239,45 @@ pub fn chown(_path: &str, _uid: u32, _gid: u32) -> DenoResult<()> {
   use crate::errors;
   Err(errors::op_not_implemented())
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use std::sync::mpsc::channel;
+  use std::thread;
+  use std::time::Duration;
+  use tempfile::TempDir;
+
+  #[test]
+  fn test_write_file_atomic() {
+    let temp_dir = TempDir::new().unwrap();
+    let filename = temp_dir.path().join("a.js");
+    write_file_atomic(&filename, "old", 0o666).unwrap();
+    write_file_atomic(&filename, "new", 0o666).unwrap();
+    assert_eq!(std::fs::read_to_string(&filename).unwrap(), "new");
+    // The temporary file was renamed.
+    let entries = std::fs::read_dir(temp_dir.path()).unwrap().count();
+    assert_eq!(entries, 1);
+  }
+
+  #[test]
+  fn test_file_lock() {
+    let temp_dir = TempDir::new().unwrap();
+    let filename = temp_dir.path().join(".a.js.lock");
+    let lock = FileLock::acquire(&filename).unwrap();
+
+    let (tx, rx) = channel();
+    let filename_ = filename.clone();
+    let waiter = thread::spawn(move || {
+      let _lock = FileLock::acquire(&filename_).unwrap();
+      tx.send(()).unwrap();
+    });
+    // The lock is still held, so the other thread has to wait.
+    assert!(rx.recv_timeout(Duration::from_millis(200)).is_err());
+    drop(lock);
+    rx.recv_timeout(Duration::from_secs(5)).unwrap();
+    waiter.join().unwrap();
+    // The lock file is removed once the lock is released.
+    assert!(!filename.exists());
+  }
+}
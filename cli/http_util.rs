@@ -1,30 +1,237 @@
 // Copyright 2018-2019 the Deno authors. All rights reserved. MIT license.
+use crate::auth_tokens::AuthTokens;
+use crate::cookie_jar::CookieJar;
 use crate::errors;
 use crate::errors::DenoError;
+use crate::errors::DenoResult;
+use crate::errors::ErrorKind;
 #[cfg(test)]
 use futures::future::{loop_fn, Loop};
 use futures::{future, Future, Stream};
 use hyper;
+use hyper::client::connect::{Connect, Connected, Destination};
 use hyper::client::{Client, HttpConnector};
+use hyper::header::HeaderMap;
+use hyper::header::AUTHORIZATION;
+use hyper::header::CACHE_CONTROL;
 use hyper::header::CONTENT_TYPE;
+use hyper::header::COOKIE;
+use hyper::header::ETAG;
+use hyper::header::IF_MODIFIED_SINCE;
+use hyper::header::IF_NONE_MATCH;
+use hyper::header::LAST_MODIFIED;
+use hyper::Body;
+use hyper::Request;
+use hyper::Response;
+use hyper::StatusCode;
 use hyper::Uri;
 use hyper_rustls;
+use hyper_rustls::MaybeHttpsStream;
+use std::fs::File;
+use std::io::BufReader;
+use std::sync::RwLock;
+use tokio_rustls::rustls::internal::pemfile;
+use tokio_rustls::rustls::sign;
+use tokio_rustls::rustls::ClientConfig;
+use tokio_rustls::rustls::PrivateKey;
+use tokio_rustls::rustls::Session;
+use webpki_roots;
+
+type HttpsConnector = hyper_rustls::HttpsConnector<HttpConnector>;
+
+/// ALPN protocol identifiers, in order of preference.
+/// https://www.iana.org/assignments/tls-extensiontype-values/tls-extensiontype-values.xhtml#alpn-protocol-ids
+const ALPN_H2: &[u8] = b"h2";
+const ALPN_HTTP_1_1: &[u8] = b"http/1.1";
+
+/// Wraps the rustls connector so that hyper uses HTTP/2 on TLS connections
+/// where the server selected "h2" during ALPN negotiation.
+#[derive(Clone)]
+pub struct Connector(HttpsConnector);
+
+impl Connect for Connector {
+  type Transport = <HttpsConnector as Connect>::Transport;
+  type Error = <HttpsConnector as Connect>::Error;
+  type Future = Box<
+    dyn Future<Item = (Self::Transport, Connected), Error = Self::Error>
+      + Send,
+  >;
+
+  fn connect(&self, dst: Destination) -> Self::Future {
+    Box::new(self.0.connect(dst).map(|(stream, connected)| {
+      let negotiated_h2 = match stream {
+        MaybeHttpsStream::Https(ref tls) => {
+          tls.get_ref().1.get_alpn_protocol() == Some(ALPN_H2)
+        }
+        MaybeHttpsStream::Http(_) => false,
+      };
+      if negotiated_h2 {
+        (stream, connected.negotiated_h2())
+      } else {
+        (stream, connected)
+      }
+    }))
+  }
+}
+
+fn tls_config() -> ClientConfig {
+  let mut config = ClientConfig::new();
+  config
+    .root_store
+    .add_server_trust_anchors(&webpki_roots::TLS_SERVER_ROOTS);
+  config.set_protocols(&[ALPN_H2.to_vec(), ALPN_HTTP_1_1.to_vec()]);
+  config
+}
+
+fn open_pem_file(path: &str) -> DenoResult<BufReader<File>> {
+  let file = File::open(path).map_err(|e| {
+    errors::new(
+      ErrorKind::NotFound,
+      format!("Unable to open certificate file '{}': {}", path, e),
+    )
+  })?;
+  Ok(BufReader::new(file))
+}
 
-type Connector = hyper_rustls::HttpsConnector<HttpConnector>;
+fn invalid_pem_file(path: &str, what: &str) -> DenoError {
+  errors::new(
+    ErrorKind::InvalidData,
+    format!("Unable to read {} from '{}'", what, path),
+  )
+}
+
+fn load_private_key(path: &str) -> DenoResult<PrivateKey> {
+  // The key may either be PKCS#8 ("BEGIN PRIVATE KEY") or PKCS#1
+  // ("BEGIN RSA PRIVATE KEY") encoded.
+  let mut keys = pemfile::pkcs8_private_keys(&mut open_pem_file(path)?)
+    .map_err(|_| invalid_pem_file(path, "private key"))?;
+  if keys.is_empty() {
+    keys = pemfile::rsa_private_keys(&mut open_pem_file(path)?)
+      .map_err(|_| invalid_pem_file(path, "private key"))?;
+  }
+  match keys.into_iter().next() {
+    Some(key) => Ok(key),
+    None => Err(invalid_pem_file(path, "private key")),
+  }
+}
+
+/// Creates the TLS configuration used for all outgoing HTTPS connections.
+/// `ca_file` is a PEM file with additional trusted root certificates; the
+/// default webpki roots stay trusted. `client_cert_and_key` are the PEM files
+/// of a client certificate chain and its private key, presented to servers
+/// which request client authentication.
+pub fn create_tls_config(
+  ca_file: Option<&str>,
+  client_cert_and_key: Option<(&str, &str)>,
+) -> DenoResult<ClientConfig> {
+  let mut config = tls_config();
+
+  if let Some(ca_file) = ca_file {
+    let mut reader = open_pem_file(ca_file)?;
+    match config.root_store.add_pem_file(&mut reader) {
+      Ok((valid, _)) if valid > 0 => {}
+      _ => return Err(invalid_pem_file(ca_file, "CA certificates")),
+    }
+  }
+
+  if let Some((cert_file, key_file)) = client_cert_and_key {
+    let cert_chain = pemfile::certs(&mut open_pem_file(cert_file)?)
+      .map_err(|_| invalid_pem_file(cert_file, "client certificate"))?;
+    if cert_chain.is_empty() {
+      return Err(invalid_pem_file(cert_file, "client certificate"));
+    }
+    let key = load_private_key(key_file)?;
+    // set_single_client_cert() panics on keys rustls can't use.
+    if sign::any_supported_type(&key).is_err() {
+      return Err(invalid_pem_file(key_file, "a supported private key"));
+    }
+    config.set_single_client_cert(cert_chain, key);
+  }
+
+  Ok(config)
+}
+
+fn new_connector(config: ClientConfig) -> Connector {
+  let num_dns_threads = 4;
+  let mut http = HttpConnector::new(num_dns_threads);
+  // Allow https:// URLs, they are handled by the TLS layer.
+  http.enforce_http(false);
+  Connector(HttpsConnector::from((http, config)))
+}
 
 lazy_static! {
-  static ref CONNECTOR: Connector = {
-    let num_dns_threads = 4;
-    Connector::new(num_dns_threads)
-  };
+  static ref CONNECTOR: RwLock<Connector> =
+    RwLock::new(new_connector(tls_config()));
+  static ref AUTH_TOKENS: RwLock<AuthTokens> =
+    RwLock::new(AuthTokens::default());
+}
+
+/// Replaces the TLS configuration used by `get_client()`. Called once at
+/// startup, so module fetching and the runtime `fetch()` share the same
+/// certificates.
+pub fn set_tls_config(config: ClientConfig) {
+  *CONNECTOR.write().unwrap() = new_connector(config);
+}
+
+/// Sets the credentials sent by `fetch_string_once()` when downloading remote
+/// modules. Called once at startup with the value of `DENO_AUTH_TOKENS`.
+pub fn set_auth_tokens(auth_tokens: AuthTokens) {
+  *AUTH_TOKENS.write().unwrap() = auth_tokens;
 }
 
 pub fn get_client() -> Client<Connector, hyper::Body> {
   // TODO use Hyper's connection pool.
-  let c = CONNECTOR.clone();
+  let c = CONNECTOR.read().unwrap().clone();
   Client::builder().build(c)
 }
 
+/// Like `get_client()`, but every connection speaks HTTP/2 without any
+/// upgrade or ALPN negotiation ("prior knowledge"). This is how h2c, HTTP/2
+/// over cleartext TCP, is used.
+pub fn get_http2_prior_knowledge_client() -> Client<Connector, hyper::Body> {
+  let c = CONNECTOR.read().unwrap().clone();
+  Client::builder().http2_only(true).build(c)
+}
+
+/// Sends `req` using a client from `get_client()`, or from
+/// `get_http2_prior_knowledge_client()` if `http2_prior_knowledge` is set.
+/// If `maybe_cookie_jar` is given, cookies from the jar that match the request
+/// URL are sent along, and `Set-Cookie` headers of the response are stored
+/// back into the jar.
+pub fn request(
+  mut req: Request<Body>,
+  maybe_cookie_jar: Option<CookieJar>,
+  http2_prior_knowledge: bool,
+) -> impl Future<Item = Response<Body>, Error = DenoError> {
+  let maybe_jar_and_url = maybe_cookie_jar.and_then(|jar| {
+    url::Url::parse(&req.uri().to_string())
+      .ok()
+      .map(|url| (jar, url))
+  });
+  if let Some((ref jar, ref url)) = maybe_jar_and_url {
+    // A Cookie header set explicitly by the script takes precedence.
+    if !req.headers().contains_key(COOKIE) {
+      if let Some(cookie) = jar.cookie_header(url) {
+        req.headers_mut().insert(COOKIE, cookie);
+      }
+    }
+  }
+  let client = if http2_prior_knowledge {
+    get_http2_prior_knowledge_client()
+  } else {
+    get_client()
+  };
+  client
+    .request(req)
+    .map_err(DenoError::from)
+    .map(move |response| {
+      if let Some((jar, url)) = maybe_jar_and_url {
+        jar.store_response_cookies(&url, response.headers());
+      }
+      response
+    })
+}
+
 /// Construct the next uri based on base uri and location header fragment
 /// See <https://tools.ietf.org/html/rfc3986#section-4.2>
 fn resolve_uri_from_location(base_uri: &Uri, location: &str) -> Uri {
@@ -57,8 +264,6 @@ fn resolve_uri_from_location(base_uri: &Uri, location: &str) -> Uri {
   }
 }
 
-#[cfg(test)]
-use crate::errors::DenoResult;
 #[cfg(test)]
 use crate::tokio_util;
 #[cfg(test)]
@@ -67,75 +272,153 @@ pub fn fetch_sync_string(module_name: &str) -> DenoResult<(String, String)> {
   tokio_util::block_on(fetch_string(module_name))
 }
 
+/// Response headers which are stored along with a cached remote module, so
+/// that it can later be revalidated with a conditional request.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct CacheHeaders {
+  pub etag: Option<String>,
+  pub last_modified: Option<String>,
+  pub cache_control: Option<String>,
+}
+
+impl CacheHeaders {
+  fn from_headers(headers: &HeaderMap) -> CacheHeaders {
+    let get = |name| {
+      headers
+        .get(name)
+        .and_then(|value| value.to_str().ok())
+        .map(ToOwned::to_owned)
+    };
+    CacheHeaders {
+      etag: get(ETAG),
+      last_modified: get(LAST_MODIFIED),
+      cache_control: get(CACHE_CONTROL),
+    }
+  }
+
+  /// True if the server promised that the response will never change.
+  pub fn is_immutable(&self) -> bool {
+    self.cache_control.as_ref().map_or(false, |cache_control| {
+      cache_control
+        .split(',')
+        .any(|directive| directive.trim().eq_ignore_ascii_case("immutable"))
+    })
+  }
+
+  /// Headers of a 304 response update those stored with the cached module.
+  pub fn update(&mut self, other: CacheHeaders) {
+    if other.etag.is_some() {
+      self.etag = other.etag;
+    }
+    if other.last_modified.is_some() {
+      self.last_modified = other.last_modified;
+    }
+    if other.cache_control.is_some() {
+      self.cache_control = other.cache_control;
+    }
+  }
+}
+
 pub enum FetchOnceResult {
-  // (code, maybe_content_type)
-  Code(String, Option<String>),
+  // (code, maybe_content_type, cache_headers)
+  Code(String, Option<String>, CacheHeaders),
   Redirect(http::uri::Uri),
+  /// Only returned for conditional requests: the cached copy is up to date.
+  NotModified(CacheHeaders),
 }
 
 /// Asynchronously fetchs the given HTTP URL one pass only.
 /// If no redirect is present and no error occurs,
-/// yields Code(code, maybe_content_type).
+/// yields Code(code, maybe_content_type, cache_headers).
 /// If redirect occurs, does not follow and
 /// yields Redirect(url).
+/// If `validators` has an ETag or Last-Modified date the request is
+/// conditional, and yields NotModified(cache_headers) when the server
+/// answers with 304.
+/// Credentials from `DENO_AUTH_TOKENS` are sent if they match the URL.
 pub fn fetch_string_once(
   url: http::uri::Uri,
+  validators: &CacheHeaders,
 ) -> impl Future<Item = FetchOnceResult, Error = DenoError> {
-  type FetchAttempt = (Option<String>, Option<String>, Option<FetchOnceResult>);
-  let client = get_client();
-  client
-    .get(url.clone())
-    .map_err(DenoError::from)
-    .and_then(
-      move |response| -> Box<
-        dyn Future<Item = FetchAttempt, Error = DenoError> + Send,
-      > {
-        if response.status().is_redirection() {
-          let location_string = response
-            .headers()
-            .get("location")
-            .expect("url redirection should provide 'location' header")
-            .to_str()
-            .unwrap()
-            .to_string();
-          debug!("Redirecting to {}...", &location_string);
-          let new_url = resolve_uri_from_location(&url, &location_string);
-          // Boxed trait object turns out to be the savior for 2+ types yielding same results.
-          return Box::new(future::ok(None).join3(
-            future::ok(None),
-            future::ok(Some(FetchOnceResult::Redirect(new_url))),
-          ));
-        } else if response.status().is_client_error()
-          || response.status().is_server_error()
-        {
-          return Box::new(future::err(errors::new(
-            errors::ErrorKind::Other,
-            format!("Import '{}' failed: {}", &url, response.status()),
-          )));
-        }
-        let content_type = response
+  let auth_tokens = AUTH_TOKENS.read().unwrap();
+  fetch_string_once_with_auth_tokens(url, validators, &auth_tokens)
+}
+
+/// Like `fetch_string_once()`, but sends credentials from `auth_tokens`
+/// instead of those set with `set_auth_tokens()`.
+fn fetch_string_once_with_auth_tokens(
+  url: http::uri::Uri,
+  validators: &CacheHeaders,
+  auth_tokens: &AuthTokens,
+) -> impl Future<Item = FetchOnceResult, Error = DenoError> {
+  let mut builder = Request::get(url.clone());
+  if let Some(authorization) = auth_tokens.authorization(&url) {
+    builder.header(AUTHORIZATION, authorization.as_str());
+  }
+  if let Some(ref etag) = validators.etag {
+    builder.header(IF_NONE_MATCH, etag.as_str());
+  }
+  if let Some(ref last_modified) = validators.last_modified {
+    builder.header(IF_MODIFIED_SINCE, last_modified.as_str());
+  }
+  let response = match builder.body(Body::empty()) {
+    Ok(request) => {
+      future::Either::A(get_client().request(request).map_err(DenoError::from))
+    }
+    Err(err) => {
+      debug!("Invalid request for {}: {}", url, err);
+      future::Either::B(future::err(errors::new(
+        ErrorKind::InvalidInput,
+        format!("Invalid request headers for {}", url),
+      )))
+    }
+  };
+  response.and_then(
+    move |response| -> Box<
+      dyn Future<Item = FetchOnceResult, Error = DenoError> + Send,
+    > {
+      if response.status() == StatusCode::NOT_MODIFIED {
+        let cache_headers = CacheHeaders::from_headers(response.headers());
+        return Box::new(future::ok(FetchOnceResult::NotModified(
+          cache_headers,
+        )));
+      } else if response.status().is_redirection() {
+        let location_string = response
           .headers()
-          .get(CONTENT_TYPE)
-          .map(|content_type| content_type.to_str().unwrap().to_owned());
-        let body = response
-          .into_body()
-          .concat2()
-          .map(|body| String::from_utf8(body.to_vec()).ok())
-          .map_err(DenoError::from);
-        Box::new(body.join3(future::ok(content_type), future::ok(None)))
-      },
-    )
-    .and_then(move |(maybe_code, maybe_content_type, maybe_redirect)| {
-      if let Some(redirect) = maybe_redirect {
-        future::ok(redirect)
-      } else {
-        // maybe_code should always contain code here!
-        future::ok(FetchOnceResult::Code(
-          maybe_code.unwrap(),
-          maybe_content_type,
-        ))
+          .get("location")
+          .expect("url redirection should provide 'location' header")
+          .to_str()
+          .unwrap()
+          .to_string();
+        debug!("Redirecting to {}...", &location_string);
+        let new_url = resolve_uri_from_location(&url, &location_string);
+        return Box::new(future::ok(FetchOnceResult::Redirect(new_url)));
+      } else if response.status().is_client_error()
+        || response.status().is_server_error()
+      {
+        return Box::new(future::err(errors::new(
+          errors::ErrorKind::Other,
+          format!("Import '{}' failed: {}", &url, response.status()),
+        )));
       }
-    })
+      let content_type = response
+        .headers()
+        .get(CONTENT_TYPE)
+        .map(|content_type| content_type.to_str().unwrap().to_owned());
+      let cache_headers = CacheHeaders::from_headers(response.headers());
+      let body = response
+        .into_body()
+        .concat2()
+        .map(move |body| {
+          FetchOnceResult::Code(
+            String::from_utf8(body.to_vec()).unwrap(),
+            content_type,
+            cache_headers,
+          )
+        }).map_err(DenoError::from);
+      Box::new(body)
+    },
+  )
 }
 
 #[cfg(test)]
@@ -225,6 +508,44 @@ fn test_fetch_sync_string_with_redirect() {
   });
 }
 
+#[test]
+fn test_fetch_string_once_auth_tokens() {
+  use hyper::service::service_fn_ok;
+  use hyper::Server;
+
+  tokio_util::init(|| {
+    // A server which only answers requests with the right credentials.
+    let server = Server::bind(&([127, 0, 0, 1], 0).into()).serve(|| {
+      service_fn_ok(|req: Request<Body>| {
+        let mut response = Response::new(Body::from("ok"));
+        if req.headers().get(AUTHORIZATION).map_or(true, |v| v != "Bearer abc")
+        {
+          *response.status_mut() = StatusCode::UNAUTHORIZED;
+        }
+        response
+      })
+    });
+    let port = server.local_addr().port();
+    tokio::spawn(server.map_err(|e| panic!("server error: {}", e)));
+
+    let url = format!("http://127.0.0.1:{}/mod.ts", port);
+    let fetch = |auth_tokens: &str| {
+      fetch_string_once_with_auth_tokens(
+        url.parse().unwrap(),
+        &CacheHeaders::default(),
+        &AuthTokens::new(auth_tokens),
+      ).wait()
+    };
+    assert!(fetch("").is_err());
+    // Plain http needs an explicit opt-in.
+    assert!(fetch(&format!("abc@127.0.0.1:{}", port)).is_err());
+    match fetch(&format!("abc@http://127.0.0.1:{}", port)).unwrap() {
+      FetchOnceResult::Code(code, _, _) => assert_eq!(code, "ok"),
+      _ => panic!("expected code"),
+    }
+  });
+}
+
 #[test]
 fn test_resolve_uri_from_location_full_1() {
   let url = "http://deno.land".parse::<Uri>().unwrap();
@@ -262,3 +583,140 @@ fn test_resolve_uri_from_location_relative_3() {
   assert_eq!(new_uri.host().unwrap(), "deno.land");
   assert_eq!(new_uri.path(), "/z");
 }
+
+#[test]
+fn test_tls_config_alpn() {
+  let config = tls_config();
+  assert_eq!(
+    config.alpn_protocols,
+    vec![b"h2".to_vec(), b"http/1.1".to_vec()]
+  );
+}
+
+#[test]
+fn test_request_http2_prior_knowledge() {
+  use hyper::service::service_fn_ok;
+  use hyper::Server;
+  tokio_util::init(|| {
+    // An h2c server, which only accepts HTTP/2 without negotiation.
+    let addr = ([127, 0, 0, 1], 0).into();
+    let server = Server::bind(&addr)
+      .http2_only(true)
+      .serve(|| service_fn_ok(|_req| Response::new(Body::from("h2c"))));
+    let port = server.local_addr().port();
+    tokio::spawn(server.map_err(|e| panic!("server error: {}", e)));
+
+    let url = format!("http://127.0.0.1:{}/", port);
+    let req = Request::get(url.as_str()).body(Body::empty()).unwrap();
+    let response = request(req, None, true).wait().unwrap();
+    assert_eq!(response.version(), hyper::Version::HTTP_2);
+    let body = response.into_body().concat2().wait().unwrap();
+    assert_eq!(&body[..], b"h2c");
+
+    // The same server can't be reached with plain HTTP/1.1.
+    let req = Request::get(url.as_str()).body(Body::empty()).unwrap();
+    assert!(request(req, None, false).wait().is_err());
+  });
+}
+
+#[test]
+fn test_create_tls_config_ca_file() {
+  let default_roots = tls_config().root_store.len();
+  let config = create_tls_config(Some("tests/tls/RootCA.pem"), None).unwrap();
+  assert_eq!(config.root_store.len(), default_roots + 1);
+  assert_eq!(config.alpn_protocols, tls_config().alpn_protocols);
+}
+
+#[test]
+fn test_create_tls_config_bad_files() {
+  let err =
+    create_tls_config(Some("tests/tls/missing.pem"), None).unwrap_err();
+  assert_eq!(err.kind(), ErrorKind::NotFound);
+  // A private key is not a certificate.
+  let err =
+    create_tls_config(Some("tests/tls/RootCA.key"), None).unwrap_err();
+  assert_eq!(err.kind(), ErrorKind::InvalidData);
+  let err = create_tls_config(
+    None,
+    Some(("tests/tls/client.key", "tests/tls/client.key")),
+  ).unwrap_err();
+  assert_eq!(err.kind(), ErrorKind::InvalidData);
+  let err = create_tls_config(
+    None,
+    Some(("tests/tls/client.crt", "tests/tls/client.crt")),
+  ).unwrap_err();
+  assert_eq!(err.kind(), ErrorKind::InvalidData);
+}
+
+#[test]
+fn test_client_certificate() {
+  use hyper::server::conn::Http;
+  use hyper::service::service_fn_ok;
+  use std::sync::Arc;
+  use tokio::net::TcpListener;
+  use tokio_rustls::rustls::{
+    AllowAnyAuthenticatedClient, RootCertStore, ServerConfig,
+  };
+  use tokio_rustls::TlsAcceptor;
+
+  tokio_util::init(|| {
+    // A server which only accepts clients presenting a certificate signed by
+    // the test CA.
+    let mut client_roots = RootCertStore::empty();
+    client_roots
+      .add_pem_file(&mut open_pem_file("tests/tls/RootCA.pem").unwrap())
+      .unwrap();
+    let mut server_config =
+      ServerConfig::new(AllowAnyAuthenticatedClient::new(client_roots));
+    let server_certs =
+      pemfile::certs(&mut open_pem_file("tests/tls/localhost.crt").unwrap())
+        .unwrap();
+    let server_key = load_private_key("tests/tls/localhost.key").unwrap();
+    server_config
+      .set_single_cert(server_certs, server_key)
+      .unwrap();
+    let acceptor = TlsAcceptor::from(Arc::new(server_config));
+
+    let listener = TcpListener::bind(&([127, 0, 0, 1], 0).into()).unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let server = listener
+      .incoming()
+      .map_err(|e| panic!("accept error: {}", e))
+      .for_each(move |tcp| {
+        let conn = acceptor
+          .accept(tcp)
+          .and_then(|tls| {
+            Http::new()
+              .serve_connection(
+                tls,
+                service_fn_ok(|_req| Response::new(Body::from("mtls"))),
+              ).map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))
+          })
+          // Rejected handshakes are expected below.
+          .map_err(|_| ());
+        tokio::spawn(conn);
+        Ok(())
+      });
+    tokio::spawn(server);
+
+    let url = format!("https://localhost:{}/", port);
+    let get = |config: ClientConfig| {
+      let client = Client::builder().build(new_connector(config));
+      client
+        .get(url.parse().unwrap())
+        .map_err(DenoError::from)
+        .and_then(|response| response.into_body().concat2().from_err())
+        .wait()
+    };
+
+    let client_cert = Some(("tests/tls/client.crt", "tests/tls/client.key"));
+    let ca_file = Some("tests/tls/RootCA.pem");
+
+    let body = get(create_tls_config(ca_file, client_cert).unwrap()).unwrap();
+    assert_eq!(&body[..], b"mtls");
+    // The server's certificate isn't trusted without the CA.
+    assert!(get(create_tls_config(None, client_cert).unwrap()).is_err());
+    // The server rejects clients without a certificate.
+    assert!(get(create_tls_config(ca_file, None).unwrap()).is_err());
+  });
+}
@@ -1,7 +1,10 @@
+use crate::builtin_modules;
 use indexmap::IndexMap;
 use serde_json::Map;
 use serde_json::Value;
 use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::fmt;
 use std::fs;
 use url::Url;
 
@@ -18,6 +21,70 @@ impl ImportMapError {
   }
 }
 
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Severity {
+  /// The entry is left out of the import map.
+  Ignored,
+  /// The entry is used, but resolving it fails.
+  Error,
+  /// The entry works, but likely doesn't do what was intended.
+  Warning,
+}
+
+/// A problem with an entry of an import map, found while parsing it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ImportMapDiagnostic {
+  pub severity: Severity,
+  /// JSON path of the entry, e.g. `scopes["/app/"]["moment"][1]`.
+  pub path: String,
+  pub message: String,
+}
+
+impl fmt::Display for ImportMapDiagnostic {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    let severity = match self.severity {
+      Severity::Ignored => "ignored",
+      Severity::Error => "error",
+      Severity::Warning => "warning",
+    };
+    write!(f, "{} {}: {}", severity, self.path, self.message)
+  }
+}
+
+/// Collects the diagnostics of `ImportMap::check()`. The targets of the
+/// entries are only checked there, as it requires file system access.
+struct Diagnostics {
+  list: Vec<ImportMapDiagnostic>,
+  check_targets: bool,
+}
+
+impl Diagnostics {
+  fn add(&mut self, severity: Severity, path: &str, message: String) {
+    self.list.push(ImportMapDiagnostic {
+      severity,
+      path: path.to_string(),
+      message,
+    });
+  }
+}
+
+/// Appends a key to a JSON path, e.g. `imports` and `moment` become
+/// `imports["moment"]`.
+fn json_path(path: &str, key: &str) -> String {
+  format!("{}[{}]", path, Value::String(key.to_string()))
+}
+
+/// The scope, key and address an import map resolved a specifier with.
+#[derive(Debug, PartialEq)]
+pub struct ImportMapMatch {
+  /// Normalized scope prefix, or `None` for a match in "imports".
+  pub scope: Option<String>,
+  /// Normalized specifier key.
+  pub key: String,
+  /// The resolved URL.
+  pub address: String,
+}
+
 // NOTE: here is difference between deno and reference implementation - deno currently
 //  can't resolve URL with other schemes (eg. data:, about:, blob:)
 const SUPPORTED_FETCH_SCHEMES: [&str; 3] = ["http", "https", "file"];
@@ -25,7 +92,7 @@ const SUPPORTED_FETCH_SCHEMES: [&str; 3] = ["http", "https", "file"];
 type SpecifierMap = IndexMap<String, Vec<String>>;
 type ScopesMap = IndexMap<String, SpecifierMap>;
 
-#[derive(Debug)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct ImportMap {
   base_url: String,
   imports: SpecifierMap,
@@ -34,26 +101,81 @@ pub struct ImportMap {
 
 impl ImportMap {
   pub fn load(base_url: &str, file_name: &str) -> Result<Self, ImportMapError> {
+    let (_, json_string) = ImportMap::read(file_name)?;
+    ImportMap::from_json(base_url, &json_string)
+  }
+
+  /// Like `load()`, but resolves relative addresses against the location of
+  /// the import map file itself.
+  pub fn load_file(file_name: &str) -> Result<Self, ImportMapError> {
+    let (base_url, json_string) = ImportMap::read(file_name)?;
+    ImportMap::from_json(&base_url, &json_string)
+  }
+
+  /// Checks an import map file with `check()`.
+  pub fn check_file(
+    file_name: &str,
+  ) -> Result<Vec<ImportMapDiagnostic>, ImportMapError> {
+    let (base_url, json_string) = ImportMap::read(file_name)?;
+    ImportMap::check(&base_url, &json_string)
+  }
+
+  /// Reads an import map file, and returns its URL and contents.
+  fn read(file_name: &str) -> Result<(String, String), ImportMapError> {
     let cwd = std::env::current_dir().unwrap();
     let resolved_path = cwd.join(file_name);
     debug!(
       "Attempt to load import map: {}",
       resolved_path.to_str().unwrap()
     );
+    let base_url = Url::from_file_path(&resolved_path).unwrap().to_string();
 
     // Load the contents of import map
     match fs::read_to_string(&resolved_path) {
-      Ok(json_string) => ImportMap::from_json(base_url, &json_string),
-      _ => panic!(
+      Ok(json_string) => Ok((base_url, json_string)),
+      _ => Err(ImportMapError::new(&format!(
         "Error retrieving import map file at \"{}\"",
         resolved_path.to_str().unwrap()
-      ),
+      ))),
     }
   }
 
   pub fn from_json(
     base_url: &str,
     json_string: &str,
+  ) -> Result<Self, ImportMapError> {
+    let mut diagnostics = Diagnostics {
+      list: vec![],
+      check_targets: false,
+    };
+    let import_map = ImportMap::parse(base_url, json_string, &mut diagnostics)?;
+    for diagnostic in diagnostics.list {
+      if diagnostic.severity == Severity::Ignored {
+        eprintln!("Import map: {}", diagnostic);
+      }
+    }
+    Ok(import_map)
+  }
+
+  /// Parses an import map like `from_json()`, and returns the problems with
+  /// all of its entries. This includes `file:` addresses which don't exist.
+  /// Fails only if the import map can't be used at all.
+  pub fn check(
+    base_url: &str,
+    json_string: &str,
+  ) -> Result<Vec<ImportMapDiagnostic>, ImportMapError> {
+    let mut diagnostics = Diagnostics {
+      list: vec![],
+      check_targets: true,
+    };
+    ImportMap::parse(base_url, json_string, &mut diagnostics)?;
+    Ok(diagnostics.list)
+  }
+
+  fn parse(
+    base_url: &str,
+    json_string: &str,
+    diagnostics: &mut Diagnostics,
   ) -> Result<Self, ImportMapError> {
     let v: Value = match serde_json::from_str(json_string) {
       Ok(v) => v,
@@ -78,7 +200,12 @@ impl ImportMap {
         }
 
         let imports_map = imports_map.as_object().unwrap();
-        ImportMap::parse_specifier_map(imports_map, base_url)
+        ImportMap::parse_specifier_map(
+          imports_map,
+          base_url,
+          "imports",
+          diagnostics,
+        )
       }
       None => IndexMap::new(),
     };
@@ -92,7 +219,7 @@ impl ImportMap {
         }
 
         let scope_map = scope_map.as_object().unwrap();
-        ImportMap::parse_scope_map(scope_map, base_url)?
+        ImportMap::parse_scope_map(scope_map, base_url, diagnostics)?
       }
       None => IndexMap::new(),
     };
@@ -107,18 +234,20 @@ impl ImportMap {
   }
 
   fn try_url_like_specifier(specifier: &str, base: &str) -> Option<Url> {
-    // this should never fail
     if specifier.starts_with('/')
       || specifier.starts_with("./")
       || specifier.starts_with("../")
     {
       let base_url = Url::parse(base).unwrap();
-      let url = base_url.join(specifier).unwrap();
-      return Some(url);
+      // This only fails for bases like "std:blank", which can't have
+      // relative URLs.
+      return base_url.join(specifier).ok();
     }
 
     if let Ok(url) = Url::parse(specifier) {
-      if SUPPORTED_FETCH_SCHEMES.contains(&url.scheme()) {
+      if SUPPORTED_FETCH_SCHEMES.contains(&url.scheme())
+        || url.scheme() == builtin_modules::SCHEME
+      {
         return Some(url);
       }
     }
@@ -156,26 +285,70 @@ impl ImportMap {
   fn normalize_addresses(
     specifier_key: &str,
     base_url: &str,
-    potential_addresses: Vec<String>,
+    potential_addresses: Vec<(String, String)>,
+    diagnostics: &mut Diagnostics,
   ) -> Vec<String> {
     let mut normalized_addresses: Vec<String> = vec![];
 
-    for potential_address in potential_addresses {
+    for (path, potential_address) in potential_addresses {
       let url =
         match ImportMap::try_url_like_specifier(&potential_address, base_url) {
           Some(url) => url,
-          None => continue,
+          None => {
+            diagnostics.add(
+              Severity::Ignored,
+              &path,
+              format!(
+                "address {:?} must be an http, https or file URL, or start \
+                 with \"/\", \"./\" or \"../\"",
+                potential_address
+              ),
+            );
+            continue;
+          }
         };
 
       let url_string = url.to_string();
+      if specifier_key.ends_with('/')
+        && builtin_modules::is_builtin_url(&url_string)
+      {
+        diagnostics.add(
+          Severity::Ignored,
+          &path,
+          format!(
+            "built-in module {:?} can't be mapped to by the package \
+             specifier key {:?}",
+            url_string, specifier_key
+          ),
+        );
+        continue;
+      }
       if specifier_key.ends_with('/') && !url_string.ends_with('/') {
-        eprintln!(
-          "Invalid target address {:?} for package specifier {:?}.\
-           Package address targets must end with \"/\".",
-          url_string, specifier_key
+        diagnostics.add(
+          Severity::Ignored,
+          &path,
+          format!(
+            "address {:?} must end with \"/\" like the specifier key {:?}",
+            url_string, specifier_key
+          ),
         );
         continue;
       }
+      if !specifier_key.ends_with('/') && url_string.ends_with('/') {
+        diagnostics.add(
+          Severity::Warning,
+          &path,
+          format!(
+            "address {:?} ends with \"/\" but the specifier key {:?} \
+             doesn't, so it only maps the directory itself",
+            url_string, specifier_key
+          ),
+        );
+      }
+
+      if diagnostics.check_targets {
+        ImportMap::check_target(&url, &path, diagnostics);
+      }
 
       normalized_addresses.push(url_string);
     }
@@ -183,6 +356,26 @@ impl ImportMap {
     normalized_addresses
   }
 
+  /// Checks that a `file:` address exists. Other addresses would need to be
+  /// fetched, so they aren't checked.
+  fn check_target(url: &Url, path: &str, diagnostics: &mut Diagnostics) {
+    if url.scheme() != "file" {
+      return;
+    }
+    let exists = match url.to_file_path() {
+      Ok(file_path) if url.path().ends_with('/') => file_path.is_dir(),
+      Ok(file_path) => file_path.is_file(),
+      Err(_) => false,
+    };
+    if !exists {
+      diagnostics.add(
+        Severity::Error,
+        path,
+        format!("address {:?} doesn't exist", url.to_string()),
+      );
+    }
+  }
+
   /// Convert provided JSON map to valid SpecifierMap.
   ///
   /// From specification:
@@ -191,43 +384,89 @@ impl ImportMap {
   fn parse_specifier_map(
     json_map: &Map<String, Value>,
     base_url: &str,
+    path: &str,
+    diagnostics: &mut Diagnostics,
   ) -> SpecifierMap {
     let mut normalized_map: SpecifierMap = SpecifierMap::new();
+    // JSON paths of the entries, by normalized specifier key.
+    let mut key_paths: HashMap<String, String> = HashMap::new();
 
     // Order is preserved because of "preserve_order" feature of "serde_json".
     for (specifier_key, value) in json_map.iter() {
+      let key_path = json_path(path, specifier_key);
       let normalized_specifier_key =
         match ImportMap::normalize_specifier_key(specifier_key, base_url) {
           Some(s) => s,
-          None => continue,
+          None => {
+            diagnostics.add(
+              Severity::Ignored,
+              &key_path,
+              "specifier keys must not be empty".to_string(),
+            );
+            continue;
+          }
         };
 
-      let potential_addresses: Vec<String> = match value {
-        Value::String(address) => vec![address.to_string()],
+      // Addresses are paired with their JSON path.
+      let potential_addresses: Vec<(String, String)> = match value {
+        Value::String(address) => vec![(key_path.clone(), address.to_string())],
         Value::Array(address_array) => {
-          let mut string_addresses: Vec<String> = vec![];
+          let mut string_addresses: Vec<(String, String)> = vec![];
 
-          for address in address_array {
+          for (i, address) in address_array.iter().enumerate() {
+            let address_path = format!("{}[{}]", key_path, i);
             match address {
               Value::String(address) => {
-                string_addresses.push(address.to_string())
+                string_addresses.push((address_path, address.to_string()))
               }
-              _ => continue,
+              _ => diagnostics.add(
+                Severity::Ignored,
+                &address_path,
+                format!("address {} must be a string", address),
+              ),
             }
           }
 
           string_addresses
         }
         Value::Null => vec![],
-        _ => vec![],
+        _ => {
+          diagnostics.add(
+            Severity::Ignored,
+            &key_path,
+            format!("address {} must be a string or an array", value),
+          );
+          vec![]
+        }
       };
 
       let normalized_address_array = ImportMap::normalize_addresses(
         &normalized_specifier_key,
         base_url,
         potential_addresses,
+        diagnostics,
       );
 
+      if !normalized_address_array.is_empty() {
+        if let Err(err) = ImportMap::select_address(&normalized_address_array)
+        {
+          diagnostics.add(Severity::Error, &key_path, err.msg);
+        }
+      }
+
+      if let Some(other_path) =
+        key_paths.insert(normalized_specifier_key.clone(), key_path.clone())
+      {
+        diagnostics.add(
+          Severity::Warning,
+          &key_path,
+          format!(
+            "replaces {}, which has the same specifier key {:?}",
+            other_path, normalized_specifier_key
+          ),
+        );
+      }
+
       debug!(
         "normalized specifier {:?}; {:?}",
         normalized_specifier_key, normalized_address_array
@@ -257,8 +496,11 @@ impl ImportMap {
   fn parse_scope_map(
     scope_map: &Map<String, Value>,
     base_url: &str,
+    diagnostics: &mut Diagnostics,
   ) -> Result<ScopesMap, ImportMapError> {
     let mut normalized_map: ScopesMap = ScopesMap::new();
+    // JSON paths of the scopes, by normalized scope prefix.
+    let mut scope_paths: IndexMap<String, String> = IndexMap::new();
 
     // Order is preserved because of "preserve_order" feature of "serde_json".
     for (scope_prefix, potential_specifier_map) in scope_map.iter() {
@@ -271,28 +513,78 @@ impl ImportMap {
 
       let potential_specifier_map =
         potential_specifier_map.as_object().unwrap();
+      let scope_path = json_path("scopes", scope_prefix);
 
       let scope_prefix_url =
         match Url::parse(base_url).unwrap().join(scope_prefix) {
           Ok(url) => {
             if !SUPPORTED_FETCH_SCHEMES.contains(&url.scheme()) {
-              eprintln!(
-              "Invalid scope {:?}. Scope URLs must have a valid fetch scheme.",
-              url.to_string()
-            );
+              diagnostics.add(
+                Severity::Ignored,
+                &scope_path,
+                format!(
+                  "scope {:?} must be an http, https or file URL",
+                  url.to_string()
+                ),
+              );
               continue;
             }
             url.to_string()
           }
-          _ => continue,
+          Err(err) => {
+            diagnostics.add(
+              Severity::Ignored,
+              &scope_path,
+              format!("scope {:?} is not a valid URL: {}", scope_prefix, err),
+            );
+            continue;
+          }
         };
 
-      let norm_map =
-        ImportMap::parse_specifier_map(potential_specifier_map, base_url);
+      let norm_map = ImportMap::parse_specifier_map(
+        potential_specifier_map,
+        base_url,
+        &scope_path,
+        diagnostics,
+      );
+
+      if let Some(other_path) =
+        scope_paths.insert(scope_prefix_url.clone(), scope_path.clone())
+      {
+        diagnostics.add(
+          Severity::Warning,
+          &scope_path,
+          format!(
+            "replaces {}, which has the same scope prefix {:?}",
+            other_path, scope_prefix_url
+          ),
+        );
+      }
 
       normalized_map.insert(scope_prefix_url, norm_map);
     }
 
+    // A specifier which isn't mapped by a scope is looked up in the scopes
+    // containing it, which is easy to overlook in large import maps.
+    for (scope, path) in scope_paths.iter() {
+      for (outer_scope, outer_path) in scope_paths.iter() {
+        if scope != outer_scope
+          && outer_scope.ends_with('/')
+          && scope.starts_with(outer_scope.as_str())
+        {
+          diagnostics.add(
+            Severity::Warning,
+            path,
+            format!(
+              "overlaps with {}; specifiers which this scope doesn't map \
+               are looked up there",
+              outer_path
+            ),
+          );
+        }
+      }
+    }
+
     // Sort in longest and alphabetical order.
     normalized_map.sort_by(|k1, _v1, k2, _v2| {
       if k1.len() > k2.len() {
@@ -307,20 +599,20 @@ impl ImportMap {
     Ok(normalized_map)
   }
 
-  pub fn resolve_scopes_match(
-    scopes: &ScopesMap,
+  /// Returns the matching scope prefix and specifier key along with the
+  /// resolved URL.
+  fn resolve_scopes_match<'a>(
+    scopes: &'a ScopesMap,
     normalized_specifier: &str,
     referrer: &str,
-  ) -> Result<Option<String>, ImportMapError> {
+  ) -> Option<(&'a str, &'a str, String)> {
     // exact-match
-    if let Some(scope_imports) = scopes.get(referrer) {
-      if let Ok(scope_match) =
+    if let Some((_, scope_prefix, scope_imports)) = scopes.get_full(referrer) {
+      // Return only if there was actual match (not None).
+      if let Ok(Some((key, address))) =
         ImportMap::resolve_imports_match(scope_imports, normalized_specifier)
       {
-        // Return only if there was actual match (not None).
-        if scope_match.is_some() {
-          return Ok(scope_match);
-        }
+        return Some((scope_prefix, key, address));
       }
     }
 
@@ -328,45 +620,73 @@ impl ImportMap {
       if normalized_scope_key.ends_with('/')
         && referrer.starts_with(normalized_scope_key)
       {
-        if let Ok(scope_match) =
+        // Return only if there was actual match (not None).
+        if let Ok(Some((key, address))) =
           ImportMap::resolve_imports_match(scope_imports, normalized_specifier)
         {
-          // Return only if there was actual match (not None).
-          if scope_match.is_some() {
-            return Ok(scope_match);
-          }
+          return Some((normalized_scope_key, key, address));
         }
       }
     }
 
-    Ok(None)
+    None
+  }
+
+  /// Picks the address of a mapping. The only supported fallback is a
+  /// built-in module followed by the URL to use if Deno doesn't have it.
+  fn select_address(addresses: &[String]) -> Result<&str, ImportMapError> {
+    match addresses {
+      [address] if builtin_modules::is_builtin_url(address) => {
+        if builtin_modules::has(address) {
+          Ok(address)
+        } else {
+          Err(ImportMapError::new(&format!(
+            "The {:?} built-in module is not implemented.",
+            address
+          )))
+        }
+      }
+      [address] => Ok(address),
+      [built_in, fallback]
+        if builtin_modules::is_builtin_url(built_in)
+          && !builtin_modules::is_builtin_url(fallback) =>
+      {
+        if builtin_modules::has(built_in) {
+          Ok(built_in)
+        } else {
+          Ok(fallback)
+        }
+      }
+      _ => Err(ImportMapError::new(
+        "Multi-address mappings are only supported for a built-in module \
+         followed by a fallback URL",
+      )),
+    }
   }
 
+  /// Returns the matching specifier key along with the resolved URL.
   // TODO: https://github.com/WICG/import-maps/issues/73#issuecomment-439327758
   // for some more optimized candidate implementations.
-  pub fn resolve_imports_match(
-    imports: &SpecifierMap,
+  fn resolve_imports_match<'a>(
+    imports: &'a SpecifierMap,
     normalized_specifier: &str,
-  ) -> Result<Option<String>, ImportMapError> {
+  ) -> Result<Option<(&'a str, String)>, ImportMapError> {
     // exact-match
-    if let Some(address_vec) = imports.get(normalized_specifier) {
+    if let Some((_, specifier_key, address_vec)) =
+      imports.get_full(normalized_specifier)
+    {
       if address_vec.is_empty() {
         return Err(ImportMapError::new(&format!(
           "Specifier {:?} was mapped to no addresses.",
           normalized_specifier
         )));
-      } else if address_vec.len() == 1 {
-        let address = address_vec.first().unwrap();
-        debug!(
-          "Specifier {:?} was mapped to {:?}.",
-          normalized_specifier, address
-        );
-        return Ok(Some(address.to_string()));
-      } else {
-        return Err(ImportMapError::new(
-          "Multi-address mappings are not yet supported",
-        ));
       }
+      let address = ImportMap::select_address(address_vec)?;
+      debug!(
+        "Specifier {:?} was mapped to {:?}.",
+        normalized_specifier, address
+      );
+      return Ok(Some((specifier_key, address.to_string())));
     }
 
     // package-prefix match
@@ -379,24 +699,20 @@ impl ImportMap {
       {
         if address_vec.is_empty() {
           return Err(ImportMapError::new(&format!("Specifier {:?} was mapped to no addresses (via prefix specifier key {:?}).", normalized_specifier, specifier_key)));
-        } else if address_vec.len() == 1 {
-          let address = address_vec.first().unwrap();
-          let after_prefix = &normalized_specifier[specifier_key.len()..];
-
-          if let Ok(base_url) = Url::parse(address) {
-            if let Ok(url) = base_url.join(after_prefix) {
-              let resolved_url = url.to_string();
-              debug!("Specifier {:?} was mapped to {:?} (via prefix specifier key {:?}).", normalized_specifier, resolved_url, address);
-              return Ok(Some(resolved_url));
-            }
+        }
+        // Built-in modules were left out when parsing package mappings.
+        let address = ImportMap::select_address(address_vec)?;
+        let after_prefix = &normalized_specifier[specifier_key.len()..];
+
+        if let Ok(base_url) = Url::parse(address) {
+          if let Ok(url) = base_url.join(after_prefix) {
+            let resolved_url = url.to_string();
+            debug!("Specifier {:?} was mapped to {:?} (via prefix specifier key {:?}).", normalized_specifier, resolved_url, address);
+            return Ok(Some((specifier_key, resolved_url)));
           }
-
-          unreachable!();
-        } else {
-          return Err(ImportMapError::new(
-            "Multi-address mappings are not yet supported",
-          ));
         }
+
+        unreachable!();
       }
     }
 
@@ -408,53 +724,78 @@ impl ImportMap {
     Ok(None)
   }
 
-  // TODO: add support for built-in modules
-  /// Currently we support two types of specifiers: URL (http://, https://, file://)
-  /// and "bare" (moment, jquery, lodash)
-  ///
-  /// Scenarios:
-  ///   1. import resolved using import map -> String
-  ///   2. import restricted by import map -> ImportMapError
-  ///   3. import not mapped -> None
-  pub fn resolve(
+  /// Resolves `specifier` like `resolve()`, but returns which scope and
+  /// specifier key matched. Returns `None` if the import map doesn't map the
+  /// specifier.
+  pub fn resolve_match(
     &self,
     specifier: &str,
     referrer: &str,
-  ) -> Result<Option<String>, ImportMapError> {
-    let resolved_url: Option<Url> =
-      ImportMap::try_url_like_specifier(specifier, referrer);
-    let normalized_specifier = match &resolved_url {
-      Some(url) => url.to_string(),
-      None => specifier.to_string(),
-    };
+  ) -> Result<Option<ImportMapMatch>, ImportMapError> {
+    let normalized_specifier =
+      match ImportMap::try_url_like_specifier(specifier, referrer) {
+        Some(url) => url.to_string(),
+        None => specifier.to_string(),
+      };
 
-    let scopes_match = ImportMap::resolve_scopes_match(
+    // match found in scopes map
+    if let Some((scope, key, address)) = ImportMap::resolve_scopes_match(
       &self.scopes,
       &normalized_specifier,
-      &referrer.to_string(),
-    )?;
-
-    // match found in scopes map
-    if scopes_match.is_some() {
-      return Ok(scopes_match);
+      referrer,
+    ) {
+      return Ok(Some(ImportMapMatch {
+        scope: Some(scope.to_string()),
+        key: key.to_string(),
+        address,
+      }));
     }
 
+    // match found in import map
     let imports_match =
       ImportMap::resolve_imports_match(&self.imports, &normalized_specifier)?;
+    Ok(imports_match.map(|(key, address)| ImportMapMatch {
+      scope: None,
+      key: key.to_string(),
+      address,
+    }))
+  }
 
-    // match found in import map
-    if imports_match.is_some() {
-      return Ok(imports_match);
+  /// Currently we support three types of specifiers: URL (http://, https://, file://),
+  /// built-in modules (std:blank) and "bare" (moment, jquery, lodash)
+  ///
+  /// Scenarios:
+  ///   1. import resolved using import map -> String
+  ///   2. import restricted by import map -> ImportMapError
+  ///   3. import not mapped -> None
+  pub fn resolve(
+    &self,
+    specifier: &str,
+    referrer: &str,
+  ) -> Result<Option<String>, ImportMapError> {
+    if let Some(import_match) = self.resolve_match(specifier, referrer)? {
+      return Ok(Some(import_match.address));
     }
 
     // no match in import map but we got resolvable URL
-    if let Some(resolved_url) = resolved_url {
-      return Ok(Some(resolved_url.to_string()));
+    if let Some(resolved_url) =
+      ImportMap::try_url_like_specifier(specifier, referrer)
+    {
+      let resolved_url = resolved_url.to_string();
+      if builtin_modules::is_builtin_url(&resolved_url)
+        && !builtin_modules::has(&resolved_url)
+      {
+        return Err(ImportMapError::new(&format!(
+          "The {:?} built-in module is not implemented.",
+          resolved_url
+        )));
+      }
+      return Ok(Some(resolved_url));
     }
 
     Err(ImportMapError::new(&format!(
       "Unmapped bare specifier {:?}",
-      normalized_specifier
+      specifier
     )))
   }
 }
@@ -2101,12 +2442,16 @@ mod tests {
   }
 
   #[test]
-  fn cant_resolve_to_built_in() {
+  fn resolve_built_in() {
     let base_url = "https://example.com/app/main.ts";
 
     let import_map = ImportMap::from_json(base_url, "{}").unwrap();
 
-    assert!(import_map.resolve("std:blank", base_url).is_err());
+    assert_eq!(
+      import_map.resolve("std:blank", base_url).unwrap(),
+      Some("std:blank".to_string())
+    );
+    assert!(import_map.resolve("std:none", base_url).is_err());
   }
 
   #[test]
@@ -2130,4 +2475,153 @@ mod tests {
       Some("https://example.com/app/none.mjs".to_string())
     );
   }
+
+  #[test]
+  fn check_diagnostics() {
+    let temp_dir = tempfile::TempDir::new().expect("tempdir fail");
+    fs::write(temp_dir.path().join("a.ts"), "").unwrap();
+    fs::create_dir(temp_dir.path().join("lib")).unwrap();
+    let base_url = Url::from_file_path(temp_dir.path().join("map.json"))
+      .unwrap()
+      .to_string();
+
+    let json_map = r#"{
+      "imports": {
+        "": "./a.ts",
+        "a": "./a.ts",
+        "missing": "./missing.ts",
+        "lib/": "./lib",
+        "lib2/": "./lib/",
+        "dir": "./lib/",
+        "bare": "lodash",
+        "num": 1,
+        "list": ["./a.ts", 2],
+        "/a.ts": "./a.ts",
+        "./../../../../../../../../a.ts": "https://example.com/a.ts",
+        "blocked": null
+      },
+      "scopes": {
+        "scope/": { "a": "./a.ts" },
+        "scope/inner/": { "": "./a.ts" },
+        "data:text/plain,x/": {}
+      }
+    }"#;
+    let diagnostics: Vec<(Severity, String)> =
+      ImportMap::check(&base_url, json_map)
+        .unwrap()
+        .into_iter()
+        .map(|d| (d.severity, d.path))
+        .collect();
+    let expected: Vec<(Severity, &str)> = vec![
+      (Severity::Ignored, r#"imports[""]"#),
+      (Severity::Error, r#"imports["missing"]"#),
+      (Severity::Ignored, r#"imports["lib/"]"#),
+      (Severity::Warning, r#"imports["dir"]"#),
+      (Severity::Ignored, r#"imports["bare"]"#),
+      (Severity::Ignored, r#"imports["num"]"#),
+      (Severity::Ignored, r#"imports["list"][1]"#),
+      (
+        Severity::Warning,
+        r#"imports["./../../../../../../../../a.ts"]"#,
+      ),
+      (Severity::Ignored, r#"scopes["scope/inner/"][""]"#),
+      (Severity::Ignored, r#"scopes["data:text/plain,x/"]"#),
+      (Severity::Warning, r#"scopes["scope/inner/"]"#),
+    ];
+    assert_eq!(
+      diagnostics,
+      expected
+        .into_iter()
+        .map(|(severity, path)| (severity, path.to_string()))
+        .collect::<Vec<_>>()
+    );
+
+    // Only ignored entries are reported when the import map is used, and
+    // targets aren't checked.
+    assert!(ImportMap::from_json(&base_url, json_map).is_ok());
+  }
+
+  #[test]
+  fn resolve_match() {
+    let base_url = "https://example.com/app/main.ts";
+
+    let json_map = r#"{
+      "imports": {
+        "moment": "./moment.ts",
+        "lodash/": "./lodash/"
+      },
+      "scopes": {
+        "scope/": {
+          "moment": "./scoped_moment.ts"
+        }
+      }
+    }"#;
+    let import_map = ImportMap::from_json(base_url, json_map).unwrap();
+
+    assert_eq!(
+      import_map
+        .resolve_match("moment", "https://example.com/app/scope/mod.ts")
+        .unwrap(),
+      Some(ImportMapMatch {
+        scope: Some("https://example.com/app/scope/".to_string()),
+        key: "moment".to_string(),
+        address: "https://example.com/app/scoped_moment.ts".to_string(),
+      })
+    );
+    assert_eq!(
+      import_map.resolve_match("lodash/fp.ts", base_url).unwrap(),
+      Some(ImportMapMatch {
+        scope: None,
+        key: "lodash/".to_string(),
+        address: "https://example.com/app/lodash/fp.ts".to_string(),
+      })
+    );
+    assert_eq!(import_map.resolve_match("./foo.ts", base_url).unwrap(), None);
+  }
+
+  #[test]
+  fn resolve_builtins_fallback() {
+    let base_url = "https://example.com/app/main.ts";
+
+    let json_map = r#"{
+      "imports": {
+        "blank": ["std:blank", "./blank.mjs"],
+        "none": ["std:none", "./none.mjs"],
+        "only-none": "std:none",
+        "two-urls": ["./a.mjs", "./b.mjs"],
+        "pkg/": "std:blank/"
+      }
+    }"#;
+    let import_map = ImportMap::from_json(base_url, json_map).unwrap();
+
+    // Built-in modules are used if Deno has them.
+    assert_eq!(
+      import_map.resolve("blank", base_url).unwrap(),
+      Some("std:blank".to_string())
+    );
+    // Otherwise the fallback URL is.
+    assert_eq!(
+      import_map.resolve("none", base_url).unwrap(),
+      Some("https://example.com/app/none.mjs".to_string())
+    );
+    assert!(import_map.resolve("only-none", base_url).is_err());
+    assert!(import_map.resolve("two-urls", base_url).is_err());
+    // Built-in modules can't be used for packages.
+    assert!(import_map.imports.get("pkg/").unwrap().is_empty());
+
+    let diagnostics: Vec<(Severity, String)> =
+      ImportMap::check(base_url, json_map)
+        .unwrap()
+        .into_iter()
+        .map(|d| (d.severity, d.path))
+        .collect();
+    assert_eq!(
+      diagnostics,
+      vec![
+        (Severity::Error, r#"imports["only-none"]"#.to_string()),
+        (Severity::Error, r#"imports["two-urls"]"#.to_string()),
+        (Severity::Ignored, r#"imports["pkg/"]"#.to_string()),
+      ]
+    );
+  }
 }
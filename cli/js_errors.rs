@@ -24,7 +24,7 @@ pub trait SourceMapGetter {
 /// find a SourceMap.
 type CachedMaps = HashMap<String, Option<SourceMap>>;
 
-struct SourceMap {
+pub struct SourceMap {
   mappings: Mappings,
   sources: Vec<String>,
 }
@@ -135,6 +135,29 @@ impl SourceMap {
       _ => None,
     }
   }
+
+  /// Returns the original source, line and column of a position in the
+  /// generated code, if there is a mapping for its line.
+  pub fn original_location(
+    &self,
+    line: u32,
+    column: u32,
+  ) -> Option<(&str, u32, u32)> {
+    let mapping =
+      self
+        .mappings
+        .original_location_for(line, column, Bias::default())?;
+    // The closest mapping can be on one of the previous lines.
+    if mapping.generated_line != line {
+      return None;
+    }
+    let original = mapping.original.as_ref()?;
+    Some((
+      self.sources[original.source as usize].as_str(),
+      original.original_line,
+      original.original_column,
+    ))
+  }
 }
 
 fn frame_apply_source_map<G: SourceMapGetter>(
@@ -232,7 +255,9 @@ fn builtin_source_map(script_name: &str) -> Option<Vec<u8>> {
   }
 }
 
-fn parse_map_string<G: SourceMapGetter>(
+/// Returns the source map of `script_name`, including those of the bundles
+/// built into deno.
+pub fn parse_map_string<G: SourceMapGetter>(
   script_name: &str,
   getter: &G,
 ) -> Option<SourceMap> {
@@ -0,0 +1,193 @@
+// Copyright 2018-2019 the Deno authors. All rights reserved. MIT license.
+//! The lock file given with `--lock` maps the URL of every remote module to
+//! the SHA-256 hash of its source code, so that later runs can verify they
+//! load exactly the same code.
+use crate::errors;
+use crate::errors::DenoResult;
+use crate::errors::ErrorKind;
+use ring;
+use serde_json;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+lazy_static! {
+  /// User workers load modules with their own `Lockfile` instance, so
+  /// writes to the file are serialized and merged with its current contents.
+  static ref WRITE_LOCK: Mutex<()> = Mutex::new(());
+}
+
+pub struct Lockfile {
+  pub filename: PathBuf,
+  /// When true (`--lock-write`) hashes are recorded instead of checked.
+  write: bool,
+  map: Mutex<BTreeMap<String, String>>,
+}
+
+impl Lockfile {
+  /// Reads the lock file at `filename`. It may only be missing if `write` is
+  /// set, in which case it will be created.
+  pub fn new(filename: PathBuf, write: bool) -> DenoResult<Lockfile> {
+    let map = match read_map(&filename) {
+      Ok(map) => map,
+      Err(ref err) if write && err.kind() == ErrorKind::NotFound => {
+        BTreeMap::new()
+      }
+      Err(err) => return Err(err),
+    };
+    Ok(Lockfile {
+      filename,
+      write,
+      map: Mutex::new(map),
+    })
+  }
+
+  /// Checks that `code` matches the hash recorded for `url`. With
+  /// `--lock-write` the hash is recorded instead, and the file is updated.
+  pub fn check_or_insert(&self, url: &str, code: &[u8]) -> DenoResult<()> {
+    let hash = sha256_hex(code);
+    let mut map = self.map.lock().unwrap();
+    if self.write {
+      if map.get(url) != Some(&hash) {
+        map.insert(url.to_string(), hash);
+        self.save(&map)?;
+      }
+      return Ok(());
+    }
+    match map.get(url) {
+      Some(expected) if *expected == hash => Ok(()),
+      Some(_) => Err(errors::new(
+        ErrorKind::InvalidData,
+        format!(
+          "Integrity check failed for \"{}\": its hash does not match the one in {}. Use --lock-write to update the lock file.",
+          url,
+          self.filename.display()
+        ),
+      )),
+      None => Err(errors::new(
+        ErrorKind::InvalidData,
+        format!(
+          "\"{}\" is not in the lock file {}. Use --lock-write to add it.",
+          url,
+          self.filename.display()
+        ),
+      )),
+    }
+  }
+
+  fn save(&self, map: &BTreeMap<String, String>) -> DenoResult<()> {
+    let _guard = WRITE_LOCK.lock().unwrap();
+    let mut merged = match read_map(&self.filename) {
+      Ok(on_disk) => on_disk,
+      Err(ref err) if err.kind() == ErrorKind::NotFound => BTreeMap::new(),
+      Err(err) => return Err(err),
+    };
+    for (url, hash) in map.iter() {
+      merged.insert(url.clone(), hash.clone());
+    }
+    let mut obj = serde_json::Map::new();
+    for (url, hash) in merged {
+      obj.insert(url, serde_json::Value::String(hash));
+    }
+    let mut s = serde_json::to_string_pretty(&obj).unwrap();
+    s.push('\n');
+    fs::write(&self.filename, s)?;
+    Ok(())
+  }
+}
+
+fn read_map(filename: &PathBuf) -> DenoResult<BTreeMap<String, String>> {
+  let s = fs::read_to_string(filename)?;
+  let invalid = || {
+    errors::new(
+      ErrorKind::InvalidData,
+      format!("Invalid lock file {}", filename.display()),
+    )
+  };
+  let obj = match serde_json::from_str::<serde_json::Value>(&s) {
+    Ok(serde_json::Value::Object(obj)) => obj,
+    _ => return Err(invalid()),
+  };
+  let mut map = BTreeMap::new();
+  for (url, hash) in obj {
+    match hash {
+      serde_json::Value::String(hash) => map.insert(url, hash),
+      _ => return Err(invalid()),
+    };
+  }
+  Ok(map)
+}
+
+/// Hex encoded SHA-256 of `data`. Besides the lock file, DenoDir uses it for
+/// the source hashes of compiled code and to name cached `data:` modules.
+pub(crate) fn sha256_hex(data: &[u8]) -> String {
+  let digest = ring::digest::digest(&ring::digest::SHA256, data);
+  let mut out = String::new();
+  for byte in digest.as_ref() {
+    write!(&mut out, "{:02x}", byte).unwrap();
+  }
+  out
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use tempfile::TempDir;
+
+  #[test]
+  fn test_sha256_hex() {
+    assert_eq!(
+      sha256_hex(b"hello world"),
+      "b94d27b9934d3e08a52e52d7da7dabfac484efe37a5380ee9088f7ace2efcde9"
+    );
+  }
+
+  #[test]
+  fn test_lockfile_write_then_check() {
+    let temp_dir = TempDir::new().unwrap();
+    let filename = temp_dir.path().join("lock.json");
+    let url = "https://deno.land/x/mod.ts";
+
+    // The lock file must exist unless it is being written.
+    assert!(Lockfile::new(filename.clone(), false).is_err());
+
+    let lockfile = Lockfile::new(filename.clone(), true).unwrap();
+    lockfile.check_or_insert(url, b"export {};").unwrap();
+    let s = fs::read_to_string(&filename).unwrap();
+    assert!(s.contains(url));
+
+    let lockfile = Lockfile::new(filename.clone(), false).unwrap();
+    assert!(lockfile.check_or_insert(url, b"export {};").is_ok());
+    let err = lockfile.check_or_insert(url, b"export {1};").unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::InvalidData);
+    assert!(err.to_string().contains(url));
+    let err = lockfile
+      .check_or_insert("https://deno.land/x/other.ts", b"")
+      .unwrap_err();
+    assert!(err.to_string().contains("https://deno.land/x/other.ts"));
+
+    // Writing again updates the hash and keeps other entries.
+    let other = Lockfile::new(filename.clone(), true).unwrap();
+    other.check_or_insert("https://deno.land/x/other.ts", b"").unwrap();
+    let lockfile = Lockfile::new(filename.clone(), true).unwrap();
+    lockfile.check_or_insert(url, b"export {1};").unwrap();
+    let lockfile = Lockfile::new(filename.clone(), false).unwrap();
+    assert!(lockfile.check_or_insert(url, b"export {1};").is_ok());
+    assert!(
+      lockfile
+        .check_or_insert("https://deno.land/x/other.ts", b"")
+        .is_ok()
+    );
+  }
+
+  #[test]
+  fn test_lockfile_invalid() {
+    let temp_dir = TempDir::new().unwrap();
+    let filename = temp_dir.path().join("lock.json");
+    fs::write(&filename, "[]").unwrap();
+    let err = Lockfile::new(filename, false).err().unwrap();
+    assert_eq!(err.kind(), ErrorKind::InvalidData);
+  }
+}
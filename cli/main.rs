@@ -6,6 +6,8 @@ extern crate log;
 #[macro_use]
 extern crate futures;
 #[macro_use]
+extern crate serde_derive;
+#[macro_use]
 extern crate serde_json;
 extern crate clap;
 extern crate deno;
@@ -15,11 +17,17 @@ extern crate nix;
 extern crate rand;
 
 mod ansi;
+mod auth_tokens;
+mod bundle;
+mod builtin_modules;
 pub mod compiler;
+mod cookie_jar;
+mod coverage;
 pub mod deno_dir;
 pub mod diagnostics;
 mod dispatch_minimal;
 pub mod errors;
+mod file_watcher;
 pub mod flags;
 mod fs;
 mod global_timer;
@@ -27,6 +35,7 @@ mod http_body;
 mod http_util;
 mod import_map;
 pub mod js_errors;
+mod lockfile;
 pub mod msg;
 pub mod msg_util;
 pub mod ops;
@@ -35,28 +44,62 @@ mod progress;
 mod repl;
 pub mod resolve_addr;
 pub mod resources;
+pub mod scheme_handlers;
 mod signal;
+mod snapshot;
+mod standalone;
 mod startup_data;
+mod test_runner;
 pub mod state;
 mod tokio_util;
 mod tokio_write;
+mod vendor;
 pub mod version;
 pub mod worker;
 
+use crate::auth_tokens::AuthTokens;
 use crate::compiler::bundle_async;
+use crate::compiler::check_async;
+use crate::compiler::CompilerWorker;
+use crate::coverage::Coverage;
+use crate::deno_dir::DenoDir;
+use crate::diagnostics::Diagnostic;
+use crate::errors::DenoError;
 use crate::errors::RustOrJsError;
+use crate::import_map::ImportMap;
+use crate::import_map::Severity;
 use crate::progress::Progress;
+use crate::resources::ResourceId;
 use crate::state::ThreadSafeState;
+use crate::test_runner::FileResult;
+use crate::test_runner::Reporter;
+use crate::test_runner::TestMessage;
+use crate::test_runner::TestResult;
 use crate::worker::root_specifier_to_url;
 use crate::worker::Worker;
 use deno::v8_set_flags;
+use deno::StartupData;
 use flags::DenoFlags;
 use flags::DenoSubcommand;
 use futures::future;
+use futures::future::Loop;
 use futures::lazy;
+use futures::sync::oneshot;
 use futures::Future;
+use futures::Stream;
 use log::{LevelFilter, Metadata, Record};
+use std::collections::HashSet;
 use std::env;
+use std::path::Path;
+use std::path::PathBuf;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::time::Instant;
+use std::time::SystemTime;
+use tempfile::TempDir;
+use url::Url;
 
 static LOGGER: Logger = Logger;
 
@@ -96,6 +139,41 @@ where
   }
 }
 
+/// Applies `--cert` (or `DENO_CERT`) and `--client-cert`/`--client-key` to
+/// the HTTP client shared by module fetching and `fetch()`.
+fn init_tls(flags: &DenoFlags) {
+  let ca_file = flags
+    .ca_file
+    .clone()
+    .or_else(|| env::var("DENO_CERT").ok())
+    .filter(|ca_file| !ca_file.is_empty());
+  let client_cert_and_key =
+    match (&flags.client_cert_file, &flags.client_key_file) {
+      (Some(cert), Some(key)) => Some((cert.as_str(), key.as_str())),
+      _ => None,
+    };
+  if ca_file.is_none() && client_cert_and_key.is_none() {
+    return;
+  }
+  let ca_file = ca_file.as_ref().map(String::as_str);
+  match http_util::create_tls_config(ca_file, client_cert_and_key) {
+    Ok(config) => http_util::set_tls_config(config),
+    Err(err) => print_err_and_exit(err.into()),
+  }
+}
+
+fn init_auth_tokens() {
+  if let Ok(auth_tokens) = env::var("DENO_AUTH_TOKENS") {
+    http_util::set_auth_tokens(AuthTokens::new(&auth_tokens));
+  }
+}
+
+fn init_scheme_handlers() {
+  if let Ok(scheme_handlers) = env::var("DENO_SCHEME_HANDLERS") {
+    scheme_handlers::register_commands(&scheme_handlers);
+  }
+}
+
 pub fn print_file_info(
   worker: Worker,
   url: &str,
@@ -148,6 +226,25 @@ fn create_worker_and_state(
   flags: DenoFlags,
   argv: Vec<String>,
 ) -> (Worker, ThreadSafeState) {
+  create_worker_and_state_with(flags, argv, startup_data::deno_isolate_init())
+}
+
+/// Like `create_worker_and_state()`, but the worker starts from
+/// `startup_data` instead of the runtime's snapshot.
+fn create_worker_and_state_with(
+  flags: DenoFlags,
+  argv: Vec<String>,
+  startup_data: StartupData,
+) -> (Worker, ThreadSafeState) {
+  let state = create_state(flags, argv);
+  let worker = Worker::new("main".to_string(), startup_data, state.clone());
+
+  (worker, state)
+}
+
+/// Creates the state of the main worker, for subcommands which only use the
+/// compiler and don't run any code.
+fn create_state(flags: DenoFlags, argv: Vec<String>) -> ThreadSafeState {
   let progress = Progress::new();
   progress.set_callback(|done, completed, total, msg| {
     if !done {
@@ -161,14 +258,7 @@ fn create_worker_and_state(
       eprintln!();
     }
   });
-  let state = ThreadSafeState::new(flags, argv, ops::op_selector_std, progress);
-  let worker = Worker::new(
-    "main".to_string(),
-    startup_data::deno_isolate_init(),
-    state.clone(),
-  );
-
-  (worker, state)
+  ThreadSafeState::new(flags, argv, ops::op_selector_std, progress)
 }
 
 fn types_command() {
@@ -213,6 +303,169 @@ fn fetch_or_info_command(
   tokio_util::run(main_future);
 }
 
+fn vendor_command(flags: DenoFlags, argv: Vec<String>) {
+  let (mut worker, state) = create_worker_and_state(flags, argv);
+
+  let main_module = state.main_module().unwrap();
+  let main_future = lazy(move || {
+    // Setup runtime.
+    js_check(worker.execute("denoMain()"));
+    debug!("main_module {}", main_module);
+
+    let main_url = root_specifier_to_url(&main_module).unwrap();
+
+    worker
+      .execute_mod_async(&main_url, true)
+      .map_err(print_err_and_exit)
+      .and_then(move |()| {
+        let maybe_deps = worker.modules.lock().unwrap().deps(main_url.as_str());
+        let module_names = match maybe_deps {
+          Some(deps) => vendor::remote_modules(&deps),
+          None => {
+            eprintln!("Cannot retrieve full dependency graph");
+            std::process::exit(1);
+          }
+        };
+        let vendor_dir = Path::new(&state.argv[2]);
+        match vendor::vendor(&state.dir, &module_names, vendor_dir) {
+          Ok(count) => {
+            println!(
+              "Vendored {} modules into {}. Run with --importmap={}",
+              count,
+              vendor_dir.display(),
+              vendor_dir.join(vendor::IMPORT_MAP_FILENAME).display()
+            );
+            Ok(())
+          }
+          Err(err) => {
+            print_err_and_exit(err.into());
+            Err(())
+          }
+        }
+      })
+  });
+  tokio_util::run(main_future);
+}
+
+/// Formats a size in bytes for `deno cache ls`, e.g. "1.5 KB".
+fn format_size(size: u64) -> String {
+  const UNITS: [&str; 4] = ["B", "KB", "MB", "GB"];
+  let mut value = size as f64;
+  let mut unit = 0;
+  while value >= 1024.0 && unit < UNITS.len() - 1 {
+    value /= 1024.0;
+    unit += 1;
+  }
+  if unit == 0 {
+    format!("{} B", size)
+  } else {
+    format!("{:.1} {}", value, UNITS[unit])
+  }
+}
+
+/// Formats the time since `time` for `deno cache ls`, e.g. "3d ago".
+fn format_age(time: SystemTime) -> String {
+  let secs = SystemTime::now()
+    .duration_since(time)
+    .map(|age| age.as_secs())
+    .unwrap_or(0);
+  match secs {
+    0..=59 => format!("{}s ago", secs),
+    60..=3599 => format!("{}m ago", secs / 60),
+    3600..=86399 => format!("{}h ago", secs / 3600),
+    _ => format!("{}d ago", secs / 86400),
+  }
+}
+
+fn cache_command(flags: DenoFlags, argv: Vec<String>) {
+  // The subcommands only manage the cache, so there is no main module and no
+  // need for a whole state.
+  let custom_root = env::var("DENO_DIR").map(String::into).ok();
+  let dir =
+    DenoDir::new(custom_root, &None, None, vec![], false, Progress::new())
+      .unwrap();
+  let result = match argv[1].as_str() {
+    "ls" => dir.cached_modules().map(|modules| {
+      let width = modules.iter().map(|m| m.url.len()).max().unwrap_or(0);
+      for module in modules {
+        println!(
+          "{:width$}  {:>9}  {:>8}  {}",
+          module.url,
+          format_size(module.size),
+          format_age(module.downloaded),
+          module.mime_type.unwrap_or_else(|| "-".to_string()),
+          width = width
+        );
+      }
+    }),
+    "clean" => {
+      let url_prefix = argv.get(2).map(String::as_str);
+      dir.clean(flags.cache_older_than, url_prefix).map(|removed| {
+        for url in removed.iter() {
+          println!("{}", url);
+        }
+        println!("Removed {} cached modules", removed.len());
+      })
+    }
+    "gc" => dir.gc().map(|removed| {
+      println!("Removed {} compiled files", removed);
+    }),
+    _ => unreachable!(),
+  };
+  if let Err(err) = result {
+    print_err_and_exit(DenoError::from(err).into());
+  }
+}
+
+fn importmap_command(flags: DenoFlags, argv: Vec<String>) {
+  let result = match argv[1].as_str() {
+    "check" => ImportMap::check_file(&argv[2]).map(|diagnostics| {
+      for diagnostic in diagnostics.iter() {
+        println!("{}", diagnostic);
+      }
+      if diagnostics.is_empty() {
+        println!("No problems found in {}", argv[2]);
+      } else {
+        println!("Found {} problems in {}", diagnostics.len(), argv[2]);
+      }
+      // Warnings alone don't fail the check.
+      if diagnostics.iter().any(|d| d.severity != Severity::Warning) {
+        std::process::exit(1);
+      }
+    }),
+    "resolve" => {
+      let import_map_path = flags.import_map_path.unwrap();
+      let specifier = &argv[2];
+      let referrer = argv.get(3).map(String::as_str).unwrap_or("./");
+      let referrer = match root_specifier_to_url(referrer) {
+        Ok(url) => url.to_string(),
+        Err(err) => return print_err_and_exit(DenoError::from(err).into()),
+      };
+      ImportMap::load_file(&import_map_path).and_then(|import_map| {
+        println!("{:10} {}", "specifier", specifier);
+        println!("{:10} {}", "referrer", referrer);
+        match import_map.resolve_match(specifier, &referrer)? {
+          Some(import_match) => {
+            let scope = import_match.scope.unwrap_or_else(|| "-".to_string());
+            println!("{:10} {}", "scope", scope);
+            println!("{:10} {}", "key", import_match.key);
+            println!("{:10} {}", "resolved", import_match.address);
+          }
+          None => {
+            let url = import_map.resolve(specifier, &referrer)?.unwrap();
+            println!("{:10} {} (not mapped)", "resolved", url);
+          }
+        }
+        Ok(())
+      })
+    }
+    _ => unreachable!(),
+  };
+  if let Err(err) = result {
+    print_err_and_exit(DenoError::from(err).into());
+  }
+}
+
 fn eval_command(flags: DenoFlags, argv: Vec<String>) {
   let (mut worker, state) = create_worker_and_state(flags, argv);
   // Wrap provided script in async function so asynchronous methods
@@ -267,21 +520,614 @@ fn xeval_command(flags: DenoFlags, argv: Vec<String>) {
 fn bundle_command(flags: DenoFlags, argv: Vec<String>) {
   let (mut _worker, state) = create_worker_and_state(flags, argv);
 
-  let main_module = state.main_module().unwrap();
-  let main_url = root_specifier_to_url(&main_module).unwrap();
-  assert!(state.argv.len() >= 3);
-  let out_file = state.argv[2].clone();
+  let out_dir = state.flags.bundle_out_dir.clone();
+  let (entries, out_files, shared_out_file) = match out_dir {
+    Some(out_dir) => {
+      let out_dir = Path::new(&out_dir);
+      let entries = state.argv[1..].to_vec();
+      let out_files: Vec<String> = entries
+        .iter()
+        .map(|entry| {
+          let name = Path::new(entry).file_stem().unwrap().to_string_lossy();
+          out_dir.join(format!("{}.js", name)).to_string_lossy().to_string()
+        }).collect();
+      let shared_out_file =
+        out_dir.join("shared.js").to_string_lossy().to_string();
+      let mut seen = HashSet::new();
+      for file in out_files.iter().chain(Some(&shared_out_file)) {
+        if !seen.insert(file) {
+          eprintln!("Several bundles would be written to {}", file);
+          std::process::exit(1);
+        }
+      }
+      if let Err(err) = std::fs::create_dir_all(out_dir) {
+        eprintln!("Could not create {}: {}", out_dir.display(), err);
+        std::process::exit(1);
+      }
+      (entries, out_files, Some(shared_out_file))
+    }
+    None => {
+      assert!(state.argv.len() >= 3);
+      (vec![state.argv[1].clone()], vec![state.argv[2].clone()], None)
+    }
+  };
+  let module_names = entries
+    .iter()
+    .map(|entry| root_specifier_to_url(entry).unwrap().to_string())
+    .collect();
+  if state.flags.watch {
+    return bundle_watch(state, module_names, out_files, shared_out_file);
+  }
+  debug!(">>>>> bundle_async START");
+  let bundle_future =
+    bundle_async(state, module_names, out_files, shared_out_file)
+      .map_err(|e| {
+        debug!("diagnostics returned, exiting!");
+        eprintln!("\n{}", e.to_string());
+        std::process::exit(1);
+      }).and_then(move |_| {
+        debug!(">>>>> bundle_async END");
+        Ok(())
+      });
+  tokio_util::run(bundle_future);
+}
+
+/// The files which `--watch` watches besides the modules: the config file
+/// and the import map.
+fn watched_files(state: &ThreadSafeState) -> Vec<PathBuf> {
+  let mut files: Vec<PathBuf> =
+    state.config_path.iter().map(PathBuf::from).collect();
+  files.extend(state.flags.import_map_path.iter().map(PathBuf::from));
+  files
+}
+
+/// The compiler resolves imports with the import map it was started with, so
+/// it is only kept for the next run while the import map stays the same.
+fn import_map_changed(state: &ThreadSafeState, changed: &[PathBuf]) -> bool {
+  match state.flags.import_map_path {
+    Some(ref import_map_path) => {
+      changed.iter().any(|path| path == Path::new(import_map_path))
+    }
+    None => false,
+  }
+}
+
+/// `deno bundle --watch` writes the bundles again whenever a local module,
+/// the config file or the import map changes. The modules of the last bundle
+/// which was written are watched, so a module which fails to compile is still
+/// watched.
+fn bundle_watch(
+  state: ThreadSafeState,
+  module_names: Vec<String>,
+  out_files: Vec<String>,
+  shared_out_file: Option<String>,
+) {
+  let initial = (state, module_names.clone());
+  let main_future = future::loop_fn(initial, move |(state, source_files)| {
+    let since = SystemTime::now();
+    let state_ = state.clone();
+    bundle_async(
+      state.clone(),
+      module_names.clone(),
+      out_files.clone(),
+      shared_out_file.clone(),
+    ).then(move |result| {
+      let source_files = match result {
+        Ok(bundled) => bundled,
+        Err(diagnostics) => {
+          eprintln!("\n{}", diagnostics.to_string());
+          source_files
+        }
+      };
+      eprintln!("Watching for file changes...");
+      let mut files = watched_files(&state_);
+      files.extend(file_watcher::local_files(&source_files));
+      file_watcher::watch(since, move || files.clone())
+        .map(move |changed| (changed, source_files))
+    }).map(move |(changed, source_files)| {
+      if import_map_changed(&state, &changed) {
+        let (_worker, state) =
+          create_worker_and_state(state.flags.clone(), state.argv.clone());
+        return Loop::Continue((state, source_files));
+      }
+      Loop::Continue((state, source_files))
+    })
+  });
+  tokio_util::run(main_future);
+}
+
+fn compile_command(flags: DenoFlags, argv: Vec<String>) {
+  let output = flags.output.clone().unwrap();
+  // The program runs with the permissions given to `deno compile`, which
+  // itself only needs to write the bundle.
+  let metadata = standalone::Metadata::from_flags(&flags);
+  let bundle_flags = DenoFlags {
+    allow_write: true,
+    bundle_source_map: Some("none".to_string()),
+    ..flags
+  };
+  let (mut _worker, state) = create_worker_and_state(bundle_flags, argv);
+
+  let temp_dir = TempDir::new().expect("tempdir fail");
+  let bundle_file = temp_dir.path().join("bundle.js");
+  let module_name = root_specifier_to_url(&state.argv[1]).unwrap().to_string();
+  let out_file = bundle_file.to_string_lossy().to_string();
   debug!(">>>>> bundle_async START");
-  let bundle_future = bundle_async(state, main_url.to_string(), out_file)
-    .map_err(|e| {
-      debug!("diagnostics returned, exiting!");
-      eprintln!("\n{}", e.to_string());
+  let compile_future =
+    bundle_async(state, vec![module_name], vec![out_file], None)
+      .map_err(|e| {
+        eprintln!("\n{}", e.to_string());
+        std::process::exit(1);
+      }).and_then(move |_| {
+        debug!(">>>>> bundle_async END");
+        let payload = standalone::Payload {
+          source: std::fs::read_to_string(&bundle_file).unwrap(),
+          metadata,
+        };
+        let exe = env::current_exe().unwrap();
+        let output_path = Path::new(&output);
+        if let Err(err) = standalone::write(&exe, output_path, &payload) {
+          eprintln!("Could not write {}: {}", output, err);
+          std::process::exit(1);
+        }
+        println!("Compiled program written to {}", output);
+        // The temporary directory is removed once the bundle was read.
+        drop(temp_dir);
+        Ok(())
+      });
+  tokio_util::run(compile_future);
+}
+
+fn snapshot_command(flags: DenoFlags, argv: Vec<String>) {
+  let output = flags.output.clone().unwrap();
+  // The compiler writes the bundle, the dependencies then run with the
+  // permissions given to `deno snapshot`.
+  let bundle_flags = DenoFlags {
+    allow_write: true,
+    bundle_source_map: Some("none".to_string()),
+    bundle_defer_main: true,
+    ..flags.clone()
+  };
+  let (mut _worker, bundle_state) =
+    create_worker_and_state(bundle_flags, argv.clone());
+  let state = ThreadSafeState::new(
+    flags,
+    argv,
+    ops::op_selector_std,
+    bundle_state.progress.clone(),
+  );
+
+  let temp_dir = TempDir::new().expect("tempdir fail");
+  let bundle_file = temp_dir.path().join("bundle.js");
+  let main_module = root_specifier_to_url(&state.argv[1]).unwrap().to_string();
+  let out_file = bundle_file.to_string_lossy().to_string();
+  debug!(">>>>> bundle_async START");
+  let snapshot_future =
+    bundle_async(bundle_state, vec![main_module.clone()], vec![out_file], None)
+      .map_err(|e| {
+        eprintln!("\n{}", e.to_string());
+        std::process::exit(1);
+      }).and_then(move |_| {
+        debug!(">>>>> bundle_async END");
+        let source = std::fs::read_to_string(&bundle_file).unwrap();
+        drop(temp_dir);
+
+        // Snapshots can't be made from snapshots, so this starts from the
+        // runtime's script. The bundle runs the top-level code of the
+        // dependencies of the main module, but not the main module itself.
+        let startup_data =
+          StartupData::Script(startup_data::deno_isolate_script());
+        let mut worker =
+          Worker::new_snapshotter("main".to_string(), startup_data, state);
+        js_check(worker.execute2(&main_module, &source));
+        let data = worker.snapshot().unwrap_or_else(|err| {
+          print_err_and_exit(err.into());
+          unreachable!()
+        });
+
+        let snapshot = snapshot::UserSnapshot { main_module, data };
+        if let Err(err) = snapshot::write(Path::new(&output), &snapshot) {
+          eprintln!("Could not write {}: {}", output, err);
+          std::process::exit(1);
+        }
+        println!("Snapshot written to {}", output);
+        Ok(())
+      });
+  tokio_util::run(snapshot_future);
+}
+
+/// Starts the program of a snapshot written by `deno snapshot`. All of
+/// `argv` is passed to the program.
+fn run_snapshot(flags: DenoFlags, mut argv: Vec<String>) {
+  let path = flags.snapshot_path.clone().unwrap();
+  let snapshot = snapshot::read(Path::new(&path)).unwrap_or_else(|err| {
+    eprintln!("Could not read {}: {}", path, err);
+    std::process::exit(1);
+  });
+  argv.insert(1, snapshot.main_module);
+  // V8 keeps using the snapshot after the isolate was created.
+  let data: &'static [u8] = Box::leak(snapshot.data.into_boxed_slice());
+  let (mut worker, _state) =
+    create_worker_and_state_with(flags, argv, StartupData::Snapshot(data));
+
+  let main_future = lazy(move || {
+    js_check(worker.execute("denoMain()"));
+    js_check(worker.execute("__runMain()"));
+    worker.then(|result| {
+      js_check(result);
+      Ok(())
+    })
+  });
+  tokio_util::run(main_future);
+}
+
+/// Returns the flags, subcommand and arguments which run the bundle of a
+/// program compiled with `deno compile`.
+fn standalone_args(
+  payload: standalone::Payload,
+  args: Vec<String>,
+) -> (DenoFlags, DenoSubcommand, Vec<String>) {
+  scheme_handlers::register(
+    standalone::SCHEME,
+    Arc::new(standalone::EmbeddedModule(payload.source)),
+  );
+  let mut argv = vec![args[0].clone(), standalone::MAIN_MODULE.to_string()];
+  argv.extend(args.into_iter().skip(1));
+  (payload.metadata.to_flags(), DenoSubcommand::Run, argv)
+}
+
+fn check_command(flags: DenoFlags, argv: Vec<String>) {
+  let json = flags.json_diagnostics;
+  let mut root_names = vec![];
+  for specifier in &argv[1..] {
+    match root_specifier_to_url(specifier) {
+      Ok(url) => root_names.push(url.to_string()),
+      Err(err) => return print_err_and_exit(DenoError::from(err).into()),
+    }
+  }
+  let state = create_state(flags, argv);
+
+  debug!(">>>>> check_async START");
+  let check_future = check_async(state, root_names).then(move |result| {
+    debug!(">>>>> check_async END");
+    let diagnostics = match result {
+      Ok(_) => Diagnostic { items: vec![] },
+      Err(diagnostics) => diagnostics,
+    };
+    if json {
+      println!("{}", serde_json::to_string(&diagnostics).unwrap());
+    } else if !diagnostics.items.is_empty() {
+      eprintln!("\n{}", diagnostics.to_string());
+    }
+    if !diagnostics.items.is_empty() {
       std::process::exit(1);
-    }).and_then(move |_| {
-      debug!(">>>>> bundle_async END");
+    }
+    Ok(())
+  });
+  tokio_util::run(check_future);
+}
+
+type TestsFuture = Box<dyn Future<Item = Option<String>, Error = ()> + Send>;
+
+/// Waits until `messages` has received the results of all tests which run in
+/// `worker`. The worker finishes after the last test, unless the tests leave
+/// timers or other ops behind, so it isn't waited for. Resolves with an error
+/// if the results ended early.
+fn wait_for_tests<M>(messages: M, worker: Worker) -> TestsFuture
+where
+  M: Future<Item = (), Error = ()> + Send + 'static,
+{
+  // Collects the results which the worker posted before it finished.
+  fn drain<M>(messages: M, error: String) -> TestsFuture
+  where
+    M: Future<Item = (), Error = ()> + Send + 'static,
+  {
+    Box::new(messages.select2(future::ok::<(), ()>(())).then(
+      move |drained| -> Result<_, ()> {
+        match drained {
+          Ok(future::Either::A(_)) => Ok(None),
+          _ => Ok(Some(error)),
+        }
+      },
+    ))
+  }
+
+  Box::new(messages.select2(worker).then(|outcome| -> TestsFuture {
+    match outcome {
+      Ok(future::Either::A(_)) => Box::new(future::ok(None)),
+      Ok(future::Either::B(((), messages))) => drain(
+        messages,
+        "The module finished before all of its tests completed".to_string(),
+      ),
+      Err(future::Either::B((err, messages))) => {
+        drain(messages, RustOrJsError::from(err).to_string())
+      }
+      Err(future::Either::A(_)) => Box::new(future::ok(Some(
+        "Lost the connection to the test worker".to_string(),
+      ))),
+    }
+  }))
+}
+
+/// Runs the tests of the module `module_url` in a worker of its own, which
+/// has its own permissions and globals. It shares the compiler and the
+/// compiled modules with `parent`. The resources which the tests leave open
+/// are closed afterwards.
+fn run_test_file(
+  parent: &ThreadSafeState,
+  name: String,
+  module_url: Url,
+  reporter: Reporter,
+  coverage: Option<TestCoverage>,
+) -> impl Future<Item = FileResult, Error = ()> {
+  let start = Instant::now();
+  let rids = open_rids();
+  let argv = vec!["deno".to_string(), module_url.to_string()];
+  let state = ThreadSafeState::new_child(
+    parent,
+    argv,
+    ops::op_selector_std,
+    parent.import_map.clone(),
+  );
+  let mut worker = Worker::new(
+    "main".to_string(),
+    startup_data::deno_isolate_init(),
+    state.clone(),
+  );
+  let flags = &parent.flags;
+  js_check(worker.execute("denoMain()"));
+  if let Some(ref coverage) = coverage {
+    worker.start_coverage();
+    // Deno.exit() ends all the tests, so the coverage collected so far is
+    // written first.
+    let handle = worker.isolate_handle();
+    let coverage = coverage.clone();
+    let exit_hook = move |state: &ThreadSafeState| {
+      coverage.add(&handle.take_coverage(), state);
+      coverage.write();
+    };
+    *state.exit_hook.lock().unwrap() = Some(Box::new(exit_hook));
+  }
+
+  let result = Arc::new(Mutex::new(FileResult::new(name)));
+  let result_ = result.clone();
+  let messages = resources::get_message_stream_from_worker(state.resource.rid)
+    .map_err(|_| ())
+    .filter_map(|msg| TestMessage::parse(&msg))
+    .take_while(|msg| Ok(*msg != TestMessage::End))
+    .for_each(move |msg| {
+      let mut result = result_.lock().unwrap();
+      match msg {
+        TestMessage::Plan { total, filtered } => {
+          result.filtered = filtered;
+          if reporter == Reporter::Pretty {
+            println!("{}", test_runner::pretty_plan(&result, total));
+          }
+        }
+        TestMessage::Result {
+          name,
+          error,
+          duration,
+        } => {
+          let test = TestResult {
+            name,
+            error,
+            duration,
+          };
+          if reporter == Reporter::Pretty {
+            println!("{}", test_runner::pretty_result(&test));
+          }
+          result.tests.push(test);
+        }
+        TestMessage::End => unreachable!(),
+      }
       Ok(())
     });
-  tokio_util::run(bundle_future);
+
+  let options = json!({
+    "filter": flags.test_filter,
+    "failFast": flags.test_fail_fast,
+  });
+  let mut worker_ = worker.clone();
+  let coverage_worker = worker.clone();
+  worker
+    .execute_mod_async(&module_url, false)
+    .and_then(move |()| {
+      worker_
+        .execute(&format!("testMain({})", options))
+        .map_err(RustOrJsError::from)?;
+      Ok(worker_)
+    }).then(move |loaded| match loaded {
+      Ok(worker) => future::Either::A(wait_for_tests(messages, worker)),
+      Err(err) => future::Either::B(future::ok(Some(err.to_string()))),
+    }).map(move |error| {
+      if let Some(coverage) = coverage {
+        coverage.add(&coverage_worker.take_coverage(), &state);
+      }
+      close_rids_since(&rids, &state.compiler.lock().unwrap());
+      let mut result = result.lock().unwrap();
+      let mut result = std::mem::replace(&mut *result, FileResult::default());
+      result.error = error;
+      result.duration = elapsed_ms(start);
+      result
+    })
+}
+
+/// The coverage of `deno test --coverage`, merged from the workers of the
+/// test files.
+#[derive(Clone)]
+struct TestCoverage {
+  dir: String,
+  coverage: Arc<Mutex<Coverage>>,
+  /// The test modules themselves are left out.
+  test_modules: Arc<Vec<String>>,
+}
+
+impl TestCoverage {
+  fn add(&self, json: &str, state: &ThreadSafeState) {
+    self.coverage.lock().unwrap().add(json, &state.dir);
+  }
+
+  fn write(&self) {
+    let mut coverage = self.coverage.lock().unwrap();
+    for module in self.test_modules.iter() {
+      coverage.files.remove(module);
+    }
+    write_coverage(&self.dir, &coverage);
+  }
+}
+
+fn elapsed_ms(start: Instant) -> u64 {
+  let elapsed = start.elapsed();
+  elapsed.as_secs() * 1000 + u64::from(elapsed.subsec_millis())
+}
+
+/// `deno test` type-checks all the test modules in one compiler pass, and
+/// then runs them one after another, each in a worker of its own.
+fn test_command(flags: DenoFlags, argv: Vec<String>) {
+  let files = match test_runner::find_test_files(&argv[1..]) {
+    Ok(files) => files,
+    Err(err) => {
+      eprintln!("Could not search for test modules: {}", err);
+      std::process::exit(1);
+    }
+  };
+  if files.is_empty() {
+    eprintln!("No test modules found");
+    std::process::exit(1);
+  }
+  let (mut _worker, state) = create_worker_and_state(flags, argv);
+
+  let tests: Vec<(String, Url)> = files
+    .iter()
+    .map(|file| {
+      let name = file.to_string_lossy().to_string();
+      let module_url = root_specifier_to_url(&name).unwrap();
+      (name, module_url)
+    }).collect();
+  if state.flags.watch {
+    return test_watch(state, tests);
+  }
+  let test_future = test_async(state, tests).map(|(_, passed)| {
+    if !passed {
+      std::process::exit(1);
+    }
+  });
+  tokio_util::run(test_future);
+}
+
+/// Type-checks and runs the tests once, and reports the results. Resolves
+/// with the names of the checked modules, which are `None` if the check
+/// failed, and whether all the tests passed.
+fn test_async(
+  state: ThreadSafeState,
+  tests: Vec<(String, Url)>,
+) -> impl Future<Item = (Option<Vec<String>>, bool), Error = ()> {
+  let flags = &state.flags;
+  let reporter =
+    Reporter::from_name(flags.test_reporter.as_ref().map(String::as_str));
+  let output = flags.output.clone();
+  let fail_fast = flags.test_fail_fast;
+  let root_names: Vec<String> =
+    tests.iter().map(|(_, url)| url.to_string()).collect();
+  let coverage = flags.coverage_dir.clone().map(|dir| TestCoverage {
+    dir,
+    coverage: Arc::new(Mutex::new(Coverage::default())),
+    test_modules: Arc::new(root_names.clone()),
+  });
+  let test_coverage = coverage.clone();
+  let start = Instant::now();
+  debug!(">>>>> check_async START");
+  check_async(state.clone(), root_names).then(move |result| {
+    debug!(">>>>> check_async END");
+    let checked = match result {
+      Ok(checked) => checked,
+      Err(diagnostics) => {
+        eprintln!("\n{}", diagnostics.to_string());
+        return future::Either::A(future::ok((None, false)));
+      }
+    };
+    // The modules are compiled now. The test workers share the compiler of
+    // `state`, in case they import more modules dynamically.
+    let run_future =
+      future::loop_fn(Vec::new(), move |mut results: Vec<FileResult>| {
+        let failed = results.last().map_or(false, FileResult::failed);
+        if results.len() == tests.len() || (fail_fast && failed) {
+          return future::Either::A(future::ok(Loop::Break(results)));
+        }
+        let (name, module_url) = tests[results.len()].clone();
+        future::Either::B(
+          run_test_file(
+            &state,
+            name,
+            module_url,
+            reporter,
+            test_coverage.clone(),
+          ).map(move |result| {
+            results.push(result);
+            Loop::Continue(results)
+          }),
+        )
+      }).map(move |results| {
+        let duration = elapsed_ms(start);
+        let report = match reporter {
+          Reporter::Pretty => {
+            print!("{}", test_runner::pretty_summary(&results, duration));
+            None
+          }
+          Reporter::Tap => Some(test_runner::tap_report(&results)),
+          Reporter::Junit => {
+            Some(test_runner::junit_report(&results, duration))
+          }
+        };
+        match (report, output) {
+          (Some(report), Some(output)) => {
+            if let Err(err) = std::fs::write(&output, report) {
+              eprintln!("Could not write {}: {}", output, err);
+              std::process::exit(1);
+            }
+          }
+          (Some(report), None) => print!("{}", report),
+          (None, _) => {}
+        }
+        if let Some(coverage) = coverage {
+          coverage.write();
+        }
+        let passed = !results.iter().any(FileResult::failed);
+        (Some(checked), passed)
+      });
+    future::Either::B(run_future)
+  })
+}
+
+/// `deno test --watch` runs the tests again whenever a local module, the
+/// config file or the import map changes. The modules of the last check
+/// which succeeded are watched, so a module which fails to compile is still
+/// watched.
+fn test_watch(state: ThreadSafeState, tests: Vec<(String, Url)>) {
+  let root_names: Vec<String> =
+    tests.iter().map(|(_, url)| url.to_string()).collect();
+  let initial = (state, root_names);
+  let main_future = future::loop_fn(initial, move |(state, source_files)| {
+    let since = SystemTime::now();
+    let state_ = state.clone();
+    test_async(state.clone(), tests.clone())
+      .and_then(move |(checked, _passed)| {
+        let source_files = checked.unwrap_or(source_files);
+        eprintln!("Watching for file changes...");
+        let mut files = watched_files(&state_);
+        files.extend(file_watcher::local_files(&source_files));
+        file_watcher::watch(since, move || files.clone())
+          .map(move |changed| (changed, source_files))
+      }).map(move |(changed, source_files)| {
+        if import_map_changed(&state, &changed) {
+          let state = create_state(state.flags.clone(), state.argv.clone());
+          return Loop::Continue((state, source_files));
+        }
+        Loop::Continue((state, source_files))
+      })
+  });
+  tokio_util::run(main_future);
 }
 
 fn run_repl(flags: DenoFlags, argv: Vec<String>) {
@@ -302,7 +1148,130 @@ fn run_repl(flags: DenoFlags, argv: Vec<String>) {
   tokio_util::run(main_future);
 }
 
+/// Returns the ids of the open resources.
+fn open_rids() -> HashSet<ResourceId> {
+  resources::table_entries()
+    .into_iter()
+    .map(|(rid, _)| rid)
+    .collect()
+}
+
+/// Closes the resources opened since `open_rids()` returned `rids`, except
+/// the one of `compiler`, which is kept for the next worker.
+fn close_rids_since(
+  rids: &HashSet<ResourceId>,
+  compiler: &Option<Arc<CompilerWorker>>,
+) {
+  let compiler_rid = compiler.as_ref().map(|compiler| compiler.rid());
+  for (rid, _) in resources::table_entries() {
+    if !rids.contains(&rid) && Some(rid) != compiler_rid {
+      if let Some(resource) = resources::lookup(rid) {
+        resource.close();
+      }
+    }
+  }
+}
+
+/// Runs the program until a local module, the config file or the import map
+/// changes, then terminates it and closes the resources it opened.
+/// `compiler` is the compiler of the previous run, if it can be kept.
+fn run_until_change(
+  flags: DenoFlags,
+  argv: Vec<String>,
+  compiler: Option<Arc<CompilerWorker>>,
+) -> impl Future<Item = Loop<(), Option<Arc<CompilerWorker>>>, Error = ()> {
+  let rids = open_rids();
+  let since = SystemTime::now();
+  let (mut worker, state) = create_worker_and_state(flags, argv);
+  *state.compiler.lock().unwrap() = compiler;
+  let handle = worker.isolate_handle();
+  js_check(worker.execute("denoMain()"));
+  let main_module = state.main_module().unwrap();
+  debug!("main_module {}", main_module);
+  let main_url = root_specifier_to_url(&main_module).unwrap();
+
+  // The program may be stuck in a loop, so the watcher runs separately and
+  // terminates it.
+  let (sender, receiver) = oneshot::channel();
+  let restarting = Arc::new(AtomicBool::new(false));
+  let restarting_ = restarting.clone();
+  let state_ = state.clone();
+  let modules = worker.modules.clone();
+  let main_module = main_url.to_string();
+  tokio::spawn(
+    file_watcher::watch(since, move || {
+      let mut files = watched_files(&state_);
+      let modules = modules.lock().unwrap();
+      files.extend(file_watcher::module_graph_files(&modules, &main_module));
+      files
+    }).map(move |changed| {
+      restarting_.store(true, Ordering::SeqCst);
+      handle.terminate_execution();
+      let _ = sender.send(changed);
+    }),
+  );
+
+  let worker_ = worker.clone();
+  let run_future = worker
+    .execute_mod_async(&main_url, false)
+    .and_then(move |()| worker_.map_err(RustOrJsError::from))
+    .then(move |result| {
+      // Terminating the program for a restart makes it fail.
+      if !restarting.load(Ordering::SeqCst) {
+        if let Err(err) = result {
+          eprintln!("{}", err.to_string());
+        }
+        eprintln!("Watching for file changes...");
+      }
+      future::empty::<Vec<PathBuf>, ()>()
+    });
+
+  run_future
+    .select(receiver.map_err(|_| ()))
+    .map_err(|_| ())
+    .map(move |(changed, _run_future)| {
+      eprintln!("Restarting: {} changed", changed[0].display());
+      let compiler = state.compiler.lock().unwrap().clone();
+      close_rids_since(&rids, &compiler);
+      if import_map_changed(&state, &changed) {
+        return Loop::Continue(None);
+      }
+      Loop::Continue(compiler)
+    })
+}
+
+/// `deno run --watch` runs the program like `run_script()`, but restarts it
+/// in a new worker whenever a local module, the config file or the import map
+/// changes, instead of exiting. The compiler is kept between runs, so only
+/// the modules which changed are compiled again.
+fn run_watch(flags: DenoFlags, argv: Vec<String>) {
+  // The first run starts in the runtime, as it spawns the watcher.
+  let main_future = lazy(move || {
+    future::loop_fn(None, move |compiler| {
+      run_until_change(flags.clone(), argv.clone(), compiler)
+    })
+  });
+  tokio_util::run(main_future);
+}
+
+/// Writes the lcov tracefile for `--coverage` to `dir`, and prints a summary
+/// to stderr so it doesn't mix with the output of the program.
+fn write_coverage(dir: &str, coverage: &Coverage) {
+  if let Err(err) = coverage.write(Path::new(dir)) {
+    eprintln!("Could not write the coverage to {}: {}", dir, err);
+    std::process::exit(1);
+  }
+  eprint!("{}", coverage.summary());
+}
+
 fn run_script(flags: DenoFlags, argv: Vec<String>) {
+  if flags.snapshot_path.is_some() {
+    return run_snapshot(flags, argv);
+  }
+  if flags.watch {
+    return run_watch(flags, argv);
+  }
+  let coverage_dir = flags.coverage_dir.clone();
   let (mut worker, state) = create_worker_and_state(flags, argv);
 
   let main_module = state.main_module().unwrap();
@@ -311,13 +1280,47 @@ fn run_script(flags: DenoFlags, argv: Vec<String>) {
     // Setup runtime.
     js_check(worker.execute("denoMain()"));
     debug!("main_module {}", main_module);
+    if let Some(ref dir) = coverage_dir {
+      worker.start_coverage();
+      // Deno.exit() ends the process before the worker completes.
+      let handle = worker.isolate_handle();
+      let dir = dir.clone();
+      let exit_hook = move |state: &ThreadSafeState| {
+        let mut coverage = Coverage::default();
+        coverage.add(&handle.take_coverage(), &state.dir);
+        write_coverage(&dir, &coverage);
+      };
+      *state.exit_hook.lock().unwrap() = Some(Box::new(exit_hook));
+    }
+    let coverage_worker = worker.clone();
 
     let main_url = root_specifier_to_url(&main_module).unwrap();
 
-    worker
-      .execute_mod_async(&main_url, false)
+    // Bundles carry their own loader and are executed as scripts, without
+    // fetching or compiling anything.
+    let load_future = match bundle::read(&main_url) {
+      Ok(Some(chunks)) => {
+        for chunk in chunks {
+          js_check(worker.execute2(&chunk.filename, &chunk.source));
+        }
+        future::Either::A(future::ok(()))
+      }
+      Ok(None) => future::Either::B(worker.execute_mod_async(&main_url, false)),
+      Err(err) => future::Either::A(future::err(RustOrJsError::from(err))),
+    };
+
+    load_future
       .and_then(move |()| {
-        worker.then(|result| {
+        worker.then(move |result| {
+          // Uncaught errors exit, so the coverage is written first.
+          if let Some(dir) = coverage_dir {
+            let mut coverage = Coverage::default();
+            coverage.add(
+              &coverage_worker.take_coverage(),
+              &coverage_worker.state.dir,
+            );
+            write_coverage(&dir, &coverage);
+          }
           js_check(result);
           Ok(())
         })
@@ -332,7 +1335,12 @@ fn main() {
 
   log::set_logger(&LOGGER).unwrap();
   let args: Vec<String> = env::args().collect();
-  let (flags, subcommand, argv) = flags::flags_from_vec(args);
+  // A program compiled with `deno compile` runs its embedded bundle, with
+  // all arguments passed on to it.
+  let (flags, subcommand, argv) = match standalone::read_current_exe() {
+    Ok(Some(payload)) => standalone_args(payload, args),
+    _ => flags::flags_from_vec(args),
+  };
 
   if let Some(ref v8_flags) = flags.v8_flags {
     v8_set_flags(v8_flags.clone());
@@ -344,14 +1352,25 @@ fn main() {
     LevelFilter::Warn
   });
 
+  init_tls(&flags);
+  init_auth_tokens();
+  init_scheme_handlers();
+
   match subcommand {
     DenoSubcommand::Bundle => bundle_command(flags, argv),
+    DenoSubcommand::Cache => cache_command(flags, argv),
+    DenoSubcommand::Check => check_command(flags, argv),
+    DenoSubcommand::Compile => compile_command(flags, argv),
     DenoSubcommand::Eval => eval_command(flags, argv),
     DenoSubcommand::Fetch => fetch_or_info_command(flags, argv, false),
+    DenoSubcommand::ImportMap => importmap_command(flags, argv),
     DenoSubcommand::Info => fetch_or_info_command(flags, argv, true),
     DenoSubcommand::Repl => run_repl(flags, argv),
     DenoSubcommand::Run => run_script(flags, argv),
+    DenoSubcommand::Snapshot => snapshot_command(flags, argv),
+    DenoSubcommand::Test => test_command(flags, argv),
     DenoSubcommand::Types => types_command(),
+    DenoSubcommand::Vendor => vendor_command(flags, argv),
     DenoSubcommand::Version => run_repl(flags, argv),
     DenoSubcommand::Xeval => xeval_command(flags, argv),
   }
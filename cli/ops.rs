@@ -1,6 +1,7 @@
 // Copyright 2018-2019 the Deno authors. All rights reserved. MIT license.
 use atty;
 use crate::ansi;
+use crate::deno_dir::resolve_file_url;
 use crate::deno_dir::resolve_path;
 use crate::dispatch_minimal::dispatch_minimal;
 use crate::dispatch_minimal::parse_min_record;
@@ -8,6 +9,7 @@ use crate::errors;
 use crate::errors::{DenoError, DenoResult, ErrorKind};
 use crate::fs as deno_fs;
 use crate::http_util;
+use crate::import_map::ImportMap;
 use crate::js_errors::apply_source_map;
 use crate::js_errors::JSErrorColor;
 use crate::msg;
@@ -308,11 +310,15 @@ fn op_is_tty(
 }
 
 fn op_exit(
-  _state: &ThreadSafeState,
+  state: &ThreadSafeState,
   base: &msg::Base<'_>,
   _data: Option<PinnedBuf>,
 ) -> Box<OpWithError> {
   let inner = base.inner_as_exit().unwrap();
+  let exit_hook = state.exit_hook.lock().unwrap().take();
+  if let Some(mut exit_hook) = exit_hook {
+    exit_hook(state);
+  }
   std::process::exit(inner.code())
 }
 
@@ -747,36 +753,41 @@ fn op_fetch(
     return odd_future(e);
   }
 
-  let client = http_util::get_client();
+  let maybe_cookie_jar = if inner.use_cookies() {
+    Some(state.cookie_jar.clone())
+  } else {
+    None
+  };
+
+  let http2_prior_knowledge = inner.http2_prior_knowledge();
 
   debug!("Before fetch {}", url);
-  let future =
-    client
-      .request(req)
-      .map_err(DenoError::from)
-      .and_then(move |res| {
-        let builder = &mut FlatBufferBuilder::new();
-        let header_off = msg_util::serialize_http_response(builder, &res);
-        let body = res.into_body();
-        let body_resource = resources::add_hyper_body(body);
-        let inner = msg::FetchRes::create(
-          builder,
-          &msg::FetchResArgs {
-            header: Some(header_off),
-            body_rid: body_resource.rid,
-          },
-        );
+  let future = http_util::request(req, maybe_cookie_jar, http2_prior_knowledge)
+    .and_then(move |res| {
+      let builder = &mut FlatBufferBuilder::new();
+      let header_off = msg_util::serialize_http_response(builder, &res);
+      let http_version = builder.create_string(&format!("{:?}", res.version()));
+      let body = res.into_body();
+      let body_resource = resources::add_hyper_body(body);
+      let inner = msg::FetchRes::create(
+        builder,
+        &msg::FetchResArgs {
+          header: Some(header_off),
+          body_rid: body_resource.rid,
+          http_version: Some(http_version),
+        },
+      );
 
-        Ok(serialize_response(
-          cmd_id,
-          builder,
-          msg::BaseArgs {
-            inner: Some(inner.as_union_value()),
-            inner_type: msg::Any::FetchRes,
-            ..Default::default()
-          },
-        ))
-      });
+      Ok(serialize_response(
+        cmd_id,
+        builder,
+        msg::BaseArgs {
+          inner: Some(inner.as_union_value()),
+          inner_type: msg::Any::FetchRes,
+          ..Default::default()
+        },
+      ))
+    });
   Box::new(future)
 }
 
@@ -2055,6 +2066,32 @@ fn op_worker_post_message(
   Box::new(op)
 }
 
+/// Reads the import map of a worker. `file_name` is resolved against the
+/// main module, like the address of a web worker is resolved against the
+/// page, and relative addresses in the map against the location of the file.
+fn load_import_map(
+  state: &ThreadSafeState,
+  file_name: &str,
+) -> DenoResult<ImportMap> {
+  let referrer = state.main_module().unwrap_or_else(|| ".".to_string());
+  let url = resolve_file_url(file_name.to_string(), referrer)?;
+  let path = match url.to_file_path() {
+    Ok(path) => path,
+    Err(()) => {
+      return Err(errors::new(
+        ErrorKind::ImportMapError,
+        format!("The import map {} is not a local file", url),
+      ))
+    }
+  };
+  let path_ = path.to_str().unwrap().to_string();
+  state.check_read(&path_)?;
+  let json_string = fs::read_to_string(&path)?;
+  let base_url = url::Url::from_file_path(&path).unwrap();
+  ImportMap::from_json(base_url.as_str(), &json_string)
+    .map_err(DenoError::from)
+}
+
 /// Create worker as the host
 fn op_create_worker(
   state: &ThreadSafeState,
@@ -2066,13 +2103,23 @@ fn op_create_worker(
   let inner = base.inner_as_create_worker().unwrap();
   let specifier = inner.specifier().unwrap();
 
+  // A worker inherits the import map of its parent, unless it is created
+  // with its own.
+  let import_map = match inner.import_map() {
+    Some(file_name) => match load_import_map(state, file_name) {
+      Ok(import_map) => Some(import_map),
+      Err(err) => return odd_future(err),
+    },
+    None => state.import_map.clone(),
+  };
+
   let parent_state = state.clone();
 
-  let child_state = ThreadSafeState::new(
-    parent_state.flags.clone(),
+  let child_state = ThreadSafeState::new_child(
+    &parent_state,
     parent_state.argv.clone(),
     op_selector_std,
-    parent_state.progress.clone(),
+    import_map,
   );
   let rid = child_state.resource.rid;
   let name = format!("USER-WORKER-{}", specifier);
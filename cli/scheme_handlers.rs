@@ -0,0 +1,214 @@
+// Copyright 2018-2019 the Deno authors. All rights reserved. MIT license.
+//! Handlers for URL schemes which Deno doesn't know itself, such as `npm:`,
+//! a company registry, or an in-memory file system in tests. A registered
+//! handler resolves the specifiers of its scheme and loads the source code,
+//! which `DenoDir` then caches and compiles like any other module.
+//!
+//! Users register handlers with the `DENO_SCHEME_HANDLERS` environment
+//! variable, a semicolon separated list of `scheme=command` entries:
+//!
+//!   DENO_SCHEME_HANDLERS=npm=npm-to-esm;corp=/opt/corp/bin/fetch-module
+//!
+//! The command is run with the URL of a module as its last argument, and
+//! prints the source code of the module to stdout.
+use crate::builtin_modules;
+use crate::errors;
+use crate::errors::DenoError;
+use crate::errors::ErrorKind;
+use futures::Future;
+use std::collections::HashMap;
+use std::process::Command;
+use std::sync::Arc;
+use std::sync::RwLock;
+use tokio_process::CommandExt;
+use url;
+use url::Url;
+
+/// Schemes which are always handled by Deno itself, `std:` by the handler
+/// of the built-in modules.
+const BUILTIN_SCHEMES: &[&str] =
+  &["data", "file", "http", "https", builtin_modules::SCHEME];
+
+/// Source code returned by `SchemeHandler::load()`.
+pub struct LoadedSource {
+  pub source_code: Vec<u8>,
+  /// MIME type of the source code. Without one, the media type is guessed
+  /// from the extension of the URL.
+  pub mime_type: Option<String>,
+}
+
+pub type LoadFuture = dyn Future<Item = LoadedSource, Error = DenoError> + Send;
+
+pub trait SchemeHandler: Send + Sync {
+  /// Resolves `specifier` imported from `referrer` to an absolute URL. Called
+  /// for specifiers with the handler's scheme, and for relative specifiers
+  /// imported from a module with the handler's scheme.
+  fn resolve(
+    &self,
+    specifier: &str,
+    referrer: &str,
+  ) -> Result<Url, url::ParseError>;
+
+  /// Loads the source code of a URL returned by `resolve()`.
+  fn load(&self, url: &Url) -> Box<LoadFuture>;
+}
+
+lazy_static! {
+  static ref HANDLERS: RwLock<HashMap<String, Arc<dyn SchemeHandler>>> = {
+    let mut handlers: HashMap<String, Arc<dyn SchemeHandler>> = HashMap::new();
+    handlers.insert(
+      builtin_modules::SCHEME.to_string(),
+      Arc::new(builtin_modules::BuiltinModules),
+    );
+    RwLock::new(handlers)
+  };
+}
+
+/// Registers `handler` for URLs with the given scheme, e.g. "npm". It
+/// replaces any handler registered before for the same scheme.
+pub fn register(scheme: &str, handler: Arc<dyn SchemeHandler>) {
+  let scheme = scheme.to_lowercase();
+  assert!(
+    !BUILTIN_SCHEMES.contains(&scheme.as_str()),
+    "The \"{}\" scheme can't be handled by a SchemeHandler",
+    scheme
+  );
+  HANDLERS.write().unwrap().insert(scheme, handler);
+}
+
+/// Registers the handlers given with `DENO_SCHEME_HANDLERS`. Malformed
+/// entries, and entries for the schemes which Deno handles itself, are
+/// skipped with a warning.
+pub fn register_commands(s: &str) {
+  for entry in s.split(';').map(str::trim).filter(|e| !e.is_empty()) {
+    let (scheme, command) = match entry.find('=') {
+      Some(i) if i > 0 => (entry[..i].to_lowercase(), &entry[i + 1..]),
+      _ => {
+        warn!("Ignoring malformed DENO_SCHEME_HANDLERS entry");
+        continue;
+      }
+    };
+    if BUILTIN_SCHEMES.contains(&scheme.as_str()) {
+      warn!("Ignoring DENO_SCHEME_HANDLERS entry for \"{}\"", scheme);
+      continue;
+    }
+    match CommandHandler::new(command) {
+      Some(handler) => register(&scheme, Arc::new(handler)),
+      None => warn!("Ignoring malformed DENO_SCHEME_HANDLERS entry"),
+    }
+  }
+}
+
+/// A handler which runs an external command to load the modules of its
+/// scheme. Relative specifiers are resolved against the URL of the module
+/// which imports them.
+pub struct CommandHandler {
+  program: String,
+  args: Vec<String>,
+}
+
+impl CommandHandler {
+  /// Returns `None` if `command` is empty.
+  pub fn new(command: &str) -> Option<Self> {
+    let mut words = command.split_whitespace().map(ToOwned::to_owned);
+    let program = words.next()?;
+    Some(CommandHandler {
+      program,
+      args: words.collect(),
+    })
+  }
+}
+
+impl SchemeHandler for CommandHandler {
+  fn resolve(
+    &self,
+    specifier: &str,
+    referrer: &str,
+  ) -> Result<Url, url::ParseError> {
+    match Url::parse(specifier) {
+      Err(url::ParseError::RelativeUrlWithoutBase) => {
+        Url::parse(referrer)?.join(specifier)
+      }
+      result => result,
+    }
+  }
+
+  fn load(&self, url: &Url) -> Box<LoadFuture> {
+    let program = self.program.clone();
+    let url = url.clone();
+    let output = Command::new(&self.program)
+      .args(&self.args)
+      .arg(url.as_str())
+      .output_async();
+    Box::new(output.map_err(DenoError::from).and_then(move |output| {
+      if !output.status.success() {
+        return Err(errors::new(
+          ErrorKind::NotFound,
+          format!(
+            "\"{}\" could not load {}: {}",
+            program,
+            url,
+            String::from_utf8_lossy(&output.stderr).trim()
+          ),
+        ));
+      }
+      Ok(LoadedSource {
+        source_code: output.stdout,
+        mime_type: None,
+      })
+    }))
+  }
+}
+
+/// Returns the handler registered for the scheme of `url`, if any.
+pub fn get(url: &str) -> Option<Arc<dyn SchemeHandler>> {
+  let scheme = url.split(':').next()?.to_lowercase();
+  HANDLERS.read().unwrap().get(&scheme).cloned()
+}
+
+/// Resolves `specifier` with a registered handler, if its scheme has one. A
+/// relative specifier is resolved by the handler of the referrer's scheme.
+/// Returns `None` if no handler applies.
+pub fn resolve(
+  specifier: &str,
+  referrer: &str,
+) -> Option<Result<Url, url::ParseError>> {
+  let handler = if Url::parse(specifier).is_ok() {
+    get(specifier)
+  } else {
+    get(referrer)
+  };
+  handler.map(|handler| handler.resolve(specifier, referrer))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::tokio_util;
+
+  #[test]
+  fn test_register_commands() {
+    register_commands(";corp-test=echo -n;=cat;file=cat;broken-test; ");
+    assert!(get("corp-test:/a.ts").is_some());
+    assert!(get("broken-test:/a.ts").is_none());
+    assert!(get("file:///a.ts").is_none());
+  }
+
+  #[test]
+  fn test_command_handler() {
+    let handler = CommandHandler::new("echo -n").unwrap();
+    let url = handler.resolve("./b.ts", "corp:/lib/a.ts").unwrap();
+    assert_eq!(url.as_str(), "corp:/lib/b.ts");
+    let url = handler.resolve("corp:/c.ts", "file:///main.ts").unwrap();
+    assert_eq!(url.as_str(), "corp:/c.ts");
+
+    let loaded = tokio_util::block_on(handler.load(&url)).unwrap();
+    assert_eq!(loaded.source_code, b"corp:/c.ts");
+    assert_eq!(loaded.mime_type, None);
+
+    let handler = CommandHandler::new("false").unwrap();
+    let result = tokio_util::block_on(handler.load(&url));
+    let err = result.map(|_| ()).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::NotFound);
+  }
+}
@@ -0,0 +1,153 @@
+// Copyright 2018-2019 the Deno authors. All rights reserved. MIT license.
+//! Snapshots written by `deno snapshot` and started from with
+//! `deno run --snapshot`. They hold the V8 snapshot of an isolate which ran
+//! the runtime and the top-level code of the program's dependencies, followed
+//! by the URL of the program's main module, the versions of deno and V8 which
+//! wrote the snapshot, and a trailer:
+//!
+//!   <V8 snapshot> <main module> <version> <main module length>
+//!   <version length> <magic>
+//!
+//! The lengths are little-endian u64s. The main module itself is only run
+//! when the program is started. V8 can only start from snapshots of its own
+//! version, so snapshots of other versions are rejected.
+use crate::version;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+const MAGIC: &[u8; 8] = b"d3n0snap";
+const TRAILER_LEN: usize = 24;
+
+#[derive(Debug, PartialEq)]
+pub struct UserSnapshot {
+  pub main_module: String,
+  pub data: Vec<u8>,
+}
+
+/// The versions of deno and V8 stored in the snapshots this binary writes.
+fn runtime_version() -> String {
+  format!("deno {}, v8 {}", version::DENO, version::v8())
+}
+
+fn invalid_data(path: &Path) -> io::Error {
+  io::Error::new(
+    io::ErrorKind::InvalidData,
+    format!("{} is not a snapshot written by deno snapshot", path.display()),
+  )
+}
+
+pub fn write(path: &Path, snapshot: &UserSnapshot) -> io::Result<()> {
+  write_with_version(path, snapshot, &runtime_version())
+}
+
+fn write_with_version(
+  path: &Path,
+  snapshot: &UserSnapshot,
+  version: &str,
+) -> io::Result<()> {
+  let main_module = snapshot.main_module.as_bytes();
+  let version = version.as_bytes();
+  let mut bytes = Vec::with_capacity(
+    snapshot.data.len() + main_module.len() + version.len() + TRAILER_LEN,
+  );
+  bytes.extend_from_slice(&snapshot.data);
+  bytes.extend_from_slice(main_module);
+  bytes.extend_from_slice(version);
+  bytes.extend_from_slice(&(main_module.len() as u64).to_le_bytes());
+  bytes.extend_from_slice(&(version.len() as u64).to_le_bytes());
+  bytes.extend_from_slice(MAGIC);
+  fs::write(path, bytes)
+}
+
+/// Reads the u64 at `offset` of `data`.
+fn read_u64(data: &[u8], offset: usize) -> usize {
+  let mut bytes = [0; 8];
+  bytes.copy_from_slice(&data[offset..offset + 8]);
+  u64::from_le_bytes(bytes) as usize
+}
+
+/// Reads a snapshot written by this version of deno.
+pub fn read(path: &Path) -> io::Result<UserSnapshot> {
+  let mut data = fs::read(path)?;
+  if data.len() < TRAILER_LEN || &data[data.len() - 8..] != MAGIC {
+    return Err(invalid_data(path));
+  }
+  let trailer_start = data.len() - TRAILER_LEN;
+  let main_module_len = read_u64(&data, trailer_start);
+  let version_len = read_u64(&data, trailer_start + 8);
+  if main_module_len > trailer_start
+    || version_len > trailer_start - main_module_len
+  {
+    return Err(invalid_data(path));
+  }
+  let version_start = trailer_start - version_len;
+  let main_module_start = version_start - main_module_len;
+  let version = &data[version_start..trailer_start];
+  let runtime_version = runtime_version();
+  if version != runtime_version.as_bytes() {
+    return Err(io::Error::new(
+      io::ErrorKind::InvalidData,
+      format!(
+        "{} was written by {}, not {}. Write it again with deno snapshot",
+        path.display(),
+        String::from_utf8_lossy(version),
+        runtime_version
+      ),
+    ));
+  }
+  let main_module =
+    String::from_utf8(data[main_module_start..version_start].to_vec())
+      .map_err(|_| invalid_data(path))?;
+  data.truncate(main_module_start);
+  Ok(UserSnapshot { main_module, data })
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use tempfile::TempDir;
+
+  #[test]
+  fn test_write_read() {
+    let dir = TempDir::new().expect("tempdir fail");
+    let path = dir.path().join("app.snap");
+    let snapshot = UserSnapshot {
+      main_module: "file:///app/main.ts".to_string(),
+      data: vec![1, 2, 3, 4],
+    };
+    write(&path, &snapshot).unwrap();
+    assert_eq!(read(&path).unwrap(), snapshot);
+  }
+
+  #[test]
+  fn test_read_invalid() {
+    let dir = TempDir::new().expect("tempdir fail");
+    let path = dir.path().join("app.snap");
+    fs::write(&path, b"not a snapshot").unwrap();
+    let err = read(&path).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+
+    let mut bytes = Vec::new();
+    bytes.extend_from_slice(&100u64.to_le_bytes());
+    bytes.extend_from_slice(&0u64.to_le_bytes());
+    bytes.extend_from_slice(MAGIC);
+    fs::write(&path, bytes).unwrap();
+    let err = read(&path).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+  }
+
+  #[test]
+  fn test_read_other_version() {
+    let dir = TempDir::new().expect("tempdir fail");
+    let path = dir.path().join("app.snap");
+    let snapshot = UserSnapshot {
+      main_module: "file:///app/main.ts".to_string(),
+      data: vec![1, 2, 3, 4],
+    };
+    write_with_version(&path, &snapshot, "deno 0.0.1, v8 1.0").unwrap();
+    let err = read(&path).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    assert!(err.to_string().contains("written by deno 0.0.1, v8 1.0"));
+  }
+}
@@ -0,0 +1,260 @@
+// Copyright 2018-2019 the Deno authors. All rights reserved. MIT license.
+//! Programs compiled with `deno compile` are a copy of the deno executable
+//! with a payload appended to it: the bundle of the program, the permission
+//! flags it was compiled with as JSON, and a trailer with their lengths.
+//!
+//!   <deno executable> <bundle> <metadata> <bundle length> <metadata length>
+//!   <magic>
+//!
+//! The lengths are little-endian u64. At startup deno looks for the trailer
+//! at the end of its own executable and, if there is one, runs the bundle
+//! instead of parsing the command line.
+use crate::errors;
+use crate::errors::ErrorKind;
+use crate::flags::DenoFlags;
+use crate::scheme_handlers::LoadFuture;
+use crate::scheme_handlers::LoadedSource;
+use crate::scheme_handlers::SchemeHandler;
+use futures::future;
+use serde_json;
+use std::env;
+use std::fs;
+use std::fs::File;
+use std::fs::OpenOptions;
+use std::io;
+use std::io::Read;
+use std::io::Seek;
+use std::io::SeekFrom;
+use std::io::Write;
+use std::path::Path;
+use url;
+use url::Url;
+
+const MAGIC: &[u8; 8] = b"d3n0l4nd";
+const TRAILER_LEN: u64 = 24;
+
+/// The embedded bundle is loaded from this URL by `EmbeddedModule`.
+pub const SCHEME: &str = "deno-compile";
+pub const MAIN_MODULE: &str = "deno-compile:///main.js";
+
+/// The flags of `deno compile` which the compiled program runs with.
+#[derive(Clone, Debug, Default, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Metadata {
+  pub allow_read: bool,
+  pub read_whitelist: Vec<String>,
+  pub allow_write: bool,
+  pub write_whitelist: Vec<String>,
+  pub allow_net: bool,
+  pub net_whitelist: Vec<String>,
+  pub allow_env: bool,
+  pub allow_run: bool,
+  pub allow_hrtime: bool,
+  pub no_prompts: bool,
+  pub v8_flags: Option<Vec<String>>,
+}
+
+impl Metadata {
+  pub fn from_flags(flags: &DenoFlags) -> Metadata {
+    Metadata {
+      allow_read: flags.allow_read,
+      read_whitelist: flags.read_whitelist.clone(),
+      allow_write: flags.allow_write,
+      write_whitelist: flags.write_whitelist.clone(),
+      allow_net: flags.allow_net,
+      net_whitelist: flags.net_whitelist.clone(),
+      allow_env: flags.allow_env,
+      allow_run: flags.allow_run,
+      allow_hrtime: flags.allow_hrtime,
+      no_prompts: flags.no_prompts,
+      v8_flags: flags.v8_flags.clone(),
+    }
+  }
+
+  pub fn to_flags(&self) -> DenoFlags {
+    DenoFlags {
+      allow_read: self.allow_read,
+      read_whitelist: self.read_whitelist.clone(),
+      allow_write: self.allow_write,
+      write_whitelist: self.write_whitelist.clone(),
+      allow_net: self.allow_net,
+      net_whitelist: self.net_whitelist.clone(),
+      allow_env: self.allow_env,
+      allow_run: self.allow_run,
+      allow_hrtime: self.allow_hrtime,
+      no_prompts: self.no_prompts,
+      v8_flags: self.v8_flags.clone(),
+      ..DenoFlags::default()
+    }
+  }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Payload {
+  pub source: String,
+  pub metadata: Metadata,
+}
+
+fn invalid_data(msg: &str) -> io::Error {
+  io::Error::new(io::ErrorKind::InvalidData, msg.to_string())
+}
+
+fn read_u64(bytes: &[u8]) -> u64 {
+  let mut buf = [0; 8];
+  buf.copy_from_slice(bytes);
+  u64::from_le_bytes(buf)
+}
+
+/// Writes a copy of the executable `exe` with `payload` appended to `out`.
+pub fn write(exe: &Path, out: &Path, payload: &Payload) -> io::Result<()> {
+  let metadata = serde_json::to_vec(&payload.metadata).unwrap();
+  // Copying keeps the permissions, so the output is executable too.
+  fs::copy(exe, out)?;
+  let mut file = OpenOptions::new().append(true).open(out)?;
+  file.write_all(payload.source.as_bytes())?;
+  file.write_all(&metadata)?;
+  file.write_all(&(payload.source.len() as u64).to_le_bytes())?;
+  file.write_all(&(metadata.len() as u64).to_le_bytes())?;
+  file.write_all(MAGIC)?;
+  Ok(())
+}
+
+/// Reads the payload appended to `exe`. Returns `None` if there is none,
+/// which is the case for the deno executable itself.
+pub fn read(exe: &Path) -> io::Result<Option<Payload>> {
+  let mut file = File::open(exe)?;
+  let len = file.metadata()?.len();
+  if len < TRAILER_LEN {
+    return Ok(None);
+  }
+  let mut trailer = [0; TRAILER_LEN as usize];
+  file.seek(SeekFrom::Start(len - TRAILER_LEN))?;
+  file.read_exact(&mut trailer)?;
+  if &trailer[16..] != MAGIC {
+    return Ok(None);
+  }
+  let source_len = read_u64(&trailer[0..8]);
+  let metadata_len = read_u64(&trailer[8..16]);
+  let payload_len = source_len
+    .checked_add(metadata_len)
+    .filter(|payload_len| *payload_len <= len - TRAILER_LEN)
+    .ok_or_else(|| invalid_data("Truncated payload"))?;
+
+  file.seek(SeekFrom::Start(len - TRAILER_LEN - payload_len))?;
+  let mut source = vec![0; source_len as usize];
+  file.read_exact(&mut source)?;
+  let mut metadata = vec![0; metadata_len as usize];
+  file.read_exact(&mut metadata)?;
+
+  let source =
+    String::from_utf8(source).map_err(|_| invalid_data("Invalid bundle"))?;
+  let metadata = serde_json::from_slice(&metadata)
+    .map_err(|_| invalid_data("Invalid metadata"))?;
+  Ok(Some(Payload { source, metadata }))
+}
+
+/// Reads the payload appended to the running executable, if any.
+pub fn read_current_exe() -> io::Result<Option<Payload>> {
+  read(&env::current_exe()?)
+}
+
+/// Loads the embedded bundle for `scheme_handlers`.
+pub struct EmbeddedModule(pub String);
+
+impl SchemeHandler for EmbeddedModule {
+  fn resolve(
+    &self,
+    specifier: &str,
+    _referrer: &str,
+  ) -> Result<Url, url::ParseError> {
+    // The bundle loads its modules itself, so only the bundle is resolved.
+    Url::parse(specifier)
+  }
+
+  fn load(&self, url: &Url) -> Box<LoadFuture> {
+    Box::new(future::result(if url.as_str() == MAIN_MODULE {
+      Ok(LoadedSource {
+        source_code: self.0.as_bytes().to_vec(),
+        mime_type: Some("application/javascript".to_string()),
+      })
+    } else {
+      Err(errors::new(
+        ErrorKind::NotFound,
+        format!("Module \"{}\" is not in the compiled program", url),
+      ))
+    }))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use futures::Future;
+  use tempfile::TempDir;
+
+  #[test]
+  fn test_write_read() {
+    let dir = TempDir::new().expect("tempdir fail");
+    let exe = dir.path().join("deno");
+    let out = dir.path().join("tool");
+    fs::write(&exe, b"\x7fELF not really an executable").unwrap();
+    assert_eq!(read(&exe).unwrap(), None);
+
+    let payload = Payload {
+      source: "console.log(\"Hello\");\n".to_string(),
+      metadata: Metadata {
+        allow_net: true,
+        read_whitelist: vec!["/tmp".to_string()],
+        ..Metadata::default()
+      },
+    };
+    write(&exe, &out, &payload).unwrap();
+    assert_eq!(read(&out).unwrap(), Some(payload));
+    // The executable itself is copied unchanged.
+    let written = fs::read(&out).unwrap();
+    assert!(written.starts_with(b"\x7fELF not really an executable"));
+  }
+
+  #[test]
+  fn test_read_truncated() {
+    let dir = TempDir::new().expect("tempdir fail");
+    let exe = dir.path().join("deno");
+    let mut bytes = Vec::new();
+    bytes.extend_from_slice(&1000u64.to_le_bytes());
+    bytes.extend_from_slice(&2u64.to_le_bytes());
+    bytes.extend_from_slice(MAGIC);
+    fs::write(&exe, &bytes).unwrap();
+    let err = read(&exe).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+  }
+
+  #[test]
+  fn test_metadata_flags() {
+    let flags = DenoFlags {
+      allow_env: true,
+      net_whitelist: vec!["deno.land".to_string()],
+      reload: true,
+      ..DenoFlags::default()
+    };
+    let metadata = Metadata::from_flags(&flags);
+    assert!(metadata.allow_env);
+    assert_eq!(
+      metadata.to_flags(),
+      DenoFlags {
+        allow_env: true,
+        net_whitelist: vec!["deno.land".to_string()],
+        ..DenoFlags::default()
+      }
+    );
+  }
+
+  #[test]
+  fn test_embedded_module() {
+    let handler = EmbeddedModule("main();".to_string());
+    let url = handler.resolve(MAIN_MODULE, ".").unwrap();
+    let loaded = handler.load(&url).wait().unwrap();
+    assert_eq!(loaded.source_code, b"main();");
+    let url = handler.resolve("deno-compile:///other.js", ".").unwrap();
+    assert!(handler.load(&url).wait().is_err());
+  }
+}
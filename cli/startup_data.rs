@@ -1,19 +1,25 @@
 // Copyright 2018-2019 the Deno authors. All rights reserved. MIT license.
 use deno::{Script, StartupData};
 
+/// The runtime's JavaScript, which `deno snapshot` starts from, as snapshots
+/// can't be made from other snapshots.
+pub fn deno_isolate_script() -> Script<'static> {
+  #[cfg(not(feature = "check-only"))]
+  let source_bytes =
+    include_bytes!(concat!(env!("GN_OUT_DIR"), "/gen/cli/bundle/main.js"));
+  #[cfg(feature = "check-only")]
+  let source_bytes = b"";
+
+  Script {
+    filename: "gen/cli/bundle/main.js",
+    source: std::str::from_utf8(&source_bytes[..]).unwrap(),
+  }
+}
+
 pub fn deno_isolate_init() -> StartupData<'static> {
   if cfg!(feature = "no-snapshot-init") {
     debug!("Deno isolate init without snapshots.");
-    #[cfg(not(feature = "check-only"))]
-    let source_bytes =
-      include_bytes!(concat!(env!("GN_OUT_DIR"), "/gen/cli/bundle/main.js"));
-    #[cfg(feature = "check-only")]
-    let source_bytes = b"";
-
-    StartupData::Script(Script {
-      filename: "gen/cli/bundle/main.js",
-      source: std::str::from_utf8(&source_bytes[..]).unwrap(),
-    })
+    StartupData::Script(deno_isolate_script())
   } else {
     debug!("Deno isolate init with snapshots.");
     #[cfg(not(any(feature = "check-only", feature = "no-snapshot-init")))]
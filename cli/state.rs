@@ -1,18 +1,23 @@
 // Copyright 2018-2019 the Deno authors. All rights reserved. MIT license.
 use crate::compiler::compile_async;
+use crate::compiler::CompilerPool;
+use crate::compiler::CompilerWorker;
 use crate::compiler::ModuleMetaData;
+use crate::cookie_jar::CookieJar;
 use crate::deno_dir;
 use crate::errors::DenoError;
 use crate::errors::DenoResult;
 use crate::flags;
 use crate::global_timer::GlobalTimer;
 use crate::import_map::ImportMap;
+use crate::lockfile::Lockfile;
 use crate::msg;
 use crate::ops;
 use crate::permissions::DenoPermissions;
 use crate::progress::Progress;
 use crate::resources;
 use crate::resources::ResourceId;
+use crate::scheme_handlers;
 use crate::tokio_util;
 use crate::worker::resolve_module_spec;
 use crate::worker::Worker;
@@ -29,6 +34,7 @@ use std::collections::HashSet;
 use std::env;
 use std::fs;
 use std::ops::Deref;
+use std::path::PathBuf;
 use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
 use std::sync::Mutex;
@@ -39,6 +45,7 @@ pub type WorkerSender = async_mpsc::Sender<Buf>;
 pub type WorkerReceiver = async_mpsc::Receiver<Buf>;
 pub type WorkerChannels = (WorkerSender, WorkerReceiver);
 pub type UserWorkerTable = HashMap<ResourceId, Shared<Worker>>;
+pub type ExitHook = Box<dyn FnMut(&ThreadSafeState) + Send>;
 
 #[derive(Default)]
 pub struct Metrics {
@@ -61,7 +68,8 @@ pub struct State {
   pub main_module: Option<String>,
   pub dir: deno_dir::DenoDir,
   pub argv: Vec<String>,
-  pub permissions: DenoPermissions,
+  /// Shared with the compiler worker.
+  pub permissions: Arc<DenoPermissions>,
   pub flags: flags::DenoFlags,
   /// When flags contains a `.config_path` option, the content of the
   /// configuration file will be resolved and set.
@@ -76,17 +84,27 @@ pub struct State {
   pub worker_channels: Mutex<WorkerChannels>,
   pub global_timer: Mutex<GlobalTimer>,
   pub workers: Mutex<UserWorkerTable>,
+  /// The compiler worker, once something was compiled.
+  pub compiler: Mutex<Option<Arc<CompilerWorker>>>,
+  /// The compiler workers which this state and the states of the workers
+  /// started from it pick their compiler from.
+  pub compilers: CompilerPool,
   pub start_time: Instant,
   /// A reference to this worker's resource.
   pub resource: resources::Resource,
   pub dispatch_selector: ops::OpSelector,
   /// Reference to global progress bar.
   pub progress: Progress,
+  /// Cookies used by `fetch()` requests with `credentials: "include"`.
+  pub cookie_jar: CookieJar,
+  /// Called by `Deno.exit()` before the process exits, e.g. to write the
+  /// coverage of `--coverage`.
+  pub exit_hook: Mutex<Option<ExitHook>>,
 
   /// Set of all URLs that have been compiled. This is a hacky way to work
   /// around the fact that --reload will force multiple compilations of the same
-  /// module.
-  compiled: Mutex<HashSet<String>>,
+  /// module. It is shared by all the states created with `new_child()`.
+  compiled: Arc<Mutex<HashSet<String>>>,
 }
 
 impl Clone for ThreadSafeState {
@@ -185,6 +203,9 @@ impl Loader for ThreadSafeState {
       }
     }
 
+    if let Some(result) = scheme_handlers::resolve(specifier, referrer) {
+      return result.map(|url| url.to_string()).map_err(DenoError::from);
+    }
     resolve_module_spec(specifier, referrer).map_err(DenoError::from)
   }
 
@@ -212,14 +233,40 @@ impl ThreadSafeState {
     argv_rest: Vec<String>,
     dispatch_selector: ops::OpSelector,
     progress: Progress,
+  ) -> Self {
+    let mut import_map = None;
+    if let Some(file_name) = &flags.import_map_path {
+      match ImportMap::load_file(file_name) {
+        Ok(map) => import_map = Some(map),
+        Err(err) => {
+          println!("{:?}", err);
+          panic!("Error parsing import map");
+        }
+      }
+    }
+
+    Self::new_with_import_map(
+      flags,
+      argv_rest,
+      dispatch_selector,
+      progress,
+      import_map,
+    )
+  }
+
+  /// Like `new()`, but uses the given import map instead of loading the one
+  /// passed with `--importmap`. Used for workers, which either inherit the
+  /// import map of their parent or are created with their own.
+  pub fn new_with_import_map(
+    flags: flags::DenoFlags,
+    argv_rest: Vec<String>,
+    dispatch_selector: ops::OpSelector,
+    progress: Progress,
+    import_map: Option<ImportMap>,
   ) -> Self {
     let custom_root = env::var("DENO_DIR").map(String::into).ok();
 
-    let (worker_in_tx, worker_in_rx) = async_mpsc::channel::<Buf>(1);
-    let (worker_out_tx, worker_out_rx) = async_mpsc::channel::<Buf>(1);
-    let internal_channels = (worker_out_tx, worker_in_rx);
-    let external_channels = (worker_in_tx, worker_out_rx);
-    let resource = resources::add_worker(external_channels);
+    let (internal_channels, resource) = new_worker_channels();
 
     // take the passed flag and resolve the file name relative to the cwd
     let config_file = match &flags.config_path {
@@ -260,46 +307,35 @@ impl ThreadSafeState {
       _ => None,
     };
 
-    let dir =
-      deno_dir::DenoDir::new(custom_root, &config, progress.clone()).unwrap();
-
-    let main_module: Option<String> = if argv_rest.len() <= 1 {
-      None
-    } else {
-      let specifier = argv_rest[1].clone();
-      let referrer = ".";
-      // TODO: does this really have to be resolved by DenoDir?
-      //  Maybe we can call `resolve_module_spec`
-      match dir.resolve_module_url(&specifier, referrer) {
-        Ok(url) => Some(url.to_string()),
-        Err(e) => {
-          debug!("Potentially swallowed error {}", e);
-          None
-        }
+    let lockfile = flags.lock.as_ref().map(|lock| {
+      match Lockfile::new(PathBuf::from(lock), flags.lock_write) {
+        Ok(lockfile) => Arc::new(lockfile),
+        Err(err) => panic!("Error loading lock file \"{}\": {}", lock, err),
       }
+    });
+
+    let dir = deno_dir::DenoDir::new(
+      custom_root,
+      &config,
+      lockfile,
+      flags.reload_whitelist.clone(),
+      flags.revalidate,
+      progress.clone(),
+    ).unwrap();
+
+    let cookie_jar = if flags.persist_cookies {
+      CookieJar::load(dir.root.join("cookies.json"))
+    } else {
+      CookieJar::new()
     };
 
-    let mut import_map = None;
-    if let Some(file_name) = &flags.import_map_path {
-      let base_url = match &main_module {
-        Some(url) => url,
-        None => unreachable!(),
-      };
-
-      match ImportMap::load(base_url, file_name) {
-        Ok(map) => import_map = Some(map),
-        Err(err) => {
-          println!("{:?}", err);
-          panic!("Error parsing import map");
-        }
-      }
-    }
+    let main_module = resolve_main_module(&dir, &argv_rest);
 
     ThreadSafeState(Arc::new(State {
       main_module,
       dir,
       argv: argv_rest,
-      permissions: DenoPermissions::from_flags(&flags),
+      permissions: Arc::new(DenoPermissions::from_flags(&flags)),
       flags,
       config,
       config_path,
@@ -308,11 +344,82 @@ impl ThreadSafeState {
       worker_channels: Mutex::new(internal_channels),
       global_timer: Mutex::new(GlobalTimer::new()),
       workers: Mutex::new(UserWorkerTable::new()),
+      compiler: Mutex::new(None),
+      compilers: CompilerPool::default(),
       start_time: Instant::now(),
       resource,
       dispatch_selector,
       progress,
-      compiled: Mutex::new(HashSet::new()),
+      cookie_jar,
+      exit_hook: Mutex::new(None),
+      compiled: Arc::new(Mutex::new(HashSet::new())),
+    }))
+  }
+
+  /// Creates the state of a worker started by `parent`, with the given import
+  /// map and the permissions given by the flags. It shares the DENO_DIR (and
+  /// so the lock file), the cookie jar, the compiler workers and the set of
+  /// compiled modules with `parent`.
+  pub fn new_child(
+    parent: &ThreadSafeState,
+    argv_rest: Vec<String>,
+    dispatch_selector: ops::OpSelector,
+    import_map: Option<ImportMap>,
+  ) -> Self {
+    let permissions = Arc::new(DenoPermissions::from_flags(&parent.flags));
+    Self::new_child_with_permissions(
+      parent,
+      argv_rest,
+      dispatch_selector,
+      import_map,
+      permissions,
+    )
+  }
+
+  /// Creates the state of the compiler worker started by `parent`. Unlike
+  /// `new_child()`, it also shares the permissions of `parent`.
+  pub fn new_for_compiler(parent: &ThreadSafeState) -> Self {
+    Self::new_child_with_permissions(
+      parent,
+      parent.argv.clone(),
+      ops::op_selector_std,
+      parent.import_map.clone(),
+      parent.permissions.clone(),
+    )
+  }
+
+  fn new_child_with_permissions(
+    parent: &ThreadSafeState,
+    argv_rest: Vec<String>,
+    dispatch_selector: ops::OpSelector,
+    import_map: Option<ImportMap>,
+    permissions: Arc<DenoPermissions>,
+  ) -> Self {
+    let (internal_channels, resource) = new_worker_channels();
+    let main_module = resolve_main_module(&parent.dir, &argv_rest);
+
+    ThreadSafeState(Arc::new(State {
+      main_module,
+      dir: parent.dir.clone(),
+      argv: argv_rest,
+      permissions,
+      flags: parent.flags.clone(),
+      config: parent.config.clone(),
+      config_path: parent.config_path.clone(),
+      import_map,
+      metrics: Metrics::default(),
+      worker_channels: Mutex::new(internal_channels),
+      global_timer: Mutex::new(GlobalTimer::new()),
+      workers: Mutex::new(UserWorkerTable::new()),
+      compiler: Mutex::new(None),
+      compilers: parent.compilers.clone(),
+      start_time: Instant::now(),
+      resource,
+      dispatch_selector,
+      progress: parent.progress.clone(),
+      cookie_jar: parent.cookie_jar.clone(),
+      exit_hook: Mutex::new(None),
+      compiled: parent.compiled.clone(),
     }))
   }
 
@@ -399,6 +506,38 @@ impl ThreadSafeState {
   }
 }
 
+/// Creates the channels of a worker, and adds the end through which the host
+/// talks to it to the resource table.
+fn new_worker_channels() -> (WorkerChannels, resources::Resource) {
+  let (worker_in_tx, worker_in_rx) = async_mpsc::channel::<Buf>(1);
+  let (worker_out_tx, worker_out_rx) = async_mpsc::channel::<Buf>(1);
+  let internal_channels = (worker_out_tx, worker_in_rx);
+  let external_channels = (worker_in_tx, worker_out_rx);
+  let resource = resources::add_worker(external_channels);
+  (internal_channels, resource)
+}
+
+/// Resolves the main module, the first argument in `argv_rest`.
+fn resolve_main_module(
+  dir: &deno_dir::DenoDir,
+  argv_rest: &[String],
+) -> Option<String> {
+  if argv_rest.len() <= 1 {
+    return None;
+  }
+  let specifier = &argv_rest[1];
+  let referrer = ".";
+  // TODO: does this really have to be resolved by DenoDir?
+  //  Maybe we can call `resolve_module_spec`
+  match dir.resolve_module_url(specifier, referrer) {
+    Ok(url) => Some(url.to_string()),
+    Err(e) => {
+      debug!("Potentially swallowed error {}", e);
+      None
+    }
+  }
+}
+
 #[test]
 fn thread_safe() {
   fn f<S: Send + Sync>(_: S) {}
@@ -407,3 +546,54 @@ fn thread_safe() {
     String::from("hello.js"),
   ]));
 }
+
+#[test]
+fn import_map_scopes() {
+  let json_map = r#"{
+    "imports": { "moment": "./moment.ts" },
+    "scopes": { "scope/": { "moment": "./scoped_moment.ts" } }
+  }"#;
+  let import_map =
+    ImportMap::from_json("file:///worker/import_map.json", json_map).unwrap();
+  let state = ThreadSafeState::new_with_import_map(
+    flags::DenoFlags::default(),
+    vec![String::from("./deno")],
+    ops::op_selector_std,
+    Progress::new(),
+    Some(import_map),
+  );
+  assert_eq!(
+    state.resolve("moment", "file:///worker/main.ts", false).unwrap(),
+    "file:///worker/moment.ts"
+  );
+  assert_eq!(
+    state
+      .resolve("moment", "file:///worker/scope/mod.ts", false)
+      .unwrap(),
+    "file:///worker/scoped_moment.ts"
+  );
+  // The import map doesn't apply to root modules.
+  assert_eq!(
+    state.resolve("./moment", "file:///worker/main.ts", true).unwrap(),
+    "file:///worker/moment"
+  );
+}
+
+#[test]
+fn new_child_shares_compiled() {
+  let parent = ThreadSafeState::mock(vec![String::from("./deno")]);
+  let child = ThreadSafeState::new_child(
+    &parent,
+    vec![String::from("./deno")],
+    ops::op_selector_std,
+    None,
+  );
+  child.mark_compiled("file:///child.ts");
+  assert!(parent.has_compiled("file:///child.ts"));
+  assert!(!Arc::ptr_eq(&child.permissions, &parent.permissions));
+
+  let compiler_state = ThreadSafeState::new_for_compiler(&parent);
+  compiler_state.mark_compiled("file:///compiled.ts");
+  assert!(parent.has_compiled("file:///compiled.ts"));
+  assert!(Arc::ptr_eq(&compiler_state.permissions, &parent.permissions));
+}
@@ -0,0 +1,550 @@
+// Copyright 2018-2019 the Deno authors. All rights reserved. MIT license.
+//! `deno test` finds the test modules, runs each of them in a worker of its
+//! own and reports the results. The tests of a module are registered with
+//! `Deno.test()` and run by `testMain()` in js/testing.ts, which posts a
+//! `TestMessage` to the runner for each of them.
+use crate::ansi;
+use regex::Regex;
+use std::collections::HashSet;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::path::PathBuf;
+
+/// Test modules are the files whose name ends with this.
+const TEST_SUFFIX: &str = "_test.ts";
+
+/// Keep in sync with `TestMessage` in js/testing.ts.
+#[derive(Debug, Deserialize, PartialEq)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum TestMessage {
+  Plan { total: usize, filtered: usize },
+  Result {
+    name: String,
+    error: Option<String>,
+    duration: u64,
+  },
+  End,
+}
+
+impl TestMessage {
+  pub fn parse(msg: &[u8]) -> Option<TestMessage> {
+    serde_json::from_slice(msg).ok()
+  }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct TestResult {
+  pub name: String,
+  pub error: Option<String>,
+  /// In milliseconds.
+  pub duration: u64,
+}
+
+/// The results of a test module.
+#[derive(Debug, Default, PartialEq)]
+pub struct FileResult {
+  pub name: String,
+  pub tests: Vec<TestResult>,
+  pub filtered: usize,
+  /// Set if the module couldn't be loaded, or failed outside of its tests.
+  pub error: Option<String>,
+  /// In milliseconds.
+  pub duration: u64,
+}
+
+impl FileResult {
+  pub fn new(name: String) -> Self {
+    FileResult {
+      name,
+      ..FileResult::default()
+    }
+  }
+
+  pub fn failed(&self) -> bool {
+    self.error.is_some() || self.tests.iter().any(|test| test.error.is_some())
+  }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Reporter {
+  /// Prints the results as the tests run.
+  Pretty,
+  Tap,
+  Junit,
+}
+
+impl Reporter {
+  pub fn from_name(name: Option<&str>) -> Reporter {
+    match name {
+      Some("tap") => Reporter::Tap,
+      Some("junit") => Reporter::Junit,
+      _ => Reporter::Pretty,
+    }
+  }
+}
+
+/// Turns a glob into a regex which matches whole paths. `**` matches any
+/// number of directories, `*` and `?` match within a path component.
+fn glob_to_regex(glob: &str) -> Regex {
+  let mut regex = String::from("^");
+  let mut chars = glob.chars().peekable();
+  while let Some(c) = chars.next() {
+    match c {
+      '*' if chars.peek() == Some(&'*') => {
+        chars.next();
+        if chars.peek() == Some(&'/') {
+          chars.next();
+          regex.push_str("(?:.*/)?");
+        } else {
+          regex.push_str(".*");
+        }
+      }
+      '*' => regex.push_str("[^/]*"),
+      '?' => regex.push_str("[^/]"),
+      c => regex.push_str(&regex::escape(&c.to_string())),
+    }
+  }
+  regex.push('$');
+  Regex::new(&regex).unwrap()
+}
+
+fn is_glob(pattern: &str) -> bool {
+  pattern.contains(|c| c == '*' || c == '?')
+}
+
+/// Returns the files below `dir`, skipping hidden directories and
+/// `node_modules`. Symlinks to directories are followed, but each directory
+/// is only walked once, so cycles of symlinks end.
+fn walk(dir: &Path, files: &mut Vec<PathBuf>) -> io::Result<()> {
+  walk_unvisited(dir, &mut HashSet::new(), files)
+}
+
+fn walk_unvisited(
+  dir: &Path,
+  visited: &mut HashSet<PathBuf>,
+  files: &mut Vec<PathBuf>,
+) -> io::Result<()> {
+  if !visited.insert(dir.canonicalize()?) {
+    return Ok(());
+  }
+  for entry in fs::read_dir(dir)? {
+    let path = entry?.path();
+    if path.is_dir() {
+      let name = path.file_name().unwrap().to_string_lossy();
+      if !name.starts_with('.') && name != "node_modules" {
+        walk_unvisited(&path, visited, files)?;
+      }
+    } else {
+      files.push(path);
+    }
+  }
+  Ok(())
+}
+
+/// Returns the test modules which `patterns` name, sorted. Directories are
+/// searched for files ending with `_test.ts`, globs match any file, and other
+/// files are taken as they are. Without patterns, the current directory is
+/// searched.
+pub fn find_test_files(patterns: &[String]) -> io::Result<Vec<PathBuf>> {
+  let default_patterns = [".".to_string()];
+  let patterns = if patterns.is_empty() {
+    &default_patterns[..]
+  } else {
+    patterns
+  };
+  let mut test_files = Vec::new();
+  for pattern in patterns {
+    let path = Path::new(pattern);
+    if is_glob(pattern) {
+      // Only walk the directories before the first wildcard.
+      let base: PathBuf = path
+        .components()
+        .take_while(|c| !is_glob(&c.as_os_str().to_string_lossy()))
+        .collect();
+      let dir = if base.as_os_str().is_empty() {
+        Path::new(".")
+      } else {
+        base.as_path()
+      };
+      let regex = glob_to_regex(pattern.trim_start_matches("./"));
+      let mut files = Vec::new();
+      if dir.is_dir() {
+        walk(dir, &mut files)?;
+      }
+      test_files.extend(files.into_iter().filter(|file| {
+        let file = file.to_string_lossy().replace('\\', "/");
+        regex.is_match(file.trim_start_matches("./"))
+      }));
+    } else if path.is_dir() {
+      let mut files = Vec::new();
+      walk(path, &mut files)?;
+      test_files.extend(files.into_iter().filter(|file| {
+        file.to_string_lossy().ends_with(TEST_SUFFIX)
+      }));
+    } else {
+      test_files.push(path.to_path_buf());
+    }
+  }
+  test_files.sort();
+  test_files.dedup();
+  Ok(test_files)
+}
+
+/// The first line of an error, e.g. "Error: boom" of its stack.
+fn first_line(error: &str) -> &str {
+  error.lines().next().unwrap_or("")
+}
+
+fn format_duration(ms: u64) -> String {
+  ansi::grey(format!("({}ms)", ms)).to_string()
+}
+
+pub fn pretty_plan(file: &FileResult, total: usize) -> String {
+  format!("running {} tests from {}", total, file.name)
+}
+
+pub fn pretty_result(test: &TestResult) -> String {
+  let status = match test.error {
+    None => ansi::green("ok".to_string()).to_string(),
+    Some(_) => ansi::red("FAILED".to_string()).to_string(),
+  };
+  format!(
+    "test {} ... {} {}",
+    test.name,
+    status,
+    format_duration(test.duration)
+  )
+}
+
+/// The failures and the summary printed at the end of a run.
+pub fn pretty_summary(results: &[FileResult], duration: u64) -> String {
+  let mut out = String::new();
+  let mut failures = Vec::new();
+  let (mut passed, mut failed, mut filtered) = (0, 0, 0);
+  for file in results {
+    filtered += file.filtered;
+    if let Some(ref error) = file.error {
+      failed += 1;
+      failures.push((file.name.clone(), error));
+    }
+    for test in file.tests.iter() {
+      match test.error {
+        None => passed += 1,
+        Some(ref error) => {
+          failed += 1;
+          failures.push((format!("{}: {}", file.name, test.name), error));
+        }
+      }
+    }
+  }
+  if !failures.is_empty() {
+    out.push_str("\nfailures:\n");
+    for (name, error) in failures {
+      out.push_str(&format!("\n{}\n{}\n", ansi::bold(name), error));
+    }
+  }
+  let result = if failed == 0 {
+    ansi::green("ok".to_string()).to_string()
+  } else {
+    ansi::red("FAILED".to_string()).to_string()
+  };
+  out.push_str(&format!(
+    "\ntest result: {}. {} passed; {} failed; {} filtered out {}\n",
+    result,
+    passed,
+    failed,
+    filtered,
+    format_duration(duration)
+  ));
+  out
+}
+
+/// Writes the results in the Test Anything Protocol, version 13.
+pub fn tap_report(results: &[FileResult]) -> String {
+  let mut out = String::from("TAP version 13\n");
+  let mut n = 0;
+  for file in results {
+    out.push_str(&format!("# {}\n", file.name));
+    let mut outcomes: Vec<(&str, Option<&String>, u64)> = file
+      .tests
+      .iter()
+      .map(|test| (test.name.as_str(), test.error.as_ref(), test.duration))
+      .collect();
+    if let Some(ref error) = file.error {
+      outcomes.push((file.name.as_str(), Some(error), file.duration));
+    }
+    for (name, error, duration) in outcomes {
+      n += 1;
+      let status = if error.is_some() { "not ok" } else { "ok" };
+      out.push_str(&format!(
+        "{} {} - {} # time={}ms\n",
+        status, n, name, duration
+      ));
+      if let Some(error) = error {
+        out.push_str("  ---\n");
+        out.push_str(&format!("  file: {}\n", file.name));
+        out.push_str("  message: |\n");
+        for line in error.lines() {
+          out.push_str(&format!("    {}\n", line));
+        }
+        out.push_str("  ...\n");
+      }
+    }
+  }
+  out.push_str(&format!("1..{}\n", n));
+  out
+}
+
+fn xml_escape(s: &str) -> String {
+  s.replace('&', "&amp;")
+    .replace('<', "&lt;")
+    .replace('>', "&gt;")
+    .replace('"', "&quot;")
+}
+
+fn seconds(ms: u64) -> String {
+  format!("{}.{:03}", ms / 1000, ms % 1000)
+}
+
+/// Writes the results as JUnit XML, with a test suite for each module.
+pub fn junit_report(results: &[FileResult], duration: u64) -> String {
+  let count = |file: &FileResult| file.tests.len() + file.error.iter().count();
+  let failures = |file: &FileResult| {
+    file.tests.iter().filter(|test| test.error.is_some()).count()
+  };
+  let errors = |file: &FileResult| file.error.iter().count();
+
+  let mut out = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+  out.push_str(&format!(
+    "<testsuites tests=\"{}\" failures=\"{}\" errors=\"{}\" time=\"{}\">\n",
+    results.iter().map(count).sum::<usize>(),
+    results.iter().map(failures).sum::<usize>(),
+    results.iter().map(errors).sum::<usize>(),
+    seconds(duration)
+  ));
+  for file in results {
+    let name = xml_escape(&file.name);
+    out.push_str(&format!(
+      "  <testsuite name=\"{}\" tests=\"{}\" failures=\"{}\" errors=\"{}\" \
+       time=\"{}\">\n",
+      name,
+      count(file),
+      failures(file),
+      errors(file),
+      seconds(file.duration)
+    ));
+    let mut outcomes: Vec<_> = file
+      .tests
+      .iter()
+      .map(|test| {
+        let error = test.error.as_ref().map(|error| ("failure", error));
+        (test.name.as_str(), error, test.duration)
+      }).collect();
+    if let Some(ref error) = file.error {
+      outcomes.push((file.name.as_str(), Some(("error", error)), file.duration));
+    }
+    for (test_name, error, duration) in outcomes {
+      out.push_str(&format!(
+        "    <testcase name=\"{}\" classname=\"{}\" time=\"{}\"",
+        xml_escape(test_name),
+        name,
+        seconds(duration)
+      ));
+      match error {
+        None => out.push_str("/>\n"),
+        Some((tag, error)) => {
+          out.push_str(&format!(
+            ">\n      <{} message=\"{}\">{}</{}>\n    </testcase>\n",
+            tag,
+            xml_escape(first_line(error)),
+            xml_escape(error),
+            tag
+          ));
+        }
+      }
+    }
+    out.push_str("  </testsuite>\n");
+  }
+  out.push_str("</testsuites>\n");
+  out
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use tempfile::TempDir;
+
+  fn results() -> Vec<FileResult> {
+    vec![
+      FileResult {
+        name: "math_test.ts".to_string(),
+        tests: vec![
+          TestResult {
+            name: "addition".to_string(),
+            error: None,
+            duration: 3,
+          },
+          TestResult {
+            name: "division".to_string(),
+            error: Some("Error: 1 < 2\n    at division".to_string()),
+            duration: 1250,
+          },
+        ],
+        filtered: 1,
+        error: None,
+        duration: 1260,
+      },
+      FileResult {
+        name: "broken_test.ts".to_string(),
+        error: Some("SyntaxError: Unexpected token".to_string()),
+        ..FileResult::default()
+      },
+    ]
+  }
+
+  #[test]
+  fn test_parse_message() {
+    assert_eq!(
+      TestMessage::parse(br#"{"type":"plan","total":3,"filtered":1}"#),
+      Some(TestMessage::Plan {
+        total: 3,
+        filtered: 1
+      })
+    );
+    assert_eq!(
+      TestMessage::parse(br#"{"type":"result","name":"a","duration":5}"#),
+      Some(TestMessage::Result {
+        name: "a".to_string(),
+        error: None,
+        duration: 5
+      })
+    );
+    assert_eq!(
+      TestMessage::parse(br#"{"type":"end"}"#),
+      Some(TestMessage::End)
+    );
+    assert_eq!(TestMessage::parse(br#""hello from a test""#), None);
+  }
+
+  #[test]
+  fn test_glob_to_regex() {
+    let regex = glob_to_regex("src/**/*_test.ts");
+    assert!(regex.is_match("src/a_test.ts"));
+    assert!(regex.is_match("src/util/b_test.ts"));
+    assert!(!regex.is_match("src/a_test.js"));
+    assert!(!regex.is_match("lib/a_test.ts"));
+    let regex = glob_to_regex("tests/?_test.ts");
+    assert!(regex.is_match("tests/a_test.ts"));
+    assert!(!regex.is_match("tests/ab_test.ts"));
+    assert!(!regex.is_match("tests/sub/a_test.ts"));
+  }
+
+  #[test]
+  fn test_find_test_files() {
+    let dir = TempDir::new().expect("tempdir fail");
+    let root = dir.path();
+    fs::create_dir_all(root.join("src/util")).unwrap();
+    fs::create_dir_all(root.join(".git")).unwrap();
+    for file in &[
+      "src/a_test.ts",
+      "src/a.ts",
+      "src/util/b_test.ts",
+      "src/util/c.test.ts",
+      ".git/d_test.ts",
+    ] {
+      fs::write(root.join(file), "").unwrap();
+    }
+
+    let found = find_test_files(&[root.to_string_lossy().to_string()]);
+    assert_eq!(
+      found.unwrap(),
+      vec![root.join("src/a_test.ts"), root.join("src/util/b_test.ts")]
+    );
+
+    let glob = format!("{}/src/**/*.test.ts", root.to_string_lossy());
+    let found = find_test_files(&[glob]);
+    assert_eq!(found.unwrap(), vec![root.join("src/util/c.test.ts")]);
+
+    let file = root.join("src/a.ts").to_string_lossy().to_string();
+    let found = find_test_files(&[file.clone(), file]);
+    assert_eq!(found.unwrap(), vec![root.join("src/a.ts")]);
+  }
+
+  #[cfg(unix)]
+  #[test]
+  fn test_find_test_files_symlink_cycle() {
+    let dir = TempDir::new().expect("tempdir fail");
+    let root = dir.path();
+    fs::create_dir_all(root.join("src")).unwrap();
+    fs::write(root.join("src/a_test.ts"), "").unwrap();
+    std::os::unix::fs::symlink(root, root.join("src/root")).unwrap();
+
+    let found = find_test_files(&[root.to_string_lossy().to_string()]);
+    assert_eq!(found.unwrap(), vec![root.join("src/a_test.ts")]);
+  }
+
+  #[test]
+  fn test_file_result_failed() {
+    let results = results();
+    assert!(results[0].failed());
+    assert!(results[1].failed());
+    assert!(!FileResult::new("ok_test.ts".to_string()).failed());
+  }
+
+  #[test]
+  fn test_pretty_summary() {
+    let summary = ansi::strip_ansi_codes(&pretty_summary(&results(), 1300))
+      .to_string();
+    assert!(summary.contains("\nmath_test.ts: division\nError: 1 < 2\n"));
+    assert!(summary.contains("\nbroken_test.ts\nSyntaxError"));
+    assert!(summary.ends_with(
+      "test result: FAILED. 1 passed; 2 failed; 1 filtered out (1300ms)\n"
+    ));
+  }
+
+  #[test]
+  fn test_tap_report() {
+    assert_eq!(
+      tap_report(&results()),
+      "TAP version 13
+# math_test.ts
+ok 1 - addition # time=3ms
+not ok 2 - division # time=1250ms
+  ---
+  file: math_test.ts
+  message: |
+    Error: 1 < 2
+        at division
+  ...
+# broken_test.ts
+not ok 3 - broken_test.ts # time=0ms
+  ---
+  file: broken_test.ts
+  message: |
+    SyntaxError: Unexpected token
+  ...
+1..3
+"
+    );
+  }
+
+  #[test]
+  fn test_junit_report() {
+    let report = junit_report(&results(), 1300);
+    assert!(report.starts_with(
+      "<?xml version=\"1.0\" encoding=\"UTF-8\"?>
+<testsuites tests=\"3\" failures=\"1\" errors=\"1\" time=\"1.300\">
+  <testsuite name=\"math_test.ts\" tests=\"2\" failures=\"1\" errors=\"0\" time=\"1.260\">
+    <testcase name=\"addition\" classname=\"math_test.ts\" time=\"0.003\"/>
+    <testcase name=\"division\" classname=\"math_test.ts\" time=\"1.250\">
+      <failure message=\"Error: 1 &lt; 2\">Error: 1 &lt; 2
+    at division</failure>
+    </testcase>
+  </testsuite>
+"
+    ));
+    assert!(report.contains("<error message=\"SyntaxError: Unexpected token\">"));
+    assert!(report.ends_with("</testsuites>\n"));
+  }
+}
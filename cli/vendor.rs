@@ -0,0 +1,200 @@
+// Copyright 2018-2019 the Deno authors. All rights reserved. MIT license.
+//! Implements `deno vendor`, which copies every remote module of a program
+//! into a local directory along with an import map that points the remote
+//! URLs at the copies.
+use crate::deno_dir;
+use crate::deno_dir::DenoDir;
+use crate::errors::DenoResult;
+use crate::fs as deno_fs;
+use deno::Deps;
+use serde_json;
+use std::collections::BTreeMap;
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::Path;
+use url::Url;
+
+pub const IMPORT_MAP_FILENAME: &str = "import_map.json";
+
+/// Returns the sorted URLs of all remote modules in a dependency tree.
+pub fn remote_modules(deps: &Deps) -> Vec<String> {
+  fn collect(deps: &Deps, out: &mut BTreeSet<String>) {
+    if deno_dir::is_remote(&deps.name) {
+      out.insert(deps.name.clone());
+    }
+    if let Some(ref children) = deps.deps {
+      for child in children {
+        collect(child, out);
+      }
+    }
+  }
+  let mut out = BTreeSet::new();
+  collect(deps, &mut out);
+  out.into_iter().collect()
+}
+
+/// Copies the cached source of each remote module in `module_names` into
+/// `vendor_dir`, using the same layout as `$DENO_DIR/deps`, and writes an
+/// import map to `vendor_dir/import_map.json` which maps each of the modules
+/// to its copy. The modules must already be in the cache. Returns the number
+/// of files written.
+pub fn vendor(
+  deno_dir: &DenoDir,
+  module_names: &[String],
+  vendor_dir: &Path,
+) -> DenoResult<usize> {
+  let mut imports = BTreeMap::new();
+  let mut vendored = BTreeSet::new();
+
+  for module_name in module_names {
+    let out = deno_dir.fetch_module_meta_data(module_name, ".", true, true)?;
+    let url = Url::parse(&out.module_name)?;
+
+    // Only the modules which were vendored are mapped, so that the URLs of
+    // other modules, e.g. ones imported dynamically, are still fetched.
+    // Redirected modules live under the URL they redirect to.
+    imports.insert(module_name.clone(), relative_address(&url));
+
+    if !vendored.insert(out.module_name.clone()) {
+      continue;
+    }
+    let filename = deno_dir::get_cache_filename(vendor_dir, &url);
+    if let Some(parent) = filename.parent() {
+      fs::create_dir_all(parent)?;
+    }
+    deno_fs::write_file(&filename, &out.source_code, 0o666)?;
+    // Keep the media type of modules whose extension doesn't reveal it.
+    deno_dir::save_source_code_headers(
+      filename.to_str().unwrap(),
+      deno_dir::get_source_code_headers(&out.filename).mime_type,
+      None,
+      None,
+    );
+  }
+
+  let mut imports_json = serde_json::Map::new();
+  for (specifier, address) in imports {
+    imports_json.insert(specifier, json!(address));
+  }
+  let import_map = json!({ "imports": imports_json });
+  fs::create_dir_all(vendor_dir)?;
+  let mut s = serde_json::to_string_pretty(&import_map).unwrap();
+  s.push('\n');
+  fs::write(vendor_dir.join(IMPORT_MAP_FILENAME), s)?;
+
+  Ok(vendored.len())
+}
+
+/// Path of the vendored copy of `url`, relative to the vendor directory and
+/// thus to the import map.
+fn relative_address(url: &Url) -> String {
+  let path = deno_dir::get_cache_filename(Path::new("."), url);
+  let components: Vec<String> = path
+    .components()
+    .map(|c| c.as_os_str().to_string_lossy().into_owned())
+    .collect();
+  components.join("/")
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::import_map::ImportMap;
+  use crate::progress::Progress;
+  use tempfile::TempDir;
+
+  #[test]
+  fn test_relative_address() {
+    let url =
+      Url::parse("http://localhost:4545/tests/subdir/mod2.ts").unwrap();
+    assert_eq!(
+      relative_address(&url),
+      "./localhost_PORT4545/tests/subdir/mod2.ts"
+    );
+    let url = Url::parse("https://deno.land/").unwrap();
+    assert_eq!(relative_address(&url), "./deno.land");
+  }
+
+  #[test]
+  fn test_vendor() {
+    let temp_dir = TempDir::new().unwrap();
+    let deno_dir = DenoDir::new(
+      Some(temp_dir.path().join("deno_dir")),
+      &None,
+      None,
+      vec![],
+      false,
+      Progress::new(),
+    ).unwrap();
+    let vendor_dir = temp_dir.path().join("vendor");
+
+    // Populate the cache as if the modules had been downloaded.
+    let cached = deno_dir.deps_https.join("deno.land/x");
+    fs::create_dir_all(&cached).unwrap();
+    fs::write(cached.join("mod.ts"), "export * from './util';").unwrap();
+    fs::write(cached.join("util"), "export const a = 1;").unwrap();
+    deno_dir::save_source_code_headers(
+      cached.join("util").to_str().unwrap(),
+      Some("application/typescript".to_string()),
+      None,
+      None,
+    );
+    deno_dir::save_source_code_headers(
+      cached.join("old.ts").to_str().unwrap(),
+      None,
+      Some("https://deno.land/x/mod.ts".to_string()),
+      None,
+    );
+
+    let module_names = vec![
+      "https://deno.land/x/mod.ts".to_string(),
+      "https://deno.land/x/old.ts".to_string(),
+      "https://deno.land/x/util".to_string(),
+    ];
+    let count = vendor(&deno_dir, &module_names, &vendor_dir).unwrap();
+    assert_eq!(count, 2);
+
+    let vendored = vendor_dir.join("deno.land/x");
+    assert_eq!(
+      fs::read_to_string(vendored.join("mod.ts")).unwrap(),
+      "export * from './util';"
+    );
+    assert!(vendored.join("util").exists());
+    assert!(vendored.join("util.headers.json").exists());
+    assert!(!vendored.join("old.ts").exists());
+
+    let import_map_path = vendor_dir.join(IMPORT_MAP_FILENAME);
+    let import_map =
+      ImportMap::load_file(import_map_path.to_str().unwrap()).unwrap();
+    let referrer = "file:///main.ts";
+    let mod_url = Url::from_file_path(vendored.join("mod.ts")).unwrap();
+    assert_eq!(
+      import_map
+        .resolve("https://deno.land/x/mod.ts", referrer)
+        .unwrap(),
+      Some(mod_url.to_string())
+    );
+    assert_eq!(
+      import_map
+        .resolve("https://deno.land/x/old.ts", referrer)
+        .unwrap(),
+      Some(mod_url.to_string())
+    );
+    let util_url = Url::from_file_path(vendored.join("util")).unwrap();
+    assert_eq!(
+      import_map.resolve("https://deno.land/x/util", referrer).unwrap(),
+      Some(util_url.to_string())
+    );
+    // Modules which weren't vendored aren't mapped.
+    assert_eq!(
+      import_map
+        .resolve("https://deno.land/x/new.ts", referrer)
+        .unwrap(),
+      Some("https://deno.land/x/new.ts".to_string())
+    );
+
+    let import_map_json =
+      fs::read_to_string(vendor_dir.join(IMPORT_MAP_FILENAME)).unwrap();
+    assert!(!import_map_json.contains("\"https://deno.land/\""));
+  }
+}
@@ -25,12 +25,32 @@ pub struct Worker {
 
 impl Worker {
   pub fn new(
+    name: String,
+    startup_data: StartupData,
+    state: ThreadSafeState,
+  ) -> Worker {
+    Self::with_config(name, startup_data, state, Config::default())
+  }
+
+  /// Like `new()`, but the isolate can be written to a snapshot with
+  /// `snapshot()`.
+  pub fn new_snapshotter(
+    name: String,
+    startup_data: StartupData,
+    state: ThreadSafeState,
+  ) -> Worker {
+    let mut config = Config::default();
+    config.will_snapshot = true;
+    Self::with_config(name, startup_data, state, config)
+  }
+
+  fn with_config(
     _name: String,
     startup_data: StartupData,
     state: ThreadSafeState,
+    mut config: Config,
   ) -> Worker {
     let state_ = state.clone();
-    let mut config = Config::default();
     config.dispatch(move |control_buf, zero_copy_buf| {
       state_.dispatch(control_buf, zero_copy_buf)
     });
@@ -57,6 +77,32 @@ impl Worker {
     isolate.execute(js_filename, js_source)
   }
 
+  /// Returns a snapshot of the isolate, which must have been created with
+  /// `new_snapshotter()`. The worker can't run anything afterwards.
+  pub fn snapshot(&self) -> Result<Vec<u8>, JSError> {
+    let isolate = self.inner.lock().unwrap();
+    let snapshot = isolate.snapshot()?;
+    Ok(snapshot.as_ref().to_vec())
+  }
+
+  /// Returns a handle which stops the JavaScript running in the worker from
+  /// any thread. It must be taken before running code which may not return.
+  pub fn isolate_handle(&self) -> deno::IsolateHandle {
+    self.inner.lock().unwrap().shared_isolate_handle()
+  }
+
+  /// Starts counting how often the code compiled from now on runs, for
+  /// `--coverage`.
+  pub fn start_coverage(&self) {
+    self.inner.lock().unwrap().start_coverage()
+  }
+
+  /// Returns the coverage counted since `start_coverage()` as JSON, see
+  /// `coverage::Coverage::add()`.
+  pub fn take_coverage(&self) -> String {
+    self.inner.lock().unwrap().take_coverage()
+  }
+
   /// Consumes worker. Executes the provided JavaScript module.
   pub fn execute_mod_async(
     &mut self,
@@ -55,6 +55,8 @@ type DispatchFn = Fn(&[u8], Option<PinnedBuf>) -> Op;
 #[derive(Default)]
 pub struct Config {
   dispatch: Option<Arc<DispatchFn>>,
+  /// Whether `Isolate::snapshot()` will be called. Such isolates have no
+  /// shared queue, Deno.core.dispatch() sends ops with Deno.core.send().
   pub will_snapshot: bool,
 }
 
@@ -115,11 +117,20 @@ impl Isolate {
 
     let needs_init = true;
 
+    // The buffer of the shared queue is outside of V8, so it can't be part of
+    // a snapshot. Isolates which will be snapshotted don't get one, and
+    // Deno.core.dispatch() falls back to Deno.core.send() in them.
+    let shared_buf = if config.will_snapshot {
+      deno_buf::empty()
+    } else {
+      shared.as_deno_buf()
+    };
+
     let mut startup_script: Option<Script> = None;
     let mut libdeno_config = libdeno::deno_config {
       will_snapshot: if config.will_snapshot { 1 } else { 0 },
       load_snapshot: Snapshot2::empty(),
-      shared: shared.as_deno_buf(),
+      shared: shared_buf,
       recv_cb: Self::pre_dispatch,
     };
 
@@ -340,6 +351,19 @@ impl Isolate {
     assert_ne!(snapshot.data_len, 0);
     Ok(snapshot)
   }
+
+  /// Enables V8's precise block coverage of the code compiled from now on.
+  pub fn start_coverage(&self) {
+    unsafe { libdeno::deno_coverage_start(self.libdeno_isolate) }
+  }
+
+  /// Returns the coverage counted since start_coverage() or the last call as
+  /// JSON, in the format described in libdeno/deno.h, and resets the counters.
+  pub fn take_coverage(&self) -> String {
+    let ptr = unsafe { libdeno::deno_coverage_take(self.libdeno_isolate) };
+    let cstr = unsafe { CStr::from_ptr(ptr) };
+    cstr.to_str().unwrap().to_string()
+  }
 }
 
 /// Called during mod_instantiate() to resolve imports.
@@ -506,6 +530,20 @@ impl IsolateHandle {
       }
     }
   }
+
+  /// Like Isolate::take_coverage(), but it can be called by an op while the
+  /// isolate runs JavaScript. It must be called on the isolate's thread.
+  /// Returns an empty list once the isolate was dropped.
+  pub fn take_coverage(&self) -> String {
+    match *self.shared_libdeno_isolate.lock().unwrap() {
+      Some(isolate) => {
+        let ptr = unsafe { libdeno::deno_coverage_take(isolate) };
+        let cstr = unsafe { CStr::from_ptr(ptr) };
+        cstr.to_str().unwrap().to_string()
+      }
+      None => "[]".to_string(),
+    }
+  }
 }
 
 pub fn js_check(r: Result<(), JSError>) {
@@ -984,6 +1022,7 @@ pub mod tests {
       let mut isolate = Isolate::new(StartupData::None, config);
       js_check(isolate.execute("a.js", "a = 1 + 2"));
       let s = isolate.snapshot().unwrap();
+      assert!(!s.as_ref().is_empty());
       drop(isolate);
       s
     };
@@ -992,4 +1031,69 @@ pub mod tests {
     let mut isolate2 = Isolate::new(startup_data, Config::default());
     js_check(isolate2.execute("check.js", "if (a != 3) throw Error('x')"));
   }
+
+  fn sync_op_config(dispatch_count: Arc<AtomicUsize>) -> Config {
+    let mut config = Config::default();
+    config.dispatch(move |control, _| -> Op {
+      dispatch_count.fetch_add(1, Ordering::Relaxed);
+      assert_eq!(control.len(), 1);
+      assert_eq!(control[0], 42);
+      Op::Sync(vec![43u8].into_boxed_slice())
+    });
+    config
+  }
+
+  #[test]
+  fn will_snapshot_ops() {
+    let dispatch_count = Arc::new(AtomicUsize::new(0));
+    let snapshot = {
+      let mut config = sync_op_config(dispatch_count.clone());
+      config.will_snapshot = true;
+      let mut isolate = Isolate::new(StartupData::None, config);
+      // The buffer of the shared queue is outside of V8, so it can't be in
+      // the snapshot. Deno.core.dispatch() sends ops directly instead.
+      js_check(isolate.execute(
+        "a.js",
+        r#"
+        if (Deno.core.shared) throw Error("shared queue set up");
+        const res = Deno.core.dispatch(new Uint8Array([42]));
+        if (res[0] != 43) throw Error("bad response");
+        "#,
+      ));
+      isolate.snapshot().unwrap()
+    };
+    assert_eq!(dispatch_count.load(Ordering::Relaxed), 1);
+
+    // Isolates started from the snapshot set up the shared queue.
+    let startup_data = StartupData::LibdenoSnapshot(snapshot);
+    let config = sync_op_config(dispatch_count.clone());
+    let mut isolate2 = Isolate::new(startup_data, config);
+    js_check(isolate2.execute(
+      "check.js",
+      r#"
+      const res2 = Deno.core.dispatch(new Uint8Array([42]));
+      if (res2[0] != 43) throw Error("bad response");
+      "#,
+    ));
+    assert_eq!(dispatch_count.load(Ordering::Relaxed), 2);
+  }
+
+  #[test]
+  fn coverage() {
+    let mut isolate = Isolate::new(StartupData::None, Config::default());
+    isolate.start_coverage();
+    js_check(isolate.execute("a.js", "function f() {}\nf();\nf();"));
+    let coverage: serde_json::Value =
+      serde_json::from_str(&isolate.take_coverage()).unwrap();
+    let script = coverage
+      .as_array()
+      .unwrap()
+      .iter()
+      .find(|script| script["url"] == "a.js")
+      .unwrap();
+    assert_eq!(script["source"], "function f() {}\nf();\nf();");
+    let f = &script["functions"][1];
+    assert_eq!(f["functionName"], "f");
+    assert_eq!(f["ranges"][0]["count"], 2);
+  }
 }
@@ -63,7 +63,7 @@ impl Deref for deno_buf {
   type Target = [u8];
   #[inline]
   fn deref(&self) -> &[u8] {
-    unsafe { std::slice::from_raw_parts(self.data_ptr, self.data_len) }
+    unsafe { slice::from_raw_parts(self.data_ptr, self.data_len) }
   }
 }
 
@@ -185,6 +185,17 @@ impl Snapshot2<'_> {
   }
 }
 
+/// The snapshot data, e.g. to write it to a file.
+impl AsRef<[u8]> for deno_snapshot<'_> {
+  #[inline]
+  fn as_ref(&self) -> &[u8] {
+    if self.data_ptr.is_null() {
+      return &[];
+    }
+    unsafe { slice::from_raw_parts(self.data_ptr, self.data_len) }
+  }
+}
+
 #[allow(non_camel_case_types)]
 type deno_recv_cb = unsafe extern "C" fn(
   user_data: *mut c_void,
@@ -261,6 +272,8 @@ extern "C" {
     js_source: *const c_char,
   );
   pub fn deno_terminate_execution(i: *const isolate);
+  pub fn deno_coverage_start(i: *const isolate);
+  pub fn deno_coverage_take(i: *const isolate) -> *const c_char;
 
   // Modules
 
@@ -0,0 +1,137 @@
+// Copyright 2018-2019 the Deno authors. All rights reserved. MIT license.
+#include <string>
+
+#include "third_party/v8/include/v8.h"
+#include "third_party/v8/src/base/logging.h"
+#include "third_party/v8/src/debug/debug-interface.h"
+
+#include "deno.h"
+#include "internal.h"
+
+namespace deno {
+
+namespace {
+
+void SetInteger(v8::Local<v8::Context> context, v8::Local<v8::Object> obj,
+                const char* key, int64_t value) {
+  auto* isolate = context->GetIsolate();
+  auto number = v8::Number::New(isolate, static_cast<double>(value));
+  CHECK(obj->Set(context, v8_str(key), number).FromJust());
+}
+
+v8::Local<v8::Object> EncodeRange(v8::Local<v8::Context> context, int start,
+                                  int end, uint32_t count) {
+  auto range_obj = v8::Object::New(context->GetIsolate());
+  SetInteger(context, range_obj, "startOffset", start);
+  SetInteger(context, range_obj, "endOffset", end);
+  SetInteger(context, range_obj, "count", count);
+  return range_obj;
+}
+
+v8::Local<v8::Object> EncodeFunctionCoverage(
+    v8::Local<v8::Context> context,
+    const v8::debug::Coverage::FunctionData& function_data) {
+  auto* isolate = context->GetIsolate();
+  auto function_obj = v8::Object::New(isolate);
+
+  v8::Local<v8::String> name;
+  if (!function_data.Name().ToLocal(&name)) {
+    name = v8_str("");
+  }
+  CHECK(function_obj->Set(context, v8_str("functionName"), name).FromJust());
+  CHECK(function_obj
+            ->Set(context, v8_str("isBlockCoverage"),
+                  v8::Boolean::New(isolate, function_data.HasBlockCoverage()))
+            .FromJust());
+
+  // The first range is the function itself, the others are its blocks.
+  uint32_t block_count = static_cast<uint32_t>(function_data.BlockCount());
+  auto ranges = v8::Array::New(isolate, static_cast<int>(block_count) + 1);
+  CHECK(ranges
+            ->Set(context, 0,
+                  EncodeRange(context, function_data.StartOffset(),
+                              function_data.EndOffset(),
+                              function_data.Count()))
+            .FromJust());
+  for (uint32_t i = 0; i < block_count; ++i) {
+    auto block = function_data.GetBlockData(i);
+    CHECK(ranges
+              ->Set(context, i + 1,
+                    EncodeRange(context, block.StartOffset(),
+                                block.EndOffset(), block.Count()))
+              .FromJust());
+  }
+  CHECK(function_obj->Set(context, v8_str("ranges"), ranges).FromJust());
+  return function_obj;
+}
+
+// Encodes the coverage like the inspector's Profiler.takePreciseCoverage does,
+// adding the source of each script as the offsets refer to it.
+std::string EncodeCoverageAsJSON(v8::Local<v8::Context> context,
+                                 const v8::debug::Coverage& coverage) {
+  auto* isolate = context->GetIsolate();
+  v8::HandleScope handle_scope(isolate);
+  v8::Context::Scope context_scope(context);
+
+  auto scripts = v8::Array::New(isolate);
+  uint32_t script_index = 0;
+  for (size_t i = 0; i < coverage.ScriptCount(); ++i) {
+    auto script_data = coverage.GetScriptData(i);
+    auto script = script_data.GetScript();
+    v8::Local<v8::String> name;
+    v8::Local<v8::String> source;
+    if (!script->Name().ToLocal(&name) || !script->Source().ToLocal(&source)) {
+      // Scripts without a name can't be mapped to a module.
+      continue;
+    }
+
+    auto script_obj = v8::Object::New(isolate);
+    CHECK(script_obj->Set(context, v8_str("url"), name).FromJust());
+    CHECK(script_obj->Set(context, v8_str("source"), source).FromJust());
+    uint32_t function_count =
+        static_cast<uint32_t>(script_data.FunctionCount());
+    auto functions = v8::Array::New(isolate, static_cast<int>(function_count));
+    for (uint32_t j = 0; j < function_count; ++j) {
+      auto function_obj =
+          EncodeFunctionCoverage(context, script_data.GetFunctionData(j));
+      CHECK(functions->Set(context, j, function_obj).FromJust());
+    }
+    CHECK(script_obj->Set(context, v8_str("functions"), functions).FromJust());
+    CHECK(scripts->Set(context, script_index++, script_obj).FromJust());
+  }
+
+  auto json_string = v8::JSON::Stringify(context, scripts).ToLocalChecked();
+  v8::String::Utf8Value json_string_(isolate, json_string);
+  return std::string(*json_string_, json_string_.length());
+}
+
+}  // namespace
+
+}  // namespace deno
+
+extern "C" {
+
+void deno_coverage_start(Deno* d_) {
+  auto* d = reinterpret_cast<deno::DenoIsolate*>(d_);
+  auto* isolate = d->isolate_;
+  v8::Locker locker(isolate);
+  v8::Isolate::Scope isolate_scope(isolate);
+  v8::HandleScope handle_scope(isolate);
+  v8::debug::Coverage::SelectMode(isolate,
+                                  v8::debug::CoverageMode::kBlockCount);
+}
+
+const char* deno_coverage_take(Deno* d_) {
+  auto* d = reinterpret_cast<deno::DenoIsolate*>(d_);
+  auto* isolate = d->isolate_;
+  v8::Locker locker(isolate);
+  v8::Isolate::Scope isolate_scope(isolate);
+  v8::HandleScope handle_scope(isolate);
+  auto context = d->context_.Get(isolate);
+  CHECK(!context.IsEmpty());
+  // Collecting precise coverage resets the counters.
+  auto coverage = v8::debug::Coverage::CollectPrecise(isolate);
+  d->coverage_json_ = deno::EncodeCoverageAsJSON(context, coverage);
+  return d->coverage_json_.c_str();
+}
+}
@@ -103,6 +103,16 @@ const char* deno_last_exception(Deno* d);
 
 void deno_terminate_execution(Deno* d);
 
+// Enables V8's precise block coverage. Only code compiled afterwards is
+// covered, so call it before executing the code of interest.
+void deno_coverage_start(Deno* d);
+
+// Returns the coverage counted since deno_coverage_start() or the last call
+// as JSON, and resets the counters. Each script has its url, source and the
+// functions with their ranges, as given by the inspector's
+// Profiler.takePreciseCoverage. The pointer is valid until the next call.
+const char* deno_coverage_take(Deno* d);
+
 // Module API
 
 typedef int deno_mod;
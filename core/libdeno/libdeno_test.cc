@@ -261,3 +261,18 @@ TEST(LibDenoTest, SharedAtomics) {
   EXPECT_EQ(s[2], 2);
   deno_delete(d);
 }
+
+TEST(LibDenoTest, Coverage) {
+  Deno* d = deno_new(deno_config{0, empty_snapshot, empty, nullptr, nullptr});
+  deno_coverage_start(d);
+  deno_execute(d, nullptr, "coverage.js",
+               "function called() {}\n"
+               "function notCalled() {}\n"
+               "called(); called();");
+  EXPECT_EQ(nullptr, deno_last_exception(d));
+  std::string json = deno_coverage_take(d);
+  EXPECT_NE(json.find("\"url\":\"coverage.js\""), std::string::npos);
+  EXPECT_NE(json.find("\"functionName\":\"notCalled\""), std::string::npos);
+  EXPECT_NE(json.find("\"count\":2"), std::string::npos);
+  deno_delete(d);
+}
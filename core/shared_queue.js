@@ -160,6 +160,10 @@ SharedQueue Binary Layout
   }
 
   function dispatch(control, zeroCopy = null) {
+    // Isolates which will be snapshotted have no shared queue.
+    if (!initialized && Deno.core.shared == null) {
+      return window.Deno.core.send(control, zeroCopy);
+    }
     maybeInit();
     // First try to push control to shared.
     const success = push(control);
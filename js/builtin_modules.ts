@@ -0,0 +1,15 @@
+// Copyright 2018-2019 the Deno authors. All rights reserved. MIT license.
+
+// The subset of deno_std which is built into the runtime, and so into its
+// snapshot. Programs import these modules with `std:` specifiers. Their code
+// isn't loaded again: the source of a `std:` module, which is generated by
+// cli/builtin_modules.rs, only re-exports the object here.
+
+import * as colors from "./colors";
+
+/** @internal */
+export const builtinModules: { [url: string]: object } = {
+  // The empty module of the import maps specification.
+  "std:blank": {},
+  "std:colors": colors
+};
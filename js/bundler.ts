@@ -0,0 +1,496 @@
+// Copyright 2018-2019 the Deno authors. All rights reserved. MIT license.
+// Post-processing of the AMD bundle which the compiler emits for
+// `deno bundle`: splitting it into one chunk per entry point plus a chunk of
+// shared modules, and minifying the chunks. Both keep the source map of the
+// bundle accurate.
+import * as ts from "typescript";
+
+/** First line of the bundles written by `deno bundle`, by which `deno run`
+ * recognizes them. It is followed by ` shared=<file>` if the bundle needs a
+ * shared chunk. Keep in sync with cli/bundle.rs. */
+export const BUNDLE_HEADER = "// deno-bundle";
+
+/** The AMD loader which each bundle starts with, so bundles run without
+ * fetching or compiling anything. `__instantiate()` runs a module of the
+ * bundle. `__preload()` only runs the dependencies of a module, which
+ * `__runMain()` then runs later, e.g. after starting from a snapshot. Dynamic
+ * imports are resolved relative to the importing module. It is kept on one
+ * line, so it shifts the source map by a single line. */
+export const BUNDLE_LOADER = [
+  "(function () {",
+  '"use strict";',
+  "if (globalThis.__instantiate) return;",
+  "var modules = Object.create(null);",
+  "var main;",
+  "function define(name, deps, factory) {",
+  "modules[name] = { deps: deps, factory: factory, exports: undefined };",
+  "}",
+  "function resolve(specifier, referrer) {",
+  "if (specifier in modules) return specifier;",
+  "return new URL(specifier, referrer).href;",
+  "}",
+  "function createRequire(referrer) {",
+  "return function (deps, onLoad, onError) {",
+  "try { onLoad(load(resolve(deps[0], referrer))); }",
+  "catch (err) { onError(err); }",
+  "};",
+  "}",
+  "function get(name) {",
+  "var module = modules[name];",
+  "if (!module) {",
+  'throw new Error("Module \\"" + name + "\\" is not in the bundle.");',
+  "}",
+  "return module;",
+  "}",
+  "function load(name) {",
+  "var module = get(name);",
+  "if (!module.exports) {",
+  "module.exports = {};",
+  "var args = module.deps.map(function (dep) {",
+  'if (dep === "require") return createRequire(name);',
+  'if (dep === "exports") return module.exports;',
+  "return load(dep);",
+  "});",
+  "var result = module.factory.apply(undefined, args);",
+  "if (result !== undefined) module.exports = result;",
+  "}",
+  "return module.exports;",
+  "}",
+  "function preload(name) {",
+  "main = name;",
+  "get(name).deps.forEach(function (dep) {",
+  'if (dep !== "require" && dep !== "exports") load(dep);',
+  "});",
+  "}",
+  "globalThis.define = define;",
+  "globalThis.__instantiate = load;",
+  "globalThis.__preload = preload;",
+  "globalThis.__runMain = function () { return load(main); };",
+  "})();"
+].join(" ");
+
+/** A source map as emitted by the TypeScript compiler. */
+export interface RawSourceMap {
+  version: number;
+  file?: string;
+  sourceRoot?: string;
+  sources: string[];
+  sourcesContent?: string[];
+  names: string[];
+  mappings: string;
+}
+
+/** A decoded mapping of a source map. All values are absolute and
+ * zero-based. */
+export interface Mapping {
+  generatedLine: number;
+  generatedColumn: number;
+  source?: number;
+  originalLine?: number;
+  originalColumn?: number;
+  name?: number;
+}
+
+/** JavaScript with the mappings of its source map. */
+export interface Chunk {
+  code: string;
+  mappings: Mapping[];
+}
+
+const BASE64_DIGITS =
+  "ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+function decodeVlqs(segment: string): number[] {
+  const values: number[] = [];
+  let value = 0;
+  let shift = 0;
+  for (const char of segment) {
+    const digit = BASE64_DIGITS.indexOf(char);
+    value += (digit & 31) << shift;
+    if (digit & 32) {
+      shift += 5;
+    } else {
+      values.push(value & 1 ? -(value >>> 1) : value >>> 1);
+      value = 0;
+      shift = 0;
+    }
+  }
+  return values;
+}
+
+function encodeVlq(value: number): string {
+  let vlq = value < 0 ? (-value << 1) | 1 : value << 1;
+  let result = "";
+  do {
+    let digit = vlq & 31;
+    vlq >>>= 5;
+    if (vlq > 0) {
+      digit |= 32;
+    }
+    result += BASE64_DIGITS[digit];
+  } while (vlq > 0);
+  return result;
+}
+
+/** Decodes the `mappings` field of a source map. */
+export function decodeMappings(mappings: string): Mapping[] {
+  const result: Mapping[] = [];
+  let source = 0;
+  let originalLine = 0;
+  let originalColumn = 0;
+  let name = 0;
+  const lines = mappings.split(";");
+  for (let line = 0; line < lines.length; line++) {
+    let generatedColumn = 0;
+    for (const segment of lines[line].split(",")) {
+      if (!segment) {
+        continue;
+      }
+      const values = decodeVlqs(segment);
+      generatedColumn += values[0];
+      const mapping: Mapping = { generatedLine: line, generatedColumn };
+      if (values.length >= 4) {
+        source += values[1];
+        originalLine += values[2];
+        originalColumn += values[3];
+        mapping.source = source;
+        mapping.originalLine = originalLine;
+        mapping.originalColumn = originalColumn;
+        if (values.length >= 5) {
+          name += values[4];
+          mapping.name = name;
+        }
+      }
+      result.push(mapping);
+    }
+  }
+  return result;
+}
+
+/** Encodes mappings, which must be sorted by their generated position, into
+ * the `mappings` field of a source map. */
+export function encodeMappings(mappings: Mapping[]): string {
+  let result = "";
+  let line = 0;
+  let generatedColumn = 0;
+  let source = 0;
+  let originalLine = 0;
+  let originalColumn = 0;
+  let name = 0;
+  let firstOnLine = true;
+  for (const mapping of mappings) {
+    while (line < mapping.generatedLine) {
+      result += ";";
+      line++;
+      generatedColumn = 0;
+      firstOnLine = true;
+    }
+    if (!firstOnLine) {
+      result += ",";
+    }
+    firstOnLine = false;
+    result += encodeVlq(mapping.generatedColumn - generatedColumn);
+    generatedColumn = mapping.generatedColumn;
+    if (mapping.source !== undefined) {
+      result += encodeVlq(mapping.source - source);
+      result += encodeVlq(mapping.originalLine! - originalLine);
+      result += encodeVlq(mapping.originalColumn! - originalColumn);
+      source = mapping.source;
+      originalLine = mapping.originalLine!;
+      originalColumn = mapping.originalColumn!;
+      if (mapping.name !== undefined) {
+        result += encodeVlq(mapping.name - name);
+        name = mapping.name;
+      }
+    }
+  }
+  return result;
+}
+
+/** A top level statement of the bundle, which spans whole lines. */
+interface Statement {
+  startLine: number;
+  endLine: number;
+  /** Set if the statement is the `define()` of a module. */
+  module?: string;
+  dependencies: string[];
+}
+
+/** Returns the module name and dependencies of a statement like
+ * `define("name", ["require", "exports", "dep"], function (...) {...});`. */
+function getDefine(
+  statement: ts.Statement
+): { module: string; dependencies: string[] } | undefined {
+  if (
+    !ts.isExpressionStatement(statement) ||
+    !ts.isCallExpression(statement.expression)
+  ) {
+    return undefined;
+  }
+  const { expression, arguments: args } = statement.expression;
+  if (
+    !ts.isIdentifier(expression) ||
+    expression.text !== "define" ||
+    args.length < 3 ||
+    !ts.isStringLiteral(args[0]) ||
+    !ts.isArrayLiteralExpression(args[1])
+  ) {
+    return undefined;
+  }
+  const dependencies = args[1].elements
+    .filter(ts.isStringLiteral)
+    .map((element): string => element.text);
+  return { module: args[0].text, dependencies };
+}
+
+function parseStatements(code: string): Statement[] {
+  const sourceFile = ts.createSourceFile(
+    "bundle.js",
+    code,
+    ts.ScriptTarget.ESNext,
+    false,
+    ts.ScriptKind.JS
+  );
+  return sourceFile.statements.map(
+    (statement): Statement => {
+      const define = getDefine(statement);
+      return {
+        startLine: sourceFile.getLineAndCharacterOfPosition(
+          statement.getStart(sourceFile)
+        ).line,
+        endLine: sourceFile.getLineAndCharacterOfPosition(statement.end).line,
+        module: define && define.module,
+        dependencies: define ? define.dependencies : []
+      };
+    }
+  );
+}
+
+/** Copies the given statements of the bundle into a chunk. */
+function createChunk(
+  lines: string[],
+  mappingsByLine: Mapping[][],
+  statements: Statement[]
+): Chunk {
+  const chunkLines: string[] = [];
+  const mappings: Mapping[] = [];
+  for (const { startLine, endLine } of statements) {
+    for (let line = startLine; line <= endLine; line++) {
+      for (const mapping of mappingsByLine[line] || []) {
+        mappings.push({ ...mapping, generatedLine: chunkLines.length });
+      }
+      chunkLines.push(lines[line]);
+    }
+  }
+  return { code: chunkLines.join("\n"), mappings };
+}
+
+/** Splits a bundle into a chunk for each entry point, with the modules only
+ * it imports, and a shared chunk with all other modules. Statements which
+ * aren't module definitions, i.e. the helpers emitted by the compiler, are
+ * copied into every chunk. Without `shared`, e.g. for a single entry point,
+ * every module goes into the first chunk. The shared chunk is undefined if
+ * there are no modules for it. */
+export function splitChunks(
+  code: string,
+  sourceMap: RawSourceMap,
+  entries: string[],
+  shared: boolean
+): { entryChunks: Chunk[]; sharedChunk?: Chunk } {
+  const lines = code.split("\n");
+  const mappingsByLine: Mapping[][] = [];
+  for (const mapping of decodeMappings(sourceMap.mappings)) {
+    const line = mapping.generatedLine;
+    (mappingsByLine[line] = mappingsByLine[line] || []).push(mapping);
+  }
+
+  const statements = parseStatements(code);
+  const modules = new Map<string, Statement>();
+  for (const statement of statements) {
+    if (statement.module) {
+      modules.set(statement.module, statement);
+    }
+  }
+
+  // The entry points which import each module, directly or indirectly.
+  const importers = new Map<string, Set<number>>();
+  entries.forEach(
+    (entry, index): void => {
+      const stack = [entry];
+      while (stack.length) {
+        const module = stack.pop()!;
+        const moduleImporters = importers.get(module) || new Set<number>();
+        if (!modules.has(module) || moduleImporters.has(index)) {
+          continue;
+        }
+        moduleImporters.add(index);
+        importers.set(module, moduleImporters);
+        stack.push(...modules.get(module)!.dependencies);
+      }
+    }
+  );
+
+  const helpers = statements.filter((statement): boolean => !statement.module);
+  const entryStatements = entries.map((): Statement[] => [...helpers]);
+  const sharedStatements = [...helpers];
+  let hasSharedModules = false;
+  for (const statement of statements) {
+    if (!statement.module) {
+      continue;
+    }
+    // Modules which no entry point imports statically may still be imported
+    // dynamically, so they are kept in the shared chunk.
+    const moduleImporters = importers.get(statement.module);
+    if (!shared) {
+      entryStatements[0].push(statement);
+    } else if (moduleImporters && moduleImporters.size === 1) {
+      entryStatements[moduleImporters.values().next().value].push(statement);
+    } else {
+      sharedStatements.push(statement);
+      hasSharedModules = true;
+    }
+  }
+
+  return {
+    entryChunks: entryStatements.map(
+      (chunkStatements): Chunk =>
+        createChunk(lines, mappingsByLine, chunkStatements)
+    ),
+    sharedChunk: hasSharedModules
+      ? createChunk(lines, mappingsByLine, sharedStatements)
+      : undefined
+  };
+}
+
+function isIdentifierChar(char: string): boolean {
+  return /[\w$\\]/.test(char) || char.charCodeAt(0) > 0x7f;
+}
+
+/** Returns true if two tokens would be read differently without whitespace
+ * between them, e.g. `return x`, `a + +b` or `1 .toString()`. */
+function needsSpace(
+  previous: ts.Node,
+  previousText: string,
+  text: string
+): boolean {
+  const last = previousText[previousText.length - 1];
+  const first = text[0];
+  return (
+    (isIdentifierChar(last) && isIdentifierChar(first)) ||
+    ((last === "+" || last === "-") && first === last) ||
+    (last === "/" && (first === "/" || first === "*")) ||
+    (last === "<" && first === "!") ||
+    (previous.kind === ts.SyntaxKind.NumericLiteral &&
+      /^\d+$/.test(previousText) &&
+      first === ".")
+  );
+}
+
+/** Statements which end with a semicolon, which may have been left out and
+ * inserted automatically at the end of a line. */
+const SEMICOLON_STATEMENTS = [
+  ts.SyntaxKind.BreakStatement,
+  ts.SyntaxKind.ContinueStatement,
+  ts.SyntaxKind.DebuggerStatement,
+  ts.SyntaxKind.DoStatement,
+  ts.SyntaxKind.ExportAssignment,
+  ts.SyntaxKind.ExportDeclaration,
+  ts.SyntaxKind.ExpressionStatement,
+  ts.SyntaxKind.ImportDeclaration,
+  ts.SyntaxKind.ReturnStatement,
+  ts.SyntaxKind.ThrowStatement,
+  ts.SyntaxKind.VariableStatement
+];
+
+/** Removes comments and whitespace from a chunk, and updates its mappings.
+ * Names are not shortened. */
+export function minify(chunk: Chunk): Chunk {
+  const sourceFile = ts.createSourceFile(
+    "chunk.js",
+    chunk.code,
+    ts.ScriptTarget.ESNext,
+    false,
+    ts.ScriptKind.JS
+  );
+  const mappingsByLine: Mapping[][] = [];
+  for (const mapping of chunk.mappings) {
+    const line = mapping.generatedLine;
+    (mappingsByLine[line] = mappingsByLine[line] || []).push(mapping);
+  }
+
+  let code = "";
+  const mappings: Mapping[] = [];
+  let previous: ts.Node | undefined;
+  let previousText = "";
+
+  function write(node: ts.Node, text: string, start?: number): void {
+    if (previous && needsSpace(previous, previousText, text)) {
+      code += " ";
+    }
+    if (start !== undefined) {
+      // The original position of the token is given by the last mapping at
+      // or before it on its line.
+      const { line, character } = sourceFile.getLineAndCharacterOfPosition(
+        start
+      );
+      const original = (mappingsByLine[line] || [])
+        .filter((mapping): boolean => mapping.generatedColumn <= character)
+        .pop();
+      if (original && original.source !== undefined) {
+        mappings.push({
+          ...original,
+          generatedLine: 0,
+          generatedColumn: code.length
+        });
+      }
+    }
+    code += text;
+    previous = node;
+    previousText = text;
+  }
+
+  function visit(node: ts.Node): void {
+    if (
+      node.kind >= ts.SyntaxKind.FirstJSDocNode &&
+      node.kind <= ts.SyntaxKind.LastJSDocNode
+    ) {
+      return;
+    }
+    const children = node.getChildren(sourceFile);
+    if (children.length === 0) {
+      const start = node.getStart(sourceFile);
+      if (start < node.end) {
+        write(node, chunk.code.slice(start, node.end), start);
+      }
+      return;
+    }
+    children.forEach(visit);
+    if (SEMICOLON_STATEMENTS.includes(node.kind) && previousText !== ";") {
+      write(node, ";");
+    }
+  }
+
+  visit(sourceFile);
+  return { code, mappings };
+}
+
+/** Returns the source map of a chunk, based on the source map of the bundle
+ * it was split from. `lineOffset` is the number of lines written before the
+ * chunk. */
+export function chunkSourceMap(
+  chunk: Chunk,
+  bundleSourceMap: RawSourceMap,
+  file: string,
+  lineOffset: number
+): string {
+  const sourceMap: RawSourceMap = {
+    version: 3,
+    file,
+    sourceRoot: bundleSourceMap.sourceRoot,
+    sources: bundleSourceMap.sources,
+    sourcesContent: bundleSourceMap.sourcesContent,
+    names: bundleSourceMap.names,
+    mappings: ";".repeat(lineOffset) + encodeMappings(chunk.mappings)
+  };
+  return JSON.stringify(sourceMap);
+}
@@ -0,0 +1,201 @@
+// Copyright 2018-2019 the Deno authors. All rights reserved. MIT license.
+import { test, assertEquals } from "./test_util.ts";
+import {
+  decodeMappings,
+  encodeMappings,
+  Mapping,
+  minify,
+  RawSourceMap,
+  splitChunks
+} from "./bundler.ts";
+
+test(function bundlerMappingsRoundTrip(): void {
+  // The deltas between consecutive mappings include negative ones, and values
+  // which need several base64 digits.
+  const mappings = [
+    {
+      generatedLine: 0,
+      generatedColumn: 0,
+      source: 0,
+      originalLine: 10,
+      originalColumn: 4,
+      name: 0
+    },
+    {
+      generatedLine: 0,
+      generatedColumn: 8,
+      source: 1,
+      originalLine: 2,
+      originalColumn: 0
+    },
+    { generatedLine: 0, generatedColumn: 12 },
+    {
+      generatedLine: 2,
+      generatedColumn: 3,
+      source: 0,
+      originalLine: 0,
+      originalColumn: 1000,
+      name: 3
+    }
+  ];
+  const encoded = encodeMappings(mappings);
+  assertEquals(encoded, "AAUIA,QCRJ,I;;GDFw+BG");
+  assertEquals(decodeMappings(encoded), mappings);
+  assertEquals(encodeMappings(decodeMappings(encoded)), encoded);
+  assertEquals(decodeMappings(""), []);
+});
+
+test(function bundlerMinifyAsi(): void {
+  const minified = (code: string): string =>
+    minify({ code, mappings: [] }).code;
+  // A line break after `return` ends the statement.
+  assertEquals(
+    minified("function f() {\n  return\n  x;\n}"),
+    "function f(){return;x;}"
+  );
+  // Neither `a + +b` nor `a - -b` may become an increment or decrement.
+  assertEquals(minified("var c = a + +b;"), "var c=a+ +b;");
+  assertEquals(minified("var c = a - -b;"), "var c=a- -b;");
+  // The dot would be read as a decimal point.
+  assertEquals(minified("var s = 1 .toString();"), "var s=1 .toString();");
+  assertEquals(minified("var s = 1.5.toString();"), "var s=1.5.toString();");
+  // A regular expression after a division would start a comment.
+  assertEquals(
+    minified("var x = a / /re/g.lastIndex;"),
+    "var x=a/ /re/g.lastIndex;"
+  );
+  assertEquals(
+    minified("// comment\nvar a = 1\nvar b = 2\n"),
+    "var a=1;var b=2;"
+  );
+});
+
+test(function bundlerMinifyMappings(): void {
+  const { code, mappings } = minify({
+    code: "var a = 1;\n  b();",
+    mappings: [
+      {
+        generatedLine: 0,
+        generatedColumn: 0,
+        source: 0,
+        originalLine: 0,
+        originalColumn: 0
+      },
+      {
+        generatedLine: 1,
+        generatedColumn: 2,
+        source: 0,
+        originalLine: 5,
+        originalColumn: 0
+      }
+    ]
+  });
+  assertEquals(code, "var a=1;b();");
+  // Each token is mapped to the original position of its line.
+  assertEquals(
+    mappings.map(
+      ({ generatedLine, generatedColumn, originalLine }): number[] => [
+        generatedLine,
+        generatedColumn,
+        originalLine!
+      ]
+    ),
+    [
+      [0, 0, 0],
+      [0, 4, 0],
+      [0, 5, 0],
+      [0, 6, 0],
+      [0, 7, 0],
+      [0, 8, 5],
+      [0, 9, 5],
+      [0, 10, 5],
+      [0, 11, 5]
+    ]
+  );
+});
+
+// A bundle as emitted by the compiler, with one statement per line. "a" and
+// "b" are entry points, "deep" is imported by both through "shared", and no
+// entry point imports "dynamic".
+const bundleLines = [
+  "var helper = 1;",
+  'define("a", ["require", "exports", "shared", "onlyA"], function () {});',
+  'define("b", ["require", "exports", "shared"], function () {});',
+  'define("shared", ["require", "exports", "deep"], function () {});',
+  'define("deep", ["require", "exports"], function () {});',
+  'define("onlyA", ["require", "exports"], function () {});',
+  'define("dynamic", ["require", "exports"], function () {});'
+];
+const bundle = bundleLines.join("\n");
+const bundleSourceMap: RawSourceMap = {
+  version: 3,
+  sources: ["bundle.ts"],
+  names: [],
+  // Each line is mapped to the same line of the source.
+  mappings: encodeMappings(
+    bundleLines.map(
+      (_, line): Mapping => ({
+        generatedLine: line,
+        generatedColumn: 0,
+        source: 0,
+        originalLine: line,
+        originalColumn: 0
+      })
+    )
+  )
+};
+
+function chunkLines(lines: number[]): string {
+  return lines.map((line): string => bundleLines[line]).join("\n");
+}
+
+test(function bundlerSplitChunksShared(): void {
+  const { entryChunks, sharedChunk } = splitChunks(
+    bundle,
+    bundleSourceMap,
+    ["a", "b"],
+    true
+  );
+  assertEquals(entryChunks.length, 2);
+  // Every chunk has the helpers.
+  assertEquals(entryChunks[0].code, chunkLines([0, 1, 5]));
+  assertEquals(entryChunks[1].code, chunkLines([0, 2]));
+  assertEquals(sharedChunk!.code, chunkLines([0, 3, 4, 6]));
+  // The mappings follow the lines into the chunks.
+  assertEquals(
+    sharedChunk!.mappings.map(
+      ({ generatedLine, originalLine }): number[] => [
+        generatedLine,
+        originalLine!
+      ]
+    ),
+    [[0, 0], [1, 3], [2, 4], [3, 6]]
+  );
+});
+
+test(function bundlerSplitChunksNoShared(): void {
+  // Without a shared chunk, every module goes into the first chunk.
+  const { entryChunks, sharedChunk } = splitChunks(
+    bundle,
+    bundleSourceMap,
+    ["a", "b"],
+    false
+  );
+  assertEquals(entryChunks[0].code, bundle);
+  assertEquals(
+    entryChunks[0].mappings,
+    decodeMappings(bundleSourceMap.mappings)
+  );
+  assertEquals(entryChunks[1].code, chunkLines([0]));
+  assertEquals(sharedChunk, undefined);
+
+  // Neither is there one if each module is only imported by one entry point.
+  const single = splitChunks(
+    chunkLines([0, 1, 5]),
+    { ...bundleSourceMap, mappings: "" },
+    ["a"],
+    true
+  );
+  assertEquals(single.entryChunks[0].code, chunkLines([0, 1, 5]));
+  assertEquals(single.sharedChunk, undefined);
+});
@@ -1,8 +1,18 @@
 // Copyright 2018-2019 the Deno authors. All rights reserved. MIT license.
+import * as base64 from "base64-js";
 import * as msg from "gen/cli/msg_generated";
 import * as ts from "typescript";
 
 import { assetSourceCode } from "./assets";
+import {
+  BUNDLE_HEADER,
+  BUNDLE_LOADER,
+  chunkSourceMap,
+  Chunk,
+  minify,
+  RawSourceMap,
+  splitChunks
+} from "./bundler";
 import { bold, cyan, yellow } from "./colors";
 import { Console } from "./console";
 import { core } from "./core";
@@ -15,7 +25,7 @@ import { TextDecoder, TextEncoder } from "./text_encoding";
 import { assert, notImplemented } from "./util";
 import * as util from "./util";
 import { window } from "./window";
-import { postMessage, workerClose, workerMain } from "./workers";
+import { postMessage, workerMain } from "./workers";
 import { writeFileSync } from "./write_file";
 
 // Startup boilerplate. This is necessary because the compiler has its own
@@ -33,12 +43,38 @@ const OUT_DIR = "$deno$";
 
 /** The format of the work message payload coming from the privileged side */
 interface CompilerReq {
+  /** Copied to the response, as several requests can be in flight. */
+  id: number;
   rootNames: string[];
-  bundle?: string;
+  bundle?: BundleOptions;
   // TODO(ry) add compiler config to this interface.
   // options: ts.CompilerOptions;
   configPath?: string;
   config?: string;
+  /** Set with `--no-check`, to transpile the single root without
+   * type-checking. */
+  noCheck?: boolean;
+  /** Source code of the root when `noCheck` is set. */
+  sourceCode?: string;
+  /** Set by `deno check`, to report modules which can't be loaded as
+   * diagnostics instead of failing. */
+  check?: boolean;
+}
+
+/** Options of `deno bundle`. */
+interface BundleOptions {
+  /** Files to write the bundles of the root names to. */
+  outFiles: string[];
+  /** File to write the modules which several root names import to. Without
+   * it, all modules are bundled into the first out file. */
+  sharedOutFile?: string;
+  minify: boolean;
+  sourceMap: "inline" | "external" | "none";
+  /** ES version of the bundles, e.g. "es5". */
+  target?: string;
+  /** Set by `deno snapshot`: the bundles only run the dependencies of their
+   * root name, which `__runMain()` runs once the program starts. */
+  deferMain?: boolean;
 }
 
 interface ConfigureResponse {
@@ -115,8 +151,16 @@ interface ModuleMetaData {
 }
 
 interface EmitResult {
+  id: number;
   emitSkipped: boolean;
   diagnostics?: Diagnostic;
+  /** JavaScript and source map of a module transpiled with `noCheck`, which
+   * the privileged side caches. */
+  outputCode?: string;
+  sourceMap?: string;
+  /** Module names of the source files of a bundle or a check, which
+   * `deno bundle --watch` and `deno test --watch` watch. */
+  sourceFiles?: string[];
 }
 
 /** Ops to Rust to resolve and fetch a modules meta data. */
@@ -203,6 +247,64 @@ function emitBundle(fileName: string, data: string): void {
   console.log(`${humanFileSize(encodedData.length)} emitted.`);
 }
 
+/** Returns a `data:` URL of a source map, to inline it in the JavaScript. */
+function sourceMapDataUrl(sourceMap: string): string {
+  return (
+    "data:application/json;base64," +
+    base64.fromByteArray(encoder.encode(sourceMap))
+  );
+}
+
+/** Writes the bundles of `rootNames` from the JavaScript and source map which
+ * the compiler emitted for them. Each bundle starts with a header and the
+ * module loader, and the bundle of a root name ends with a call which runs
+ * it. */
+function writeBundles(
+  options: BundleOptions,
+  rootNames: string[],
+  code: string,
+  sourceMap: RawSourceMap
+): void {
+  const { outFiles, sharedOutFile } = options;
+  const { entryChunks, sharedChunk } = splitChunks(
+    code,
+    sourceMap,
+    rootNames,
+    sharedOutFile != null
+  );
+  const bundles = entryChunks.map(
+    (chunk, index): { fileName: string; chunk: Chunk; rootName?: string } => ({
+      fileName: outFiles[index],
+      chunk,
+      rootName: rootNames[index]
+    })
+  );
+  let header = BUNDLE_HEADER;
+  if (sharedOutFile && sharedChunk) {
+    bundles.push({ fileName: sharedOutFile, chunk: sharedChunk });
+    header += ` shared=${sharedOutFile.split("/").pop()}`;
+  }
+  for (const { fileName, chunk: bundledChunk, rootName } of bundles) {
+    const chunk = options.minify ? minify(bundledChunk) : bundledChunk;
+    const baseName = fileName.split("/").pop()!;
+    // The header and the loader are on the two lines before the chunk.
+    const map = chunkSourceMap(chunk, sourceMap, baseName, 2);
+    let data = rootName ? header : BUNDLE_HEADER;
+    data += `\n${BUNDLE_LOADER}\n${chunk.code}\n`;
+    if (rootName) {
+      const run = options.deferMain ? "__preload" : "__instantiate";
+      data += `${run}(${JSON.stringify(rootName)});\n`;
+    }
+    if (options.sourceMap === "inline") {
+      data += `//# sourceMappingURL=${sourceMapDataUrl(map)}\n`;
+    } else if (options.sourceMap === "external") {
+      data += `//# sourceMappingURL=${baseName}.map\n`;
+      emitBundle(`${fileName}.map`, map);
+    }
+    emitBundle(fileName, data);
+  }
+}
+
 /** Returns the TypeScript Extension enum for a given media type. */
 function getExtension(
   fileName: string,
@@ -221,6 +323,36 @@ function getExtension(
   }
 }
 
+/** Source files parsed by earlier requests, by the compiler options they were
+ * parsed with and then by file name. They are reused while their source stays
+ * the same, so compiling again after a change, e.g. with `--watch`, only
+ * parses the modules which changed. Only the source files of the latest
+ * program are kept, see `evictSourceFiles()`.
+ */
+const sourceFileCache = new Map<string, Map<string, ts.SourceFile>>();
+
+/** Removes the source files which `program` doesn't use from the cache, so
+ * that it doesn't grow with every module which was ever compiled, or keep
+ * the old versions of modules which were removed. */
+function evictSourceFiles(
+  options: ts.CompilerOptions,
+  program: ts.Program
+): void {
+  const optionsKey = JSON.stringify(options);
+  const sourceFiles = sourceFileCache.get(optionsKey);
+  sourceFileCache.clear();
+  if (!sourceFiles) {
+    return;
+  }
+  const used = new Map<string, ts.SourceFile>();
+  for (const sourceFile of program.getSourceFiles()) {
+    if (sourceFiles.get(sourceFile.fileName) === sourceFile) {
+      used.set(sourceFile.fileName, sourceFile);
+    }
+  }
+  sourceFileCache.set(optionsKey, used);
+}
+
 class Host implements ts.CompilerHost {
   private readonly _options: ts.CompilerOptions = {
     allowJs: true,
@@ -256,20 +388,21 @@ class Host implements ts.CompilerHost {
 
   /* Deno specific APIs */
 
+  /** The JavaScript and source map emitted for a bundle. */
+  bundleOutput: { code?: string; sourceMap?: RawSourceMap } = {};
+
   /** Provides the `ts.HostCompiler` interface for Deno.
    *
-   * @param _bundle Set to a string value to configure the host to write out a
-   *   bundle instead of caching individual files.
+   * @param _bundle Set to configure the host to emit a bundle, which the
+   *   caller then writes out, instead of caching individual files.
    */
-  constructor(private _bundle?: string) {
+  constructor(private _bundle?: BundleOptions) {
     if (this._bundle) {
       // options we need to change when we are generating a bundle
       const bundlerOptions: ts.CompilerOptions = {
         module: ts.ModuleKind.AMD,
-        inlineSourceMap: true,
         outDir: undefined,
-        outFile: `${OUT_DIR}/bundle.js`,
-        sourceMap: false
+        outFile: `${OUT_DIR}/bundle.js`
       };
       Object.assign(this._options, bundlerOptions);
     }
@@ -309,6 +442,20 @@ class Host implements ts.CompilerHost {
     };
   }
 
+  /** Sets the ES version to emit, e.g. "es5". Returns diagnostics if the
+   * version is invalid. */
+  setTarget(target: string): ts.Diagnostic[] | undefined {
+    const { options, errors } = ts.convertCompilerOptionsFromJson(
+      { target },
+      cwd()
+    );
+    if (errors.length) {
+      return errors;
+    }
+    this._options.target = options.target;
+    return undefined;
+  }
+
   /* TypeScript CompilerHost APIs */
 
   fileExists(_fileName: string): boolean {
@@ -349,11 +496,32 @@ class Host implements ts.CompilerHost {
     if (!moduleMetaData || !moduleMetaData.sourceCode) {
       return undefined;
     }
-    return ts.createSourceFile(
+    const optionsKey = JSON.stringify(this._options);
+    let sourceFiles = sourceFileCache.get(optionsKey);
+    if (!sourceFiles) {
+      sourceFiles = new Map();
+      sourceFileCache.set(optionsKey, sourceFiles);
+    }
+    const cached = sourceFiles.get(fileName);
+    if (
+      cached &&
+      cached.languageVersion === languageVersion &&
+      cached.text === moduleMetaData.sourceCode
+    ) {
+      return cached;
+    }
+    const sourceFile = ts.createSourceFile(
       fileName,
       moduleMetaData.sourceCode,
       languageVersion
     );
+    if (this._bundle) {
+      // Name the modules of a bundle by their URL, so the bundle can be split
+      // into chunks which import each other's modules.
+      sourceFile.moduleName = fileName;
+    }
+    sourceFiles.set(fileName, sourceFile);
+    return sourceFile;
   }
 
   readFile(_fileName: string): string | undefined {
@@ -400,7 +568,11 @@ class Host implements ts.CompilerHost {
     util.log("writeFile", fileName);
     try {
       if (this._bundle) {
-        emitBundle(this._bundle, data);
+        if (fileName.endsWith(".map")) {
+          this.bundleOutput.sourceMap = JSON.parse(data);
+        } else {
+          this.bundleOutput.code = data;
+        }
       } else {
         assert(sourceFiles != null && sourceFiles.length == 1);
         const sourceFileName = sourceFiles![0].fileName;
@@ -425,16 +597,57 @@ class Host implements ts.CompilerHost {
   }
 }
 
+/** Transpiles a module on its own, without loading its dependencies and
+ * without type-checking it. Only syntax errors are reported. */
+function transpile(
+  id: number,
+  fileName: string,
+  sourceCode: string,
+  host: Host
+): EmitResult {
+  const { outputText, sourceMapText, diagnostics } = ts.transpileModule(
+    sourceCode,
+    {
+      fileName,
+      compilerOptions: host.getCompilationSettings(),
+      reportDiagnostics: true
+    }
+  );
+  if (diagnostics && diagnostics.length) {
+    return {
+      id,
+      emitSkipped: true,
+      diagnostics: fromTypeScriptDiagnostic(diagnostics)
+    };
+  }
+  return {
+    id,
+    emitSkipped: false,
+    outputCode: outputText,
+    sourceMap: sourceMapText
+  };
+}
+
 // provide the "main" function that will be called by the privileged side when
 // lazy instantiating the compiler web worker
 window.compilerMain = function compilerMain(): void {
   // workerMain should have already been called since a compiler is a worker.
   window.onmessage = ({ data }: { data: CompilerReq }): void => {
-    const { rootNames, configPath, config, bundle } = data;
+    const {
+      id,
+      rootNames,
+      configPath,
+      config,
+      bundle,
+      noCheck,
+      sourceCode,
+      check
+    } = data;
     const host = new Host(bundle);
 
     let emitSkipped = true;
     let diagnostics: ts.Diagnostic[] | undefined;
+    let sourceFiles: string[] | undefined;
 
     // if there is a configuration supplied, we need to parse that
     if (config && config.length && configPath) {
@@ -452,55 +665,91 @@ window.compilerMain = function compilerMain(): void {
       }
     }
 
+    if (bundle && bundle.target && (!diagnostics || diagnostics.length === 0)) {
+      diagnostics = host.setTarget(bundle.target);
+    }
+
+    if (noCheck && (!diagnostics || diagnostics.length === 0)) {
+      postMessage(transpile(id, rootNames[0], sourceCode!, host));
+      return;
+    }
+
     // if there was a configuration and no diagnostics with it, we will continue
     // to generate the program and possibly emit it.
     if (!diagnostics || (diagnostics && diagnostics.length === 0)) {
-      const options = host.getCompilationSettings();
-      const program = ts.createProgram(rootNames, options, host);
-
-      diagnostics = ts.getPreEmitDiagnostics(program).filter(
-        ({ code }): boolean => {
-          // TS2691: An import path cannot end with a '.ts' extension. Consider
-          // importing 'bad-module' instead.
-          if (code === 2691) return false;
-          // TS5009: Cannot find the common subdirectory path for the input files.
-          if (code === 5009) return false;
-          // TS5055: Cannot write file
-          // 'http://localhost:4545/tests/subdir/mt_application_x_javascript.j4.js'
-          // because it would overwrite input file.
-          if (code === 5055) return false;
-          // TypeScript is overly opinionated that only CommonJS modules kinds can
-          // support JSON imports.  Allegedly this was fixed in
-          // Microsoft/TypeScript#26825 but that doesn't seem to be working here,
-          // so we will ignore complaints about this compiler setting.
-          if (code === 5070) return false;
-          return true;
+      try {
+        const options = host.getCompilationSettings();
+        const program = ts.createProgram(rootNames, options, host);
+        evictSourceFiles(options, program);
+        if (bundle || check) {
+          sourceFiles = program
+            .getSourceFiles()
+            .map(({ fileName }): string => fileName);
         }
-      );
 
-      // We will only proceed with the emit if there are no diagnostics.
-      if (diagnostics && diagnostics.length === 0) {
-        if (bundle) {
-          console.log(`Bundling "${bundle}"`);
+        diagnostics = ts.getPreEmitDiagnostics(program).filter(
+          ({ code }): boolean => {
+            // TS2691: An import path cannot end with a '.ts' extension. Consider
+            // importing 'bad-module' instead.
+            if (code === 2691) return false;
+            // TS5009: Cannot find the common subdirectory path for the input files.
+            if (code === 5009) return false;
+            // TS5055: Cannot write file
+            // 'http://localhost:4545/tests/subdir/mt_application_x_javascript.j4.js'
+            // because it would overwrite input file.
+            if (code === 5055) return false;
+            // TypeScript is overly opinionated that only CommonJS modules kinds can
+            // support JSON imports.  Allegedly this was fixed in
+            // Microsoft/TypeScript#26825 but that doesn't seem to be working here,
+            // so we will ignore complaints about this compiler setting.
+            if (code === 5070) return false;
+            return true;
+          }
+        );
+
+        // We will only proceed with the emit if there are no diagnostics.
+        if (diagnostics && diagnostics.length === 0) {
+          if (bundle) {
+            console.log(`Bundling "${bundle.outFiles.join('", "')}"`);
+          }
+          const emitResult = program.emit();
+          emitSkipped = emitResult.emitSkipped;
+          // emitResult.diagnostics is `readonly` in TS3.5+ and can't be assigned
+          // without casting.
+          diagnostics = emitResult.diagnostics as ts.Diagnostic[];
+          if (bundle && !emitSkipped && diagnostics.length === 0) {
+            const { code, sourceMap } = host.bundleOutput;
+            writeBundles(bundle, rootNames, code!, sourceMap!);
+          }
+        }
+      } catch (e) {
+        if (!check) {
+          throw e;
         }
-        const emitResult = program.emit();
-        emitSkipped = emitResult.emitSkipped;
-        // emitResult.diagnostics is `readonly` in TS3.5+ and can't be assigned
-        // without casting.
-        diagnostics = emitResult.diagnostics as ts.Diagnostic[];
+        // A module which can't be fetched, reported like the diagnostic
+        // TypeScript gives for an unresolved import.
+        diagnostics = [
+          {
+            category: ts.DiagnosticCategory.Error,
+            code: 2307,
+            file: undefined,
+            start: undefined,
+            length: undefined,
+            messageText: e instanceof Error ? e.message : String(e)
+          }
+        ];
       }
     }
 
     const result: EmitResult = {
+      id,
       emitSkipped,
       diagnostics: diagnostics.length
         ? fromTypeScriptDiagnostic(diagnostics)
-        : undefined
+        : undefined,
+      sourceFiles
     };
 
     postMessage(result);
-
-    // The compiler isolate exits after a single message.
-    workerClose();
   };
 };
@@ -79,6 +79,7 @@ export {
   Signal
 } from "./process";
 export { inspect } from "./console";
+export { test, TestFunction, TestDefinition } from "./testing";
 export { build, platform, OperatingSystem, Arch } from "./build";
 export { version } from "./version";
 export const args: string[] = [];
@@ -87,6 +88,8 @@ export const args: string[] = [];
 // appear in the runtime type library.
 /** @internal */
 export { core } from "./core";
+/** @internal */
+export { builtinModules } from "./builtin_modules";
 
 // TODO Don't expose Console nor stringifyArgs.
 /** @internal */
@@ -436,6 +436,11 @@ export interface RequestInit {
   referrerPolicy?: ReferrerPolicy;
   signal?: AbortSignal | null;
   window?: any;
+  /** Deno specific. Speak HTTP/2 to the server without negotiating it first
+   * ("prior knowledge"). This is required to reach h2c servers over plain
+   * `http:`. Over `https:`, HTTP/2 is used automatically when the server
+   * supports it. */
+  http2PriorKnowledge?: boolean;
 }
 
 export interface ResponseInit {
@@ -253,7 +253,10 @@ class Response implements domTypes.Response {
     readonly status: number,
     headersList: Array<[string, string]>,
     rid: number,
-    body_: null | Body = null
+    body_: null | Body = null,
+    /** Deno specific. The HTTP version used for this response, e.g.
+     * "HTTP/1.1" or "HTTP/2.0". */
+    readonly httpVersion: string = "HTTP/1.1"
   ) {
     this.trailer = createResolvable();
     this.headers = new Headers(headersList);
@@ -304,7 +307,13 @@ class Response implements domTypes.Response {
       headersList.push(header);
     }
 
-    return new Response(this.status, headersList, -1, this.body);
+    return new Response(
+      this.status,
+      headersList,
+      -1,
+      this.body,
+      this.httpVersion
+    );
   }
 }
 
@@ -357,11 +366,15 @@ export async function fetch(
   let method: string | null = null;
   let headers: domTypes.Headers | null = null;
   let body: ArrayBufferView | undefined;
+  let credentials: string | undefined;
+  let http2PriorKnowledge = false;
 
   if (typeof input === "string") {
     url = input;
     if (init != null) {
       method = init.method || null;
+      credentials = init.credentials;
+      http2PriorKnowledge = !!init.http2PriorKnowledge;
       if (init.headers) {
         headers =
           init.headers instanceof Headers
@@ -403,6 +416,7 @@ export async function fetch(
     url = input.url;
     method = input.method;
     headers = input.headers;
+    credentials = input.credentials;
 
     //@ts-ignore
     if (input._bodySource) {
@@ -410,13 +424,22 @@ export async function fetch(
     }
   }
 
+  // Cookies are only sent and stored when explicitly requested, since there
+  // is no origin to compare against for "same-origin".
+  const useCookies = credentials === "include";
+
   // Send Fetch message
   const builder = flatbuffers.createBuilder();
   const headerOff = msgHttpRequest(builder, url, method, headers);
   const resBase = await sendAsync(
     builder,
     msg.Any.Fetch,
-    msg.Fetch.createFetch(builder, headerOff),
+    msg.Fetch.createFetch(
+      builder,
+      headerOff,
+      useCookies,
+      http2PriorKnowledge
+    ),
     body
   );
 
@@ -431,7 +454,14 @@ export async function fetch(
   const status = header.status();
 
   const headersList = deserializeHeaderFields(header);
-
-  const response = new Response(status, headersList, bodyRid);
+  const httpVersion = inner.httpVersion()!;
+
+  const response = new Response(
+    status,
+    headersList,
+    bodyRid,
+    null,
+    httpVersion
+  );
   return response;
 }
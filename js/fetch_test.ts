@@ -156,6 +156,35 @@ testPerm({ net: true }, async function fetchInitBlobBody(): Promise<void> {
   assert(response.headers.get("content-type").startsWith("text/javascript"));
 });
 
+testPerm({ net: true }, async function fetchHttpVersion(): Promise<void> {
+  const response = await fetch("http://localhost:4545/echo_server", {
+    method: "POST",
+    body: "hello"
+  });
+  assertEquals(response.httpVersion, "HTTP/1.1");
+  assertEquals(response.clone().httpVersion, "HTTP/1.1");
+  await response.text();
+});
+
+testPerm({ net: true }, async function fetchCredentialsInclude(): Promise<
+  void
+> {
+  const setResponse = await fetch("http://localhost:4545/set_cookie", {
+    credentials: "include"
+  });
+  assertEquals(setResponse.status, 200);
+  await setResponse.text();
+
+  const response = await fetch("http://localhost:4545/echo_cookie", {
+    credentials: "include"
+  });
+  assertEquals(await response.text(), "deno_test=1");
+
+  // Without opting in, no cookies are sent.
+  const omitted = await fetch("http://localhost:4545/echo_cookie");
+  assertEquals(await omitted.text(), "");
+});
+
 // TODO(ry) The following tests work but are flaky. There's a race condition
 // somewhere. Here is what one of these flaky failures looks like:
 //
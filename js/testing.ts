@@ -0,0 +1,108 @@
+// Copyright 2018-2019 the Deno authors. All rights reserved. MIT license.
+import { window } from "./window";
+import { postMessage } from "./workers";
+
+export type TestFunction = () => void | Promise<void>;
+
+export interface TestDefinition {
+  fn: TestFunction;
+  name: string;
+}
+
+const tests: TestDefinition[] = [];
+
+/** Registers a test, which `deno test` runs once the module is loaded. The
+ * name of a test defaults to the name of its function.
+ *
+ *       Deno.test(function addition(): void {
+ *         if (1 + 1 !== 2) throw new Error("1 + 1 should be 2");
+ *       });
+ *
+ *       Deno.test("read config", async (): Promise<void> => {
+ *         await Deno.readFile("config.json");
+ *       });
+ */
+export function test(t: TestDefinition | TestFunction): void;
+export function test(name: string, fn: TestFunction): void;
+export function test(
+  t: string | TestDefinition | TestFunction,
+  fn?: TestFunction
+): void {
+  let definition: TestDefinition;
+  if (typeof t === "string") {
+    if (!fn) {
+      throw new TypeError("Missing test function");
+    }
+    definition = { fn, name: t };
+  } else if (typeof t === "function") {
+    definition = { fn: t, name: t.name };
+  } else {
+    definition = t;
+  }
+  if (!definition.name) {
+    throw new TypeError("The name of a test can't be empty");
+  }
+  tests.push(definition);
+}
+
+/** Options of a test run, passed to `testMain()` by `deno test`. */
+interface TestOptions {
+  filter?: string;
+  failFast: boolean;
+}
+
+/** Keep in sync with `TestMessage` in cli/test_runner.rs. */
+type TestMessage =
+  | { type: "plan"; total: number; filtered: number }
+  | { type: "result"; name: string; error?: string; duration: number }
+  | { type: "end" };
+
+/** A filter wrapped in slashes is a regular expression, any other matches
+ * the names which contain it. */
+function matchesFilter(name: string, filter?: string): boolean {
+  if (!filter) {
+    return true;
+  }
+  if (filter.length > 2 && filter.startsWith("/") && filter.endsWith("/")) {
+    return new RegExp(filter.slice(1, -1)).test(name);
+  }
+  return name.includes(filter);
+}
+
+async function runTests(
+  definitions: TestDefinition[],
+  { filter, failFast }: TestOptions,
+  report: (message: TestMessage) => void
+): Promise<void> {
+  const selected = definitions.filter(
+    ({ name }): boolean => matchesFilter(name, filter)
+  );
+  report({
+    type: "plan",
+    total: selected.length,
+    filtered: definitions.length - selected.length
+  });
+  for (const { fn, name } of selected) {
+    const start = Date.now();
+    let error: string | undefined;
+    try {
+      await fn();
+    } catch (err) {
+      error = err instanceof Error && err.stack ? err.stack : String(err);
+    }
+    report({ type: "result", name, error, duration: Date.now() - start });
+    if (error !== undefined && failFast) {
+      break;
+    }
+  }
+  report({ type: "end" });
+}
+
+/** Runs the tests which the module loaded by `deno test` registered, and
+ * posts their results to the runner.
+ */
+window.testMain = async function testMain(
+  options: TestOptions
+): Promise<void> {
+  await runTests(tests, options, postMessage);
+};
@@ -0,0 +1,6 @@
+// Copyright 2018-2019 the Deno authors. All rights reserved. MIT license.
+
+// CommonJS modules, like the TypeScript compiler, assign their exports to
+// `module.exports`. Imported as ES modules, they find `module` on the global
+// object, which this sets up.
+Object.assign(window, { module: { exports: {} } });
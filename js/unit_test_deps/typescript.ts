@@ -0,0 +1,27 @@
+// Copyright 2018-2019 the Deno authors. All rights reserved. MIT license.
+
+// The TypeScript compiler for the unit tests of modules which import
+// "typescript", like js/bundler.ts. The compiler's bundle includes it, but
+// the runtime doesn't, so js/unit_test_import_map.json maps "typescript" to
+// this module, which loads the CommonJS build of the compiler. Only the parts
+// which the tested modules use are exported.
+import "./module.ts";
+import "../../third_party/node_modules/typescript/lib/typescript.js";
+import * as tsTypes from "../../third_party/node_modules/typescript/lib/typescript.d.ts";
+
+declare const module: { exports: typeof tsTypes };
+
+export type Node = tsTypes.Node;
+export type Statement = tsTypes.Statement;
+
+export const {
+  createSourceFile,
+  isArrayLiteralExpression,
+  isCallExpression,
+  isExpressionStatement,
+  isIdentifier,
+  isStringLiteral,
+  ScriptKind,
+  ScriptTarget,
+  SyntaxKind
+} = module.exports;
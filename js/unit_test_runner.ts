@@ -1,4 +1,4 @@
-#!/usr/bin/env deno run --reload --allow-run
+#!/usr/bin/env deno run --reload --allow-run --importmap=js/unit_test_import_map.json
 // Copyright 2018-2019 the Deno authors. All rights reserved. MIT license.
 import "./unit_tests.ts";
 import { permissionCombinations, parseUnitTestOutput } from "./test_util.ts";
@@ -56,6 +56,7 @@ async function main(): Promise<void> {
       Deno.execPath,
       "run",
       "--no-prompt",
+      "--importmap=js/unit_test_import_map.json",
       ...cliPerms,
       "js/unit_tests.ts"
     ];
@@ -1,12 +1,14 @@
 // Copyright 2018-2019 the Deno authors. All rights reserved. MIT license.
 
 // This test is executed as part of tools/test.py
-// But it can also be run manually: ./target/debug/deno js/unit_tests.ts
+// But it can also be run manually:
+// ./target/debug/deno --importmap=js/unit_test_import_map.json js/unit_tests.ts
 
 import "./blob_test.ts";
 import "./body_test.ts";
 import "./buffer_test.ts";
 import "./build_test.ts";
+import "./bundler_test.ts";
 import "./chmod_test.ts";
 import "./chown_test.ts";
 import "./console_test.ts";
@@ -20,10 +20,15 @@ export function decodeMessage(dataIntArray: Uint8Array): any {
   return JSON.parse(dataJson);
 }
 
-function createWorker(specifier: string): number {
+function createWorker(specifier: string, importMap?: string): number {
   const builder = flatbuffers.createBuilder();
   const specifier_ = builder.createString(specifier);
-  const inner = msg.CreateWorker.createCreateWorker(builder, specifier_);
+  const importMap_ = importMap == null ? 0 : builder.createString(importMap);
+  const inner = msg.CreateWorker.createCreateWorker(
+    builder,
+    specifier_,
+    importMap_
+  );
   const baseRes = sendSync(builder, msg.Any.CreateWorker, inner);
   assert(baseRes != null);
   assert(
@@ -141,6 +146,13 @@ export async function workerMain(): Promise<void> {
   }
 }
 
+export interface WorkerOptions {
+  /** Path of an import map for the modules of the worker, relative to the
+   * main module. Without one, the worker uses the import map of the code that
+   * creates it. */
+  importMap?: string;
+}
+
 export interface Worker {
   onerror?: () => void;
   onmessage?: (e: { data: any }) => void;
@@ -157,8 +169,8 @@ export class WorkerImpl implements Worker {
   public onmessage?: (data: any) => void;
   public onmessageerror?: () => void;
 
-  constructor(specifier: string) {
-    this.rid = createWorker(specifier);
+  constructor(specifier: string, options: WorkerOptions = {}) {
+    this.rid = createWorker(specifier, options.importMap);
     this.run();
     this.isClosedPromise = hostGetWorkerClosed(this.rid);
     this.isClosedPromise.then(
@@ -0,0 +1,4 @@
+import { a } from "data:application/typescript;base64,ZXhwb3J0IGNvbnN0IGE6IHN0cmluZyA9ICJkYXRhIG1vZHVsZSI7";
+import { b } from "data:application/javascript,export%20const%20b%20%3D%202%3B";
+
+console.log(a, b);
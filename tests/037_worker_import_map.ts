@@ -0,0 +1,13 @@
+// The workers run one after the other, so their output doesn't interleave.
+async function main(): Promise<void> {
+  // Inherits the import map passed with --importmap.
+  await new Worker("./tests/importmaps/test.ts").closed;
+
+  // Uses its own import map, which scopes "moment" differently. Its path is
+  // relative to the main module, this one.
+  await new Worker("./tests/importmaps/worker/main.ts", {
+    importMap: "./importmaps/worker/import_map.json"
+  }).closed;
+}
+
+main();
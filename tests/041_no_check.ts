@@ -0,0 +1,7 @@
+import { printHello } from "./subdir/print_hello.ts";
+
+// Not a number, which type-checking would report.
+const answer: number = "forty-two";
+
+printHello();
+console.log(answer);
@@ -0,0 +1,11 @@
+// deno-bundle shared=shared.js
+(function () { "use strict"; if (globalThis.__instantiate) return; var modules = Object.create(null); var main; function define(name, deps, factory) { modules[name] = { deps: deps, factory: factory, exports: undefined }; } function resolve(specifier, referrer) { if (specifier in modules) return specifier; return new URL(specifier, referrer).href; } function createRequire(referrer) { return function (deps, onLoad, onError) { try { onLoad(load(resolve(deps[0], referrer))); } catch (err) { onError(err); } }; } function get(name) { var module = modules[name]; if (!module) { throw new Error("Module \"" + name + "\" is not in the bundle."); } return module; } function load(name) { var module = get(name); if (!module.exports) { module.exports = {}; var args = module.deps.map(function (dep) { if (dep === "require") return createRequire(name); if (dep === "exports") return module.exports; return load(dep); }); var result = module.factory.apply(undefined, args); if (result !== undefined) module.exports = result; } return module.exports; } function preload(name) { main = name; get(name).deps.forEach(function (dep) { if (dep !== "require" && dep !== "exports") load(dep); }); } globalThis.define = define; globalThis.__instantiate = load; globalThis.__preload = preload; globalThis.__runMain = function () { return load(main); }; })();
+define("file:///bundle/main.ts", ["require", "exports", "file:///bundle/print_hello.ts"], function (require, exports, print_hello_ts_1) {
+    "use strict";
+    Object.defineProperty(exports, "__esModule", { value: true });
+    print_hello_ts_1.printHello();
+    new Promise((resolve_1, reject_1) => { require(["./answer.ts"], resolve_1, reject_1); }).then(({ answer }) => {
+        console.log("The answer is", answer);
+    });
+});
+__instantiate("file:///bundle/main.ts");
@@ -0,0 +1,15 @@
+// deno-bundle
+(function () { "use strict"; if (globalThis.__instantiate) return; var modules = Object.create(null); var main; function define(name, deps, factory) { modules[name] = { deps: deps, factory: factory, exports: undefined }; } function resolve(specifier, referrer) { if (specifier in modules) return specifier; return new URL(specifier, referrer).href; } function createRequire(referrer) { return function (deps, onLoad, onError) { try { onLoad(load(resolve(deps[0], referrer))); } catch (err) { onError(err); } }; } function get(name) { var module = modules[name]; if (!module) { throw new Error("Module \"" + name + "\" is not in the bundle."); } return module; } function load(name) { var module = get(name); if (!module.exports) { module.exports = {}; var args = module.deps.map(function (dep) { if (dep === "require") return createRequire(name); if (dep === "exports") return module.exports; return load(dep); }); var result = module.factory.apply(undefined, args); if (result !== undefined) module.exports = result; } return module.exports; } function preload(name) { main = name; get(name).deps.forEach(function (dep) { if (dep !== "require" && dep !== "exports") load(dep); }); } globalThis.define = define; globalThis.__instantiate = load; globalThis.__preload = preload; globalThis.__runMain = function () { return load(main); }; })();
+define("file:///bundle/print_hello.ts", ["require", "exports"], function (require, exports) {
+    "use strict";
+    Object.defineProperty(exports, "__esModule", { value: true });
+    function printHello() {
+        console.log("Hello");
+    }
+    exports.printHello = printHello;
+});
+define("file:///bundle/answer.ts", ["require", "exports"], function (require, exports) {
+    "use strict";
+    Object.defineProperty(exports, "__esModule", { value: true });
+    exports.answer = 42;
+});
@@ -0,0 +1,5 @@
+import { printHello } from "./subdir/print_hello.ts";
+
+printHello();
+const { env, net } = Deno.permissions();
+console.log(Deno.args.slice(1).join(" "), env, net);
@@ -0,0 +1,4 @@
+import { add } from "./math.ts";
+
+console.log(add(1, 2));
+Deno.exit(3);
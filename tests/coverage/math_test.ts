@@ -0,0 +1,7 @@
+import { sub } from "./math.ts";
+
+Deno.test(function subtraction(): void {
+  if (sub(3, 1) !== 2) {
+    throw new Error("3 - 1 should be 2");
+  }
+});
@@ -0,0 +1,5 @@
+import "std:blank";
+import "blank";
+import "none";
+import { bold } from "colors";
+console.log(bold("Hello from builtin.ts!").includes("Hello from builtin.ts!"));
@@ -0,0 +1,3 @@
+import "moment";
+import "./scope/scoped.ts";
+console.log("Hello from worker!");
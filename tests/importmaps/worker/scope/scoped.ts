@@ -0,0 +1,2 @@
+import "moment";
+console.log("Hello from worker scope!");
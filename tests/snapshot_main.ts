@@ -0,0 +1,4 @@
+import { squares } from "./subdir/squares.ts";
+
+console.log(squares.join(","));
+console.log(Deno.args.slice(1).join(" "), Deno.permissions().env);
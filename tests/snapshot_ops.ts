@@ -0,0 +1,3 @@
+import { hello } from "./subdir/snapshot_read_file.ts";
+
+console.log(hello.trim());
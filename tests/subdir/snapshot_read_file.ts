@@ -0,0 +1,5 @@
+// Read by the top-level code of the module while `deno snapshot` makes the
+// snapshot, so the program doesn't read the file again when it starts.
+export const hello = new TextDecoder().decode(
+  Deno.readFileSync("tests/hello.txt")
+);
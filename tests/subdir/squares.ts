@@ -0,0 +1,7 @@
+// Computed by the top-level code of the module, which `deno snapshot` runs
+// once while making the snapshot.
+export const squares: number[] = [];
+for (let i = 0; i < 5; i++) {
+  squares.push(i * i);
+}
+console.log("squares initialized");
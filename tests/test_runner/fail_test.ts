@@ -0,0 +1,7 @@
+Deno.test(function passes(): void {});
+
+Deno.test(function fails(): void {
+  throw new Error("expected failure");
+});
+
+Deno.test(function skippedByFailFast(): void {});
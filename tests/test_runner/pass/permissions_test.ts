@@ -0,0 +1,6 @@
+Deno.test(function revokeRead(): void {
+  Deno.revokePermission("read");
+  if (Deno.permissions().read) {
+    throw new Error("read should be revoked");
+  }
+});
@@ -0,0 +1,21 @@
+Deno.test(function readIsAllowed(): void {
+  // permissions_test.ts revoked read in its own worker.
+  if (!Deno.permissions().read) {
+    throw new Error("read should be allowed");
+  }
+});
+
+Deno.test("addition", (): void => {
+  if (1 + 1 !== 2) {
+    throw new Error("1 + 1 should be 2");
+  }
+});
+
+Deno.test(async function asyncAddition(): Promise<void> {
+  const sum = await new Promise<number>(
+    (resolve): number => setTimeout((): void => resolve(1 + 2), 1)
+  );
+  if (sum !== 3) {
+    throw new Error("1 + 2 should be 3");
+  }
+});
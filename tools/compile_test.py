@@ -0,0 +1,37 @@
+#!/usr/bin/env python
+# Copyright 2018-2019 the Deno authors. All rights reserved. MIT license.
+import os
+import shutil
+
+from test_util import DenoTestCase, run_tests
+from util import (executable_suffix, mkdtemp, root_path, tests_path,
+                  run_output)
+
+
+class TestCompile(DenoTestCase):
+    def test_compile(self):
+        d = mkdtemp()
+        try:
+            tool = os.path.join(d, "tool" + executable_suffix)
+            result = run_output([
+                os.path.join(root_path, self.deno_exe), "compile",
+                "--allow-env",
+                os.path.join(tests_path, "compile_main.ts"), "-o", tool
+            ],
+                                quiet=True)
+            self.assertEqual(result.code, 0)
+            self.assertTrue(os.path.isfile(tool))
+
+            # The compiled program runs with the permissions it was compiled
+            # with, and gets all arguments. It doesn't need the cache.
+            result = run_output([tool, "a", "--allow-net"],
+                                merge_env={"DENO_DIR": d},
+                                quiet=True)
+            self.assertEqual(result.code, 0)
+            self.assertEqual(result.out, "Hello\na --allow-net true false\n")
+        finally:
+            shutil.rmtree(d)
+
+
+if __name__ == "__main__":
+    run_tests()
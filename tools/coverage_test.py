@@ -0,0 +1,63 @@
+#!/usr/bin/env python
+# Copyright 2018-2019 the Deno authors. All rights reserved. MIT license.
+import os
+import shutil
+
+from test_util import DenoTestCase, run_tests
+from util import mkdtemp, root_path, tests_path, run_output
+
+
+class TestCoverage(DenoTestCase):
+    def setUp(self):
+        self.d = mkdtemp()
+
+    def tearDown(self):
+        shutil.rmtree(self.d)
+
+    def run_deno(self, *args):
+        deno_exe = os.path.join(root_path, self.deno_exe)
+        return run_output([deno_exe] + list(args), quiet=True)
+
+    def read_lcov(self):
+        with open(os.path.join(self.d, "lcov.info")) as f:
+            return f.read()
+
+    def test_run_coverage(self):
+        result = self.run_deno("run", "--reload", "--coverage=" + self.d,
+                               os.path.join(tests_path, "coverage/main.ts"))
+        self.assertEqual(result.code, 0)
+        self.assertEqual(result.out, "3\n")
+        # The line of sub() is covered, as its export statement ran.
+        self.assertIn("math.ts ... 66.67% (4/6), missed lines 6-7\n",
+                      result.err)
+        self.assertIn("main.ts ... 100.00% (2/2)\n", result.err)
+        self.assertIn("coverage: 75.00% (6/8 lines)\n", result.err)
+
+        lcov = self.read_lcov()
+        self.assertIn("FN:1,add\nFN:5,sub\nFNDA:1,add\nFNDA:0,sub\n", lcov)
+        self.assertIn("DA:2,1\nDA:3,1\nDA:5,1\nDA:6,0\nDA:7,0\n", lcov)
+
+    def test_run_coverage_exit(self):
+        # Deno.exit() doesn't let the program finish, but the coverage is
+        # written all the same.
+        result = self.run_deno("run", "--reload", "--coverage=" + self.d,
+                               os.path.join(tests_path, "coverage/exit.ts"))
+        self.assertEqual(result.code, 3)
+        self.assertEqual(result.out, "3\n")
+        self.assertIn("math.ts ... 66.67% (4/6), missed lines 6-7\n",
+                      result.err)
+        self.assertIn("DA:6,0\nDA:7,0\n", self.read_lcov())
+
+    def test_test_coverage(self):
+        result = self.run_deno("test", "--reload", "--coverage=" + self.d,
+                               os.path.join(tests_path, "coverage"))
+        self.assertEqual(result.code, 0)
+        self.assertIn("math.ts ... 66.67% (4/6), missed lines 2-3\n",
+                      result.err)
+        # The test modules themselves are left out.
+        self.assertNotIn("math_test.ts", result.err)
+        self.assertNotIn("math_test.ts", self.read_lcov())
+
+
+if __name__ == "__main__":
+    run_tests()
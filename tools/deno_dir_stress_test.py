@@ -0,0 +1,52 @@
+#!/usr/bin/env python
+# Copyright 2018-2019 the Deno authors. All rights reserved. MIT license.
+# Runs many deno processes at the same time against a single DENO_DIR, to check
+# that they don't observe each other's partially written cache files.
+import os
+import shutil
+import subprocess
+
+import http_server
+from test_util import DenoTestCase, run_tests
+from util import make_env, mkdtemp, tests_path
+
+PROCESSES = 8
+ROUNDS = 3
+
+
+class TestDenoDirStress(DenoTestCase):
+    def test_concurrent_processes(self):
+        deno_dir = mkdtemp()
+        try:
+            t = os.path.join(tests_path, "006_url_imports.ts")
+            env = make_env(merge_env={"DENO_DIR": deno_dir})
+            for i in range(ROUNDS):
+                # The first round downloads and compiles concurrently, the
+                # following ones read the cache while --reload rewrites it.
+                args = [self.deno_exe, "run", t]
+                if i > 0:
+                    args.insert(2, "--reload")
+                processes = [
+                    subprocess.Popen(
+                        args,
+                        env=env,
+                        stdout=subprocess.PIPE,
+                        stderr=subprocess.PIPE) for _ in range(PROCESSES)
+                ]
+                for p in processes:
+                    out, err = p.communicate()
+                    self.assertEqual(p.returncode, 0, err)
+                    self.assertEqual(out.replace("\r\n", "\n"),
+                                     "Hello\nsuccess\n")
+
+            # No temporary files are left behind.
+            for root, _, files in os.walk(deno_dir):
+                for f in files:
+                    assert not f.endswith(".tmp"), os.path.join(root, f)
+        finally:
+            shutil.rmtree(deno_dir)
+
+
+if __name__ == "__main__":
+    with http_server.spawn():
+        run_tests()
@@ -50,6 +50,40 @@ class ContentTypeHandler(QuietSimpleHTTPRequestHandler):
                       '\r\n--boundary--\r\n'
                       'Epilogue'))
             return
+        if "set_cookie" in self.path:
+            self.protocol_version = 'HTTP/1.1'
+            self.send_response(200, 'OK')
+            self.send_header('Set-Cookie', 'deno_test=1; Path=/')
+            self.send_header('Content-Length', '0')
+            self.end_headers()
+            return
+        if "echo_cookie" in self.path:
+            cookie = self.headers.getheader('cookie') or ''
+            self.protocol_version = 'HTTP/1.1'
+            self.send_response(200, 'OK')
+            self.send_header('Content-type', 'text/plain')
+            self.send_header('Content-Length', str(len(cookie)))
+            self.end_headers()
+            self.wfile.write(bytes(cookie))
+            return
+        if "etag_script.ts" in self.path:
+            etag = '"33a64df551425fcc55e"'
+            self.protocol_version = 'HTTP/1.1'
+            if self.headers.getheader('if-none-match') == etag:
+                self.send_response(304, 'Not Modified')
+                self.send_header('ETag', etag)
+                self.send_header('Content-Length', '0')
+                self.end_headers()
+                return
+            body = 'console.log("etag_script.ts");\n'
+            self.send_response(200, 'OK')
+            self.send_header('Content-type', 'application/typescript')
+            self.send_header('ETag', etag)
+            self.send_header('Cache-Control', 'max-age=3600')
+            self.send_header('Content-Length', str(len(body)))
+            self.end_headers()
+            self.wfile.write(bytes(body))
+            return
         return SimpleHTTPServer.SimpleHTTPRequestHandler.do_GET(self)
 
     def do_POST(self):
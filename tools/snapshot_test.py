@@ -0,0 +1,69 @@
+#!/usr/bin/env python
+# Copyright 2018-2019 the Deno authors. All rights reserved. MIT license.
+import os
+import shutil
+
+from test_util import DenoTestCase, run_tests
+from util import mkdtemp, root_path, tests_path, run_output
+
+
+class TestSnapshot(DenoTestCase):
+    def test_snapshot(self):
+        d = mkdtemp()
+        try:
+            deno_exe = os.path.join(root_path, self.deno_exe)
+            snap = os.path.join(d, "app.snap")
+            result = run_output([
+                deno_exe, "snapshot",
+                os.path.join(tests_path, "snapshot_main.ts"), "-o", snap
+            ],
+                                quiet=True)
+            self.assertEqual(result.code, 0)
+            # The dependency ran while the snapshot was made, the main module
+            # didn't.
+            self.assertIn("squares initialized\n", result.out)
+            self.assertNotIn("0,1,4,9,16", result.out)
+
+            result = run_output([
+                deno_exe, "run", "--snapshot=" + snap, "--allow-env", "a",
+                "b"
+            ],
+                                quiet=True)
+            self.assertEqual(result.code, 0)
+            self.assertEqual(result.out, "0,1,4,9,16\na b true\n")
+        finally:
+            shutil.rmtree(d)
+
+    def test_snapshot_ops(self):
+        d = mkdtemp()
+        try:
+            deno_exe = os.path.join(root_path, self.deno_exe)
+            snap = os.path.join(d, "app.snap")
+            args = [
+                deno_exe, "snapshot",
+                os.path.join(tests_path, "snapshot_ops.ts"), "-o", snap
+            ]
+            # The dependency reads a file at the top level, which needs the
+            # permissions given to deno snapshot.
+            result = run_output(args, quiet=True, cwd=root_path)
+            self.assertNotEqual(result.code, 0)
+            self.assertIn("PermissionDenied", result.err)
+
+            result = run_output(
+                args[:2] + ["--allow-read"] + args[2:],
+                quiet=True,
+                cwd=root_path)
+            self.assertEqual(result.code, 0)
+
+            # The file was read while the snapshot was made.
+            result = run_output([deno_exe, "run", "--snapshot=" + snap],
+                                quiet=True,
+                                cwd=d)
+            self.assertEqual(result.code, 0)
+            self.assertEqual(result.out, "Hello world!\n")
+        finally:
+            shutil.rmtree(d)
+
+
+if __name__ == "__main__":
+    run_tests()
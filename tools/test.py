@@ -5,15 +5,20 @@
 import os
 
 from benchmark_test import TestBenchmark
+from compile_test import TestCompile
+from coverage_test import TestCoverage
 from deno_dir_test import TestDenoDir
+from deno_dir_stress_test import TestDenoDirStress
 from fetch_test import TestFetch
 from fmt_test import TestFmt
 from integration_tests import TestIntegrations
 from repl_test import TestRepl
 from setup_test import TestSetup
+from snapshot_test import TestSnapshot
 from target_test import TestTarget
 from unit_tests import JsUnitTests
 from util_test import TestUtil
+from watch_test import TestWatch
 # NOTE: These tests are skipped on Windows
 from is_tty_test import TestIsTty
 from permission_prompt_test import permission_prompt_tests
@@ -42,9 +47,14 @@ def main():
         JsUnitTests,
         TestFetch,
         TestFmt,
+        TestCompile,
+        TestSnapshot,
+        TestWatch,
+        TestCoverage,
         TestIntegrations,
         TestRepl,
         TestDenoDir,
+        TestDenoDirStress,
         TestBenchmark,
         TestIsTty,
     ]
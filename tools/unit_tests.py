@@ -11,6 +11,7 @@ class JsUnitTests(DenoTestCase):
     def test_unit_test_runner(self):
         cmd = [
             self.deno_exe, "run", "--reload", "--allow-run",
+            "--importmap=js/unit_test_import_map.json",
             "js/unit_test_runner.ts"
         ]
         process = subprocess.Popen(
@@ -0,0 +1,110 @@
+#!/usr/bin/env python
+# Copyright 2018-2019 the Deno authors. All rights reserved. MIT license.
+import os
+import shutil
+from subprocess import PIPE, Popen
+from threading import Thread
+import time
+
+from test_util import DenoTestCase, run_tests
+from util import mkdtemp
+
+
+def write_file(path, content):
+    with open(path, "w") as f:
+        f.write(content)
+
+
+def read_file(path):
+    if not os.path.exists(path):
+        return ""
+    with open(path) as f:
+        return f.read()
+
+
+class TestWatch(DenoTestCase):
+    def setUp(self):
+        self.d = mkdtemp()
+        self.main = os.path.join(self.d, "main.ts")
+        self.dep = os.path.join(self.d, "dep.ts")
+        write_file(self.dep, 'export const message = "first";\n')
+        write_file(
+            self.main,
+            'import { message } from "./dep.ts";\nconsole.log(message);\n')
+
+    def tearDown(self):
+        shutil.rmtree(self.d)
+
+    def spawn(self, *args):
+        return Popen([self.deno_exe] + list(args), stdout=PIPE, stderr=PIPE)
+
+    def read_lines(self, p):
+        """Collects the lines which p prints in a thread, so the test can wait
+        for them with a deadline instead of blocking on readline()."""
+        lines = []
+
+        def read():
+            for line in iter(p.stdout.readline, b""):
+                lines.append(line.strip())
+
+        thread = Thread(target=read)
+        thread.daemon = True
+        thread.start()
+        return lines
+
+    def test_run_watch(self):
+        p = self.spawn("run", "--watch", self.main)
+        try:
+            lines = self.read_lines(p)
+            self.wait_for(lambda: len(lines) >= 1)
+            self.assertEqual(lines[0], b"first")
+            write_file(self.dep, 'export const message = "second";\n')
+            self.wait_for(lambda: len(lines) >= 2)
+            self.assertEqual(lines[1], b"second")
+        finally:
+            p.kill()
+            p.wait()
+
+    def test_bundle_watch(self):
+        out = os.path.join(self.d, "bundle.js")
+        p = self.spawn("bundle", "--watch", self.main, out)
+        try:
+            self.wait_for(lambda: '"first"' in read_file(out))
+            write_file(self.dep, 'export const message = "second";\n')
+            self.wait_for(lambda: '"second"' in read_file(out))
+        finally:
+            p.kill()
+            p.wait()
+
+    def test_test_watch(self):
+        write_file(
+            os.path.join(self.d, "dep_test.ts"),
+            'import { message } from "./dep.ts";\n'
+            'Deno.test(function dep(): void {\n'
+            '  if (message !== "first") throw new Error(message);\n'
+            '});\n')
+        p = self.spawn("test", "--watch", self.d)
+
+        def has_result(lines, status):
+            return any(
+                line.startswith(b"test dep ...") and status in line
+                for line in lines)
+
+        try:
+            lines = self.read_lines(p)
+            self.wait_for(lambda: has_result(lines, b"ok"))
+            write_file(self.dep, 'export const message = "second";\n')
+            self.wait_for(lambda: has_result(lines, b"FAILED"))
+        finally:
+            p.kill()
+            p.wait()
+
+    def wait_for(self, condition, timeout=10):
+        deadline = time.time() + timeout
+        while not condition():
+            self.assertLess(time.time(), deadline)
+            time.sleep(0.1)
+
+
+if __name__ == "__main__":
+    run_tests()